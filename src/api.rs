@@ -0,0 +1,202 @@
+//! The interface for using the oracle from other programs. Unlike `run`,
+//! nothing here prints: results and errors are returned.
+//!
+//! ```no_run
+//! use mlb_oracle::{settings::Settings, Oracle};
+//!
+//! let oracle = Oracle::new(Settings::default())?;
+//! for game in oracle.schedule(mlb_oracle::dates::today())? {
+//!     let prediction = oracle.predict(&game)?;
+//!     if let Some(p) = prediction.home_win_probability() {
+//!         println!("{} @ {}: {:.3}", game.away_name, game.home_name, p);
+//!     }
+//! }
+//! # Ok::<(), String>(())
+//! ```
+
+use chrono::NaiveDate;
+
+use crate::{
+    config::Config,
+    history,
+    mlbstats::{self, Feed, Game, Situation, Team},
+    settings::Settings,
+    simba::SimResult,
+};
+
+/// Fetches games and lineups from the Stats API, and simulates games
+#[derive(Clone)]
+pub struct Oracle {
+    cfg: Config,
+}
+
+/// What became of a prediction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Predicted,
+    /// The game was simulated, but no simulated game was played to the end,
+    /// e.g. with no iterations
+    NoPrediction,
+    Postponed,
+    /// The lineup of one team or both, as flagged, is not posted yet
+    MissingLineups { away: bool, home: bool },
+}
+
+/// A game's prediction, and what it was made from
+#[derive(Clone, Debug)]
+pub struct Prediction {
+    pub game_id: String,
+    pub outcome: Outcome,
+    /// Starters and lineups, away first, unless a lineup is missing
+    pub teams: Option<[Team; 2]>,
+    /// The state the game was simulated from, if it was in progress
+    pub situation: Option<Situation>,
+    /// The simulations, unless the game could not be simulated
+    pub result: Option<SimResult>,
+    /// See `history::model_version`
+    pub model_version: String,
+}
+
+impl Prediction {
+    pub fn home_win_probability(&self) -> Option<f64> {
+        self.result.as_ref()?.home_win_probability
+    }
+
+    pub fn away_win_probability(&self) -> Option<f64> {
+        self.home_win_probability().map(|p| 1.0 - p)
+    }
+
+    /// Expected final runs, away first
+    pub fn expected_runs(&self) -> Option<[f64; 2]> {
+        let r = self.result.as_ref()?;
+        Some([r.expected_away_runs, r.expected_home_runs])
+    }
+}
+
+impl Oracle {
+    /// An oracle with the built-in defaults, overridden by `settings`, e.g.
+    /// as read by `Settings::read` from a config file
+    pub fn new(settings: Settings) -> Result<Oracle, String> {
+        Ok(Oracle {
+            cfg: Config::from_settings(settings)?,
+        })
+    }
+
+    /// An oracle with a configuration as assembled by the command line
+    pub fn with_config(cfg: Config) -> Oracle {
+        Oracle { cfg }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.cfg
+    }
+
+    /// The games on a date, with their status and score
+    pub fn schedule(&self, date: NaiveDate) -> Result<Vec<Game>, String> {
+        let cfg = Config {
+            date: date.format("%F").to_string(),
+            ..self.cfg.clone()
+        };
+        mlbstats::schedule(&cfg)
+    }
+
+    /// The starters and lineups of a game, away first, once they are posted
+    pub fn teams(&self, game_id: &str) -> Result<(Option<Team>, Option<Team>), String> {
+        mlbstats::teams(&self.cfg, game_id)
+    }
+
+    /// Simulate a game, from its current situation if it is in progress
+    pub fn predict(&self, game: &Game) -> Result<Prediction, String> {
+        if game.status == "Postponed" {
+            return Ok(self.unsimulated(game, Outcome::Postponed));
+        }
+        self.predict_from(game, &mlbstats::feed(&self.cfg, &game.game_id)?)
+    }
+
+    /// `predict` from a feed already fetched, e.g. to read the game from it
+    pub(crate) fn predict_from(&self, game: &Game, feed: &Feed) -> Result<Prediction, String> {
+        let (away, home) = match feed.teams(&self.cfg)? {
+            (Some(away), Some(home)) => (away, home),
+            (away, home) => {
+                let outcome = Outcome::MissingLineups {
+                    away: away.is_none(),
+                    home: home.is_none(),
+                };
+                return Ok(self.unsimulated(game, outcome));
+            }
+        };
+        let mut prediction = self.unsimulated(game, Outcome::Predicted);
+        if game.is_live() {
+            prediction.situation = feed.situation(&self.cfg)?;
+        }
+        let result = crate::simulate(&self.cfg, game, &away, &home, prediction.situation.as_ref())?;
+        prediction.outcome = match result.home_win_probability {
+            Some(_) => Outcome::Predicted,
+            None => Outcome::NoPrediction,
+        };
+        prediction.teams = Some([away, home]);
+        prediction.result = Some(result);
+        Ok(prediction)
+    }
+
+    fn unsimulated(&self, game: &Game, outcome: Outcome) -> Prediction {
+        Prediction {
+            game_id: game.game_id.clone(),
+            outcome,
+            teams: None,
+            situation: None,
+            result: None,
+            model_version: history::model_version(&self.cfg),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fixtures, simba::Score};
+
+    #[test]
+    fn api_settings() {
+        let settings = Settings {
+            iterations: Some(500),
+            seed: Some(7),
+            ..Settings::default()
+        };
+        let oracle = Oracle::new(settings).unwrap();
+        assert_eq!(oracle.config().iterations, 500);
+        assert_eq!(oracle.config().seed, Some(7));
+        assert_eq!(oracle.config().history, None);
+        let settings = Settings {
+            home_advantage: Some(2.0),
+            ..Settings::default()
+        };
+        assert!(Oracle::new(settings).is_err());
+    }
+
+    #[test]
+    fn api_predict() {
+        let oracle = Oracle::new(Settings::default()).unwrap();
+        let game = Game {
+            status: "Postponed".to_string(),
+            abstract_status: "Final".to_string(),
+            ..fixtures::game("Chicago Cubs", "Texas Rangers")
+        };
+        // Postponed games are not looked up
+        let mut prediction = oracle.predict(&game).unwrap();
+        assert_eq!(prediction.outcome, Outcome::Postponed);
+        assert_eq!(prediction.home_win_probability(), None);
+        assert_eq!(prediction.model_version, history::model_version(oracle.config()));
+
+        prediction.result = Some(SimResult {
+            home_win_probability: Some(0.75),
+            expected_away_runs: 3.5,
+            expected_home_runs: 5.0,
+            iterations: 4,
+            seed: 1,
+            scores: vec![(Score { away: 3, home: 5 }, 4)],
+        });
+        assert_eq!(prediction.away_win_probability(), Some(0.25));
+        assert_eq!(prediction.expected_runs(), Some([3.5, 5.0]));
+    }
+}
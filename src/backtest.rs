@@ -0,0 +1,249 @@
+use std::fs;
+use std::path::Path;
+
+use chrono::NaiveDate;
+use log;
+
+use crate::{
+    calibration::{self, Bin},
+    config::Config,
+    filter::Slate,
+    mlbstats,
+    output::csv_field,
+    simulate,
+};
+
+/// Probabilities are clamped this far away from 0 and 1 in the log loss
+const EPSILON: f64 = 1e-6;
+
+/// Number of equal-width bins of predicted probability
+const N_BINS: usize = 10;
+
+/// A past game, its prediction and its result
+#[derive(Clone, Debug)]
+pub struct GameResult {
+    pub date: NaiveDate,
+    pub game_id: String,
+    pub away_name: String,
+    pub home_name: String,
+    pub home_win_probability: f64,
+    pub away_score: i32,
+    pub home_score: i32,
+}
+
+impl GameResult {
+    pub fn home_won(&self) -> bool {
+        self.home_score > self.away_score
+    }
+}
+
+#[derive(Debug)]
+pub struct Summary {
+    pub games: usize,
+    /// Fraction of games where the favorite won
+    pub accuracy: f64,
+    pub brier: f64,
+    pub log_loss: f64,
+    pub bins: Vec<Bin>,
+    pub ece: f64,
+}
+
+pub fn summarize(results: &[GameResult]) -> Summary {
+    let n = results.len() as f64;
+    let mut correct = 0;
+    let mut brier = 0.0;
+    let mut log_loss = 0.0;
+    for res in results {
+        let p = res.home_win_probability;
+        let y = if res.home_won() { 1.0 } else { 0.0 };
+        if (p > 0.5) == res.home_won() {
+            correct += 1;
+        }
+        brier += (p - y).powi(2);
+        let pc = p.clamp(EPSILON, 1.0 - EPSILON);
+        log_loss -= y * pc.ln() + (1.0 - y) * (1.0 - pc).ln();
+    }
+
+    let obs: Vec<_> = results
+        .iter()
+        .map(|r| (r.home_win_probability, r.home_won()))
+        .collect();
+    let bins = calibration::reliability(&obs, N_BINS);
+
+    Summary {
+        games: results.len(),
+        accuracy: correct as f64 / n,
+        brier: brier / n,
+        log_loss: log_loss / n,
+        ece: calibration::expected_calibration_error(&bins),
+        bins,
+    }
+}
+
+impl Summary {
+    pub fn print(&self) {
+        println!("Games:    {}", self.games);
+        println!("Accuracy: {:.3}", self.accuracy);
+        println!("Brier:    {:.4}", self.brier);
+        println!("Log loss: {:.4}", self.log_loss);
+        println!();
+        calibration::print_reliability(&self.bins);
+    }
+}
+
+fn write_csv(path: &Path, results: &[GameResult]) -> Result<(), String> {
+    let mut csv = String::from(
+        "date,game_id,away_name,home_name,home_win_probability,away_score,home_score,home_won\n",
+    );
+    for r in results {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{},{}\n",
+            r.date,
+            csv_field(&r.game_id),
+            csv_field(&r.away_name),
+            csv_field(&r.home_name),
+            r.home_win_probability,
+            r.away_score,
+            r.home_score,
+            r.home_won() as i32
+        ));
+    }
+    fs::write(path, csv).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Predict every completed game of one day, as of that day
+fn backtest_date(cfg: &Config, slate: &Slate, date: NaiveDate) -> Result<Vec<GameResult>, String> {
+    let cfg = Config {
+        date: date.format("%F").to_string(),
+        as_of: Some(date),
+        ..cfg.clone()
+    };
+
+    let mut results = Vec::new();
+    for game in slate.games(&cfg)? {
+        let (Some(away_score), Some(home_score)) = (game.away_score, game.home_score) else {
+            continue;
+        };
+        if !game.is_final() || game.status == "Postponed" || away_score == home_score {
+            continue;
+        }
+
+        // A game that cannot be predicted, e.g. because of a debut with no
+        // prior stats, is left out rather than ending the backtest
+        let teams = match mlbstats::teams(&cfg, &game.game_id) {
+            Ok((Some(away), Some(home))) => (away, home),
+            Ok(_) => {
+                log::warn!(target: "backtest", "{}: missing lineups", game.game_id);
+                continue;
+            }
+            Err(e) => {
+                log::warn!(target: "backtest", "{}: {}", game.game_id, e);
+                continue;
+            }
+        };
+        let hwp = match simulate(&cfg, &game, &teams.0, &teams.1, None) {
+            Ok(res) => res.home_win_probability,
+            Err(e) => {
+                log::warn!(target: "backtest", "{}: {}", game.game_id, e);
+                None
+            }
+        };
+        let Some(hwp) = hwp.filter(|p| p.is_finite()) else {
+            continue;
+        };
+
+        results.push(GameResult {
+            date,
+            game_id: game.game_id,
+            away_name: game.away_name,
+            home_name: game.home_name,
+            home_win_probability: hwp,
+            away_score,
+            home_score,
+        });
+    }
+    Ok(results)
+}
+
+pub fn backtest(
+    cfg: &Config,
+    from: NaiveDate,
+    to: NaiveDate,
+    output: Option<&Path>,
+) -> Result<(), String> {
+    // Clubs are looked up in the standings of the first season
+    let slate = Slate::new(&Config {
+        date: from.format("%F").to_string(),
+        ..cfg.clone()
+    })?;
+    let mut results = Vec::new();
+    for date in from.iter_days().take_while(|d| *d <= to) {
+        match backtest_date(cfg, &slate, date) {
+            Ok(res) => {
+                eprintln!("[backtest] {}: {} games", date, res.len());
+                results.extend(res);
+            }
+            Err(e) => eprintln!("[WARNING] {}: {}", date, e),
+        }
+    }
+
+    if let Some(path) = output {
+        write_csv(path, &results)?;
+    }
+    if results.is_empty() {
+        return Err("No completed games to score in this date range".to_string());
+    }
+    summarize(&results).print();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(hwp: f64, away: i32, home: i32) -> GameResult {
+        GameResult {
+            date: NaiveDate::from_ymd_opt(2023, 4, 1).unwrap(),
+            game_id: "1".to_string(),
+            away_name: "Away".to_string(),
+            home_name: "Home".to_string(),
+            home_win_probability: hwp,
+            away_score: away,
+            home_score: home,
+        }
+    }
+
+    #[test]
+    fn test_summarize() {
+        let results = [
+            result(0.8, 1, 2),
+            result(0.7, 3, 2),
+            result(0.3, 5, 4),
+            result(0.25, 1, 0),
+        ];
+        let s = summarize(&results);
+        assert_eq!(s.games, 4);
+        assert!((s.accuracy - 0.75).abs() < 1e-12);
+        let brier = (0.04 + 0.49 + 0.09 + 0.0625) / 4.0;
+        assert!((s.brier - brier).abs() < 1e-12);
+        let log_loss = -(0.8f64.ln() + 0.3f64.ln() + 0.7f64.ln() + 0.75f64.ln()) / 4.0;
+        assert!((s.log_loss - log_loss).abs() < 1e-12);
+
+        assert_eq!(s.bins.len(), N_BINS);
+        assert_eq!(s.bins[2].count, 1);
+        assert_eq!(s.bins[3].count, 1);
+        assert_eq!(s.bins[7].count, 1);
+        assert_eq!(s.bins[8].count, 1);
+        assert_eq!(s.bins[8].observed, 1.0);
+        assert_eq!(s.bins[7].observed, 0.0);
+        assert!((s.ece - (0.2 + 0.7 + 0.3 + 0.25) / 4.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_summarize_edges() {
+        let s = summarize(&[result(1.0, 0, 1), result(0.0, 0, 1)]);
+        assert_eq!(s.bins[N_BINS - 1].count, 1);
+        assert_eq!(s.bins[0].count, 1);
+        assert!(s.log_loss.is_finite());
+    }
+}
@@ -1,36 +0,0 @@
-use std::env;
-use std::error::Error;
-
-use chrono::NaiveDate;
-
-use mlb_oracle::{
-    config::Config,
-    mlbstats,
-    utils
-};
-
-fn get_datestr() -> Result<String, Box<dyn Error>> {
-    let args: Vec<_> = env::args().collect();
-    let datestr = if args.len() >= 2 {
-        NaiveDate::parse_from_str(&args[1], "%F")?
-            .format("%F")
-            .to_string()
-    } else {
-        chrono::offset::Local::now().format("%F").to_string()
-    };
-    Ok(datestr)
-}
-
-fn main() -> Result<(), Box<dyn Error>> {
-    let datestr = get_datestr()?;
-    let cfg = Config {
-        date: datestr,
-        verbose: true,
-    };
-
-    utils::init_log(cfg.verbose);
-
-    let sched = mlbstats::schedule(&cfg)?;
-    println!("{:#?}", sched);
-    Ok(())
-}
@@ -0,0 +1,387 @@
+use std::fs;
+use std::path::Path;
+
+use serde_json as json;
+
+use crate::output::csv_split;
+
+/// Probabilities are kept this far away from 0 and 1 before taking logits
+const EPSILON: f64 = 1e-6;
+
+/// A predicted home win probability and whether the home team won
+pub type Observation = (f64, bool);
+
+#[derive(Debug, PartialEq)]
+pub struct Bin {
+    pub lower: f64,
+    pub upper: f64,
+    pub count: usize,
+    /// Mean predicted probability
+    pub predicted: f64,
+    /// Fraction of positive outcomes
+    pub observed: f64,
+}
+
+/// Reliability table over `n_bins` equal-width bins of predicted probability
+pub fn reliability(obs: &[Observation], n_bins: usize) -> Vec<Bin> {
+    let mut bins: Vec<Bin> = (0..n_bins)
+        .map(|i| Bin {
+            lower: i as f64 / n_bins as f64,
+            upper: (i + 1) as f64 / n_bins as f64,
+            count: 0,
+            predicted: 0.0,
+            observed: 0.0,
+        })
+        .collect();
+    for &(p, y) in obs {
+        let bin = &mut bins[((p * n_bins as f64) as usize).min(n_bins - 1)];
+        bin.count += 1;
+        bin.predicted += p;
+        bin.observed += if y { 1.0 } else { 0.0 };
+    }
+    for bin in bins.iter_mut().filter(|b| b.count > 0) {
+        bin.predicted /= bin.count as f64;
+        bin.observed /= bin.count as f64;
+    }
+    bins
+}
+
+/// Expected calibration error: the gap between predicted and observed
+/// rates, averaged over bins weighted by their number of predictions
+pub fn expected_calibration_error(bins: &[Bin]) -> f64 {
+    let n: usize = bins.iter().map(|b| b.count).sum();
+    if n == 0 {
+        return 0.0;
+    }
+    bins.iter()
+        .map(|b| b.count as f64 * (b.predicted - b.observed).abs())
+        .sum::<f64>()
+        / n as f64
+}
+
+pub fn print_reliability(bins: &[Bin]) {
+    println!("{:<9} {:>6} {:>10} {:>9}", "Bin", "Games", "Predicted", "Observed");
+    for bin in bins.iter().filter(|b| b.count > 0) {
+        println!(
+            "{:.1} - {:.1} {:>6} {:>10.3} {:>9.3}",
+            bin.lower, bin.upper, bin.count, bin.predicted, bin.observed
+        );
+    }
+    println!("ECE: {:.4}", expected_calibration_error(bins));
+}
+
+fn logit(p: f64) -> f64 {
+    let p = p.clamp(EPSILON, 1.0 - EPSILON);
+    (p / (1.0 - p)).ln()
+}
+
+fn sigmoid(x: f64) -> f64 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+/// Map from raw to calibrated probabilities
+#[derive(Clone, Debug, PartialEq)]
+pub enum Recalibration {
+    /// sigmoid(a * logit(p) + b)
+    Platt { a: f64, b: f64 },
+    /// Monotone piecewise-linear map through the given points, with x
+    /// strictly increasing and y non-decreasing
+    Isotonic { points: Vec<(f64, f64)> },
+}
+
+impl Recalibration {
+    /// Fit a Platt scaling by maximum likelihood, with Newton's method
+    pub fn fit_platt(obs: &[Observation]) -> Recalibration {
+        let (mut a, mut b) = (1.0, 0.0);
+        for _ in 0..100 {
+            // Gradient and Hessian of the log loss
+            let (mut ga, mut gb) = (0.0, 0.0);
+            let (mut haa, mut hab, mut hbb) = (0.0, 0.0, 0.0);
+            for &(p, y) in obs {
+                let x = logit(p);
+                let q = sigmoid(a * x + b);
+                let r = q - if y { 1.0 } else { 0.0 };
+                let w = q * (1.0 - q);
+                ga += r * x;
+                gb += r;
+                haa += w * x * x;
+                hab += w * x;
+                hbb += w;
+            }
+            let det = haa * hbb - hab * hab;
+            if det.abs() < 1e-12 {
+                break;
+            }
+            let da = (hbb * ga - hab * gb) / det;
+            let db = (haa * gb - hab * ga) / det;
+            a -= da;
+            b -= db;
+            if da.abs() < 1e-10 && db.abs() < 1e-10 {
+                break;
+            }
+        }
+        Recalibration::Platt { a, b }
+    }
+
+    /// Fit an isotonic regression with the pool-adjacent-violators algorithm
+    pub fn fit_isotonic(obs: &[Observation]) -> Recalibration {
+        let mut sorted: Vec<_> = obs.to_vec();
+        sorted.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        // Blocks of (sum of x, sum of y, count), tied predictions pooled
+        // first so that the map does not depend on their order
+        let mut blocks: Vec<(f64, f64, f64)> = Vec::new();
+        for tied in sorted.chunk_by(|a, b| a.0 == b.0) {
+            let (mut x, mut y, mut n) = (0.0, 0.0, 0.0);
+            for &(p, won) in tied {
+                x += p;
+                y += if won { 1.0 } else { 0.0 };
+                n += 1.0;
+            }
+            blocks.push((x, y, n));
+            while blocks.len() > 1 {
+                let (x1, y1, n1) = blocks[blocks.len() - 1];
+                let (x0, y0, n0) = blocks[blocks.len() - 2];
+                if y0 / n0 < y1 / n1 {
+                    break;
+                }
+                blocks.pop();
+                *blocks.last_mut().unwrap() = (x0 + x1, y0 + y1, n0 + n1);
+            }
+        }
+        Recalibration::Isotonic {
+            points: blocks.iter().map(|(x, y, n)| (x / n, y / n)).collect(),
+        }
+    }
+
+    pub fn apply(&self, p: f64) -> f64 {
+        match self {
+            Recalibration::Platt { a, b } => sigmoid(a * logit(p) + b),
+            Recalibration::Isotonic { points } => {
+                let Some(first) = points.first() else {
+                    return p;
+                };
+                if p <= first.0 {
+                    return first.1;
+                }
+                for w in points.windows(2) {
+                    let ((x0, y0), (x1, y1)) = (w[0], w[1]);
+                    if p <= x1 {
+                        return y0 + (y1 - y0) * (p - x0) / (x1 - x0);
+                    }
+                }
+                points.last().unwrap().1
+            }
+        }
+    }
+
+    pub fn to_json(&self) -> json::Value {
+        match self {
+            Recalibration::Platt { a, b } => json::json!({
+                "method": "platt",
+                "a": a,
+                "b": b,
+            }),
+            Recalibration::Isotonic { points } => json::json!({
+                "method": "isotonic",
+                "x": points.iter().map(|p| p.0).collect::<Vec<_>>(),
+                "y": points.iter().map(|p| p.1).collect::<Vec<_>>(),
+            }),
+        }
+    }
+
+    pub fn from_json(value: &json::Value) -> Result<Recalibration, String> {
+        let num = |k: &str| {
+            value[k]
+                .as_f64()
+                .ok_or(format!("Recalibration map: missing number \"{}\"", k))
+        };
+        let nums = |k: &str| -> Result<Vec<f64>, String> {
+            value[k]
+                .as_array()
+                .ok_or(format!("Recalibration map: missing array \"{}\"", k))?
+                .iter()
+                .map(|v| v.as_f64().ok_or(format!("Recalibration map: bad \"{}\"", k)))
+                .collect()
+        };
+        match value["method"].as_str() {
+            Some("platt") => Ok(Recalibration::Platt {
+                a: num("a")?,
+                b: num("b")?,
+            }),
+            Some("isotonic") => {
+                let (x, y) = (nums("x")?, nums("y")?);
+                if x.len() != y.len() {
+                    return Err("Recalibration map: x and y differ in length".to_string());
+                }
+                if !x.windows(2).all(|w| w[0] < w[1]) {
+                    return Err("Recalibration map: x is not strictly increasing".to_string());
+                }
+                if !y.windows(2).all(|w| w[0] <= w[1]) {
+                    return Err("Recalibration map: y is not non-decreasing".to_string());
+                }
+                Ok(Recalibration::Isotonic {
+                    points: x.into_iter().zip(y).collect(),
+                })
+            }
+            _ => Err("Recalibration map: unknown method".to_string()),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        fs::write(path, format!("{:#}\n", self.to_json()))
+            .map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn load(path: &Path) -> Result<Recalibration, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let value = json::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Recalibration::from_json(&value).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+/// Read the predictions and outcomes of a backtest CSV file
+pub fn read_observations(path: &Path) -> Result<Vec<Observation>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut lines = text.lines();
+    let header = csv_split(lines.next().unwrap_or(""));
+    let column = |name: &str| {
+        header
+            .iter()
+            .position(|h| h == name)
+            .ok_or(format!("{}: no \"{}\" column", path.display(), name))
+    };
+    let (p_col, y_col) = (column("home_win_probability")?, column("home_won")?);
+
+    let mut obs = Vec::new();
+    for (n, line) in lines.enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+        let row = csv_split(line);
+        let bad = || format!("{}: line {}: bad row", path.display(), n + 2);
+        let p: f64 = row.get(p_col).and_then(|v| v.parse().ok()).ok_or_else(bad)?;
+        let y = match row.get(y_col).map(|v| v.as_str()) {
+            Some("1") => true,
+            Some("0") => false,
+            _ => return Err(bad()),
+        };
+        obs.push((p, y));
+    }
+    Ok(obs)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Method {
+    Platt,
+    Isotonic,
+}
+
+/// Print the reliability of past predictions, and optionally fit and save
+/// a recalibration map
+pub fn calibrate(
+    input: &Path,
+    n_bins: usize,
+    method: Method,
+    output: Option<&Path>,
+) -> Result<(), String> {
+    let obs = read_observations(input)?;
+    if obs.is_empty() {
+        return Err(format!("{}: no predictions", input.display()));
+    }
+    println!("{} predictions", obs.len());
+    print_reliability(&reliability(&obs, n_bins));
+
+    if let Some(output) = output {
+        let map = match method {
+            Method::Platt => Recalibration::fit_platt(&obs),
+            Method::Isotonic => Recalibration::fit_isotonic(&obs),
+        };
+        let recalibrated: Vec<_> = obs.iter().map(|&(p, y)| (map.apply(p), y)).collect();
+        println!();
+        println!("After recalibration (in sample):");
+        print_reliability(&reliability(&recalibrated, n_bins));
+        map.save(output)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reliability() {
+        let obs = [(0.05, false), (0.15, false), (0.12, true), (0.95, true), (1.0, true)];
+        let bins = reliability(&obs, 10);
+        assert_eq!(bins[0].count, 1);
+        assert_eq!(bins[1].count, 2);
+        assert!((bins[1].predicted - 0.135).abs() < 1e-12);
+        assert_eq!(bins[1].observed, 0.5);
+        assert_eq!(bins[9].count, 2);
+        let ece = (0.05 + 2.0 * 0.365 + 2.0 * 0.025) / 5.0;
+        assert!((expected_calibration_error(&bins) - ece).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_platt() {
+        // Overconfident predictions: 80% calls that win 60% of the time
+        let mut obs = Vec::new();
+        for i in 0..100 {
+            obs.push((0.8, i < 60));
+            obs.push((0.2, i >= 60));
+        }
+        let map = Recalibration::fit_platt(&obs);
+        assert!((map.apply(0.8) - 0.6).abs() < 1e-6);
+        assert!((map.apply(0.2) - 0.4).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_isotonic() {
+        let obs = [(0.1, false), (0.2, true), (0.3, false), (0.4, true), (0.5, true)];
+        let map = Recalibration::fit_isotonic(&obs);
+        let Recalibration::Isotonic { points } = &map else {
+            panic!("expected an isotonic map");
+        };
+        assert_eq!(points.len(), 3);
+        assert!(points.windows(2).all(|w| w[0].1 <= w[1].1));
+        assert_eq!(map.apply(0.0), 0.0);
+        assert!((map.apply(0.25) - 0.5).abs() < 1e-12);
+        assert_eq!(map.apply(0.9), 1.0);
+    }
+
+    #[test]
+    fn test_isotonic_ties() {
+        let obs = [(0.2, true), (0.2, false), (0.2, false), (0.6, true)];
+        let map = Recalibration::fit_isotonic(&obs);
+        let mut reversed = obs;
+        reversed.reverse();
+        assert_eq!(Recalibration::fit_isotonic(&reversed), map);
+        let Recalibration::Isotonic { points } = &map else {
+            panic!("expected an isotonic map");
+        };
+        assert_eq!(points.len(), 2);
+        assert!((points[0].0 - 0.2).abs() < 1e-12);
+        assert!((points[0].1 - 1.0 / 3.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_json_roundtrip() {
+        let maps = [
+            Recalibration::Platt { a: 0.8, b: -0.1 },
+            Recalibration::Isotonic {
+                points: vec![(0.1, 0.2), (0.6, 0.55)],
+            },
+        ];
+        for map in maps {
+            assert_eq!(Recalibration::from_json(&map.to_json()).unwrap(), map);
+        }
+        assert!(Recalibration::from_json(&json::json!({"method": "magic"})).is_err());
+        let bad = [
+            ([0.6, 0.1], [0.2, 0.55]),
+            ([0.1, 0.1], [0.2, 0.55]),
+            ([0.1, 0.6], [0.55, 0.2]),
+        ];
+        for (x, y) in bad {
+            let value = json::json!({"method": "isotonic", "x": x, "y": y});
+            assert!(Recalibration::from_json(&value).is_err());
+        }
+    }
+}
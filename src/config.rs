@@ -1,47 +1,914 @@
+use std::env;
+use std::path::PathBuf;
+use std::time::Duration;
+
 use chrono;
 use chrono::NaiveDate;
-use clap::Parser;
+use chrono_tz::Tz;
+use clap::{Args, Parser, Subcommand};
+
+use crate::calibration::{Method, Recalibration};
+use crate::dates;
+use crate::filter::{Division, Filter, League};
+use crate::lineup::{Objective, Side};
+use crate::scenario::{Change, Scenario};
+use crate::series;
+use crate::mlbstats::{StatSource, DEFAULT_API_URL};
+use crate::output::{ColorChoice, Colormap, Display, Format};
+use crate::park::{ParkTable, Venue, DEFAULT_HOME_ADVANTAGE};
+use crate::settings::Settings;
+use crate::simba::{Fatigue, Matchup, MatchupModel};
 
+#[derive(Clone)]
 pub struct Config {
     pub date: String,
     pub verbose: bool,
+    pub mode: Mode,
+    pub format: Format,
+    pub color: ColorChoice,
+    pub display: Display,
+    pub iterations: usize,
+    pub seed: Option<u64>,
+    /// Where to keep API responses for reuse, if anywhere
+    pub cache_dir: Option<PathBuf>,
+    /// Only use cached API responses
+    pub offline: bool,
+    /// Use the stats players had on this date instead of their current ones
+    pub as_of: Option<NaiveDate>,
+    /// Applied to every predicted home win probability
+    pub recalibration: Option<Recalibration>,
+    /// Where predictions are recorded, if anywhere
+    pub history: Option<PathBuf>,
+    pub park_factors: ParkTable,
+    /// See `Venue::home_advantage`
+    pub home_advantage: f64,
+    pub fatigue: Fatigue,
+    pub matchup: Matchup,
+    pub stats: StatSource,
+    pub colormap: Colormap,
+    pub api_url: String,
+    /// Which games of the slate to keep
+    pub filter: Filter,
+    /// Where game times are shown, or None for the local time zone
+    pub tz: Option<Tz>,
+}
+
+#[derive(Clone)]
+pub enum Mode {
+    /// Print one prediction per game and exit
+    Predict,
+    /// Keep refreshing predictions as games progress
+    Watch { interval: Duration, swing: f64 },
+    /// Browse the games of a day in a full-screen dashboard
+    Dashboard { interval: Duration },
+    /// Break down the prediction of a single game
+    Explain { game_id: String },
+    /// Replay Retrosheet event files through the simulator
+    Replay { paths: Vec<PathBuf> },
+    /// Predict past games and score the predictions against the results
+    Backtest {
+        from: NaiveDate,
+        to: NaiveDate,
+        output: Option<PathBuf>,
+    },
+    /// Report the calibration of backtest predictions, and fit a
+    /// recalibration map to them
+    Calibrate {
+        input: PathBuf,
+        bins: usize,
+        method: Method,
+        output: Option<PathBuf>,
+    },
+    /// Record the final scores of predicted games
+    Grade,
+    /// Score the recorded predictions
+    History,
+    /// Search the batting orders of one team of a game
+    OptimizeLineup {
+        game_id: String,
+        side: Side,
+        objective: Objective,
+    },
+    /// Change players of a game and compare the prediction with the original
+    WhatIf { game_id: String, scenario: Scenario },
+    /// Simulate the rest of the season
+    Season { simulations: usize },
+    /// Simulate a postseason series between two clubs, the one with
+    /// home-field advantage last, or the whole bracket if there are none
+    Series {
+        teams: Option<[String; 2]>,
+        format: series::Format,
+        rotations: Vec<(String, Vec<String>)>,
+    },
+    /// Compare predictions with sportsbook odds
+    Odds { path: PathBuf, kelly: f64 },
+    /// Write the predictions of a day as a document
+    Report { output: Option<PathBuf> },
+    /// Print the configuration in effect
+    ShowConfig,
+    /// Answer HTTP requests for schedules and predictions
+    Serve { bind: String, ttl: Duration },
+    /// Show the detailed prediction of one game
+    Game { game_id: String },
+    /// Show the stats the model uses for a player, by ID or name
+    Player { query: String },
+    /// List the games of a day
+    Schedule,
+    /// List the clubs with their IDs and abbreviations
+    Teams,
 }
 
 impl Config {
     pub fn get() -> Result<Config, String> {
 	Cli::parse().to_config()
     }
+
+    /// The date of the predictions
+    pub fn naive_date(&self) -> Result<NaiveDate, String> {
+        NaiveDate::parse_from_str(&self.date, "%F")
+            .map_err(|_| format!("Invalid date: {}", self.date))
+    }
+
+    /// The defaults, overridden by `settings`, as if no flags were given.
+    /// Nothing is recorded.
+    pub fn from_settings(settings: Settings) -> Result<Config, String> {
+        Cli {
+            no_history: true,
+            ..Cli::default()
+        }
+        .merge(settings)
+    }
+
+    /// Park factors and home-field advantage of a venue
+    pub fn venue(&self, venue_id: Option<&str>) -> Venue {
+        Venue {
+            park: self.park_factors.get(venue_id),
+            home_advantage: self.home_advantage,
+        }
+    }
 }
 
-#[derive(Debug, Parser)]
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            date: today(),
+            verbose: false,
+            mode: Mode::Predict,
+            format: Format::Text,
+            color: ColorChoice::Auto,
+            display: Display::Bar,
+            iterations: 1000,
+            seed: None,
+            cache_dir: None,
+            offline: false,
+            as_of: None,
+            recalibration: None,
+            history: None,
+            park_factors: ParkTable::bundled(),
+            home_advantage: DEFAULT_HOME_ADVANTAGE,
+            fatigue: Fatigue::default(),
+            matchup: Matchup::default(),
+            stats: StatSource::Career,
+            colormap: Colormap::default(),
+            api_url: DEFAULT_API_URL.to_string(),
+            filter: Filter::default(),
+            tz: None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Parser)]
 #[command(name = "mlb-oracle")]
 #[command(version = "0.1.0")]
 #[command(about = "MLB daily predictions!", long_about = None)]
+#[command(args_conflicts_with_subcommands = true)]
 struct Cli {
-    /// Make predictions for this date (Default: today)
-    #[arg(value_name = "YYYY-MM-DD")]
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Make predictions for this date: YYYY-MM-DD, today, yesterday,
+    /// tomorrow or a number of days from today such as +2 or -1 (Default:
+    /// today, which lasts until 3 AM Pacific time)
+    #[arg(value_name = "DATE", allow_negative_numbers = true)]
     date: Option<String>,
-    #[arg(short, long)]
+    #[command(flatten)]
+    filter: FilterArgs,
+    /// Read settings from this TOML file (Default:
+    /// ~/.config/mlb-oracle/config.toml, if it exists)
+    #[arg(long, value_name = "FILE", global = true)]
+    config: Option<PathBuf>,
+    /// Output format of the predictions (Default: text), or of the report
+    /// (Default: html)
+    #[arg(short, long, value_enum, global = true)]
+    format: Option<Format>,
+    /// When to color the text output (Default: auto)
+    #[arg(long, value_enum, global = true)]
+    color: Option<ColorChoice>,
+    /// Show game times in this time zone, e.g. America/Chicago (Default:
+    /// local)
+    #[arg(long, value_name = "ZONE", global = true)]
+    tz: Option<String>,
+    /// What the text output shows of each prediction (Default: bar)
+    #[arg(long, value_enum, global = true)]
+    display: Option<Display>,
+    /// Number of simulated games per prediction (Default: 1000)
+    #[arg(short = 'n', long, global = true)]
+    iterations: Option<usize>,
+    /// Seed the simulations for reproducible predictions
+    #[arg(long, global = true)]
+    seed: Option<u64>,
+    /// Keep API responses in this directory and reuse them. They never
+    /// expire, so this is meant for past games (Default for backtest:
+    /// ~/.cache/mlb-oracle)
+    #[arg(long, value_name = "DIR", global = true)]
+    cache_dir: Option<PathBuf>,
+    /// Only use cached API responses
+    #[arg(long, global = true)]
+    offline: bool,
+    /// Recalibrate win probabilities with a map fitted by `calibrate`
+    #[arg(long, value_name = "FILE", global = true)]
+    recalibrate: Option<PathBuf>,
+    /// Record predictions in this file (Default:
+    /// ~/.local/share/mlb-oracle/history.ndjson)
+    #[arg(long, value_name = "FILE", global = true)]
+    history: Option<PathBuf>,
+    /// Park factors to use instead of the bundled ones, as a CSV file with
+    /// the columns venue_id, single, double, triple, homerun, walk and
+    /// strikeout (100 is neutral)
+    #[arg(long, value_name = "FILE", global = true)]
+    park_factors: Option<PathBuf>,
+    /// Relative boost to the home team's batting, and cut to the visitors'
+    /// (Default: 0.02)
+    #[arg(long, global = true)]
+    home_advantage: Option<f64>,
+    /// Boost to batters for each time they have gone through the order
+    /// against the same pitcher (Default: 0.03)
+    #[arg(long, global = true)]
+    tto_penalty: Option<f64>,
+    /// Pitch count from which pitchers tire (Default: 90)
+    #[arg(long, global = true)]
+    fatigue_threshold: Option<i32>,
+    /// Boost to batters for each pitch thrown past the fatigue threshold
+    /// (Default: 0.002)
+    #[arg(long, global = true)]
+    fatigue_rate: Option<f64>,
+    /// Pitch count at which pitchers make way for an average reliever
+    /// (Default: 100)
+    #[arg(long, global = true)]
+    pull_at: Option<i32>,
+    /// How batter and pitcher rates combine (Default: geometric)
+    #[arg(long, value_enum, global = true)]
+    matchup: Option<MatchupModel>,
+    /// Which player stats feed the model (Default: career)
+    #[arg(long, value_enum, global = true)]
+    stats: Option<StatSource>,
+    /// Do not record predictions
+    #[arg(long, global = true, conflicts_with = "history")]
+    no_history: bool,
+    #[arg(short, long, global = true)]
     verbose: bool,
 }
 
+/// Which games of the slate to keep
+#[derive(Debug, Default, Args)]
+struct FilterArgs {
+    /// Only the games of these clubs, by abbreviation, full name or ID
+    /// (e.g. NYY,BOS)
+    #[arg(long, value_name = "TEAM,...", value_delimiter = ',')]
+    team: Vec<String>,
+    /// Only the games of this league's clubs
+    #[arg(long, value_enum, ignore_case = true)]
+    league: Option<League>,
+    /// Only the games of this division's clubs, in both leagues unless
+    /// --league is given
+    #[arg(long, value_enum, ignore_case = true)]
+    division: Option<Division>,
+}
 
-fn parse_date(datestr: &str) -> Result<String, String> {
-    match NaiveDate::parse_from_str(datestr, "%F") {
-        Ok(v) => Ok(v.to_string()),
-        Err(_) => Err(format!("Invalid date: {}", datestr)),
+impl FilterArgs {
+    fn to_filter(&self) -> Filter {
+        Filter {
+            teams: self.team.clone(),
+            league: self.league,
+            division: self.division,
+        }
     }
 }
 
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Predict every game of a day (the default)
+    Predict {
+        /// Make predictions for this date (Default: today)
+        #[arg(value_name = "DATE", allow_negative_numbers = true)]
+        date: Option<String>,
+        #[command(flatten)]
+        filter: FilterArgs,
+    },
+    /// Show one game's prediction in detail: win probabilities, fair odds,
+    /// expected runs, lineups and the likeliest scores
+    Game {
+        /// Game ID, as found on the schedule
+        #[arg(value_name = "GAMEPK")]
+        game_id: String,
+    },
+    /// Show the stats and rates the model uses for a player
+    Player {
+        /// Player ID, or part of a name to look up in this season's rosters
+        #[arg(value_name = "ID|NAME")]
+        player: String,
+    },
+    /// List the games of a day with their IDs, status and score
+    Schedule {
+        /// List the games on this date (Default: today)
+        #[arg(value_name = "DATE", allow_negative_numbers = true)]
+        date: Option<String>,
+        #[command(flatten)]
+        filter: FilterArgs,
+    },
+    /// List the clubs with their IDs, abbreviations and divisions
+    Teams,
+    /// Follow live games, refreshing win probabilities as they progress
+    Watch {
+        /// Follow games on this date (Default: today)
+        #[arg(value_name = "DATE", allow_negative_numbers = true)]
+        date: Option<String>,
+        /// Seconds between refreshes
+        #[arg(
+            short,
+            long,
+            default_value_t = 30,
+            value_parser = clap::value_parser!(u64).range(1..)
+        )]
+        interval: u64,
+        /// Flag win probability changes at least this large between refreshes
+        #[arg(short, long, default_value_t = 0.15)]
+        swing: f64,
+        #[command(flatten)]
+        filter: FilterArgs,
+    },
+    /// Browse a day's games full-screen, with each game's lineups and
+    /// matchups, and the scores of live games
+    Dashboard {
+        /// Show the games on this date (Default: today)
+        #[arg(value_name = "DATE", allow_negative_numbers = true)]
+        date: Option<String>,
+        /// Seconds between refreshes of the scores and live games
+        #[arg(
+            short,
+            long,
+            default_value_t = 30,
+            value_parser = clap::value_parser!(u64).range(1..)
+        )]
+        interval: u64,
+        #[command(flatten)]
+        filter: FilterArgs,
+    },
+    /// Show the matchup probabilities behind a game's prediction
+    Explain {
+        /// Game ID, as found on the schedule
+        #[arg(value_name = "GAMEPK")]
+        game_id: String,
+    },
+    /// Replay Retrosheet event files and report where the simulator's
+    /// transitions disagree with the recorded plays
+    Replay {
+        /// Event files (.EVN/.EVA)
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+    },
+    /// Predict past games with the stats available before each of them,
+    /// and score the predictions against the final scores
+    Backtest {
+        /// First date of the backtest
+        #[arg(long, value_name = "DATE", allow_negative_numbers = true)]
+        from: String,
+        /// Last date of the backtest (inclusive)
+        #[arg(long, value_name = "DATE", allow_negative_numbers = true)]
+        to: String,
+        /// Write per-game results to this CSV file
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+        #[command(flatten)]
+        filter: FilterArgs,
+    },
+    /// Report how well the predictions of a backtest are calibrated, and
+    /// fit a recalibration map to them
+    Calibrate {
+        /// Per-game results written by `backtest --output`
+        #[arg(value_name = "CSV")]
+        input: PathBuf,
+        /// Number of probability bins in the reliability table
+        #[arg(short, long, default_value_t = 10)]
+        bins: usize,
+        /// How to fit the recalibration map
+        #[arg(short, long, value_enum, default_value_t = Method::Isotonic)]
+        method: Method,
+        /// Fit a recalibration map and write it to this file
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    /// Fetch the final scores of recorded predictions that are not graded yet
+    Grade,
+    /// Show the record, Brier score and calibration of recorded predictions
+    /// for each model version
+    History,
+    /// Search batting orders for the one that scores the most runs, or wins
+    /// the most games, against the opposing starter
+    OptimizeLineup {
+        /// Game ID, as found on the schedule
+        #[arg(value_name = "GAMEPK")]
+        game_id: String,
+        /// Team whose lineup is optimized
+        #[arg(short, long, value_enum)]
+        team: Side,
+        /// What the best order maximizes
+        #[arg(short, long, value_enum, default_value_t = Objective::Runs)]
+        objective: Objective,
+    },
+    /// Change the players of a game, e.g. for a late scratch or an opener,
+    /// and show how the win probabilities move
+    WhatIf {
+        /// Game ID, as found on the schedule
+        #[arg(value_name = "GAMEPK")]
+        game_id: String,
+        /// Put a player in a lineup slot, e.g. home:3=660271
+        #[arg(long, value_name = "SIDE:SLOT=ID")]
+        swap: Vec<String>,
+        /// Start another pitcher, e.g. away=543037
+        #[arg(long, value_name = "SIDE=ID")]
+        starter: Vec<String>,
+        /// Replace a batter with an average hitter of the game
+        #[arg(long, value_name = "ID")]
+        bench: Vec<String>,
+    },
+    /// Simulate the rest of the season from the standings, and show
+    /// projected wins and playoff odds
+    ///
+    /// Clubs are rated by their run differential (a Pythagorean record
+    /// regressed to .500), not by simulating their lineups; only the
+    /// home-field edge is simulated.
+    Season {
+        /// Project from the standings on this date (Default: today)
+        #[arg(value_name = "DATE", allow_negative_numbers = true)]
+        date: Option<String>,
+        /// Number of simulated seasons
+        #[arg(short, long, default_value_t = 10000)]
+        simulations: usize,
+    },
+    /// Simulate a postseason series game by game with each club's
+    /// rotation, or the whole bracket seeded from the standings
+    Series {
+        /// Clubs, the one with home-field advantage last (e.g. SD LAD)
+        #[arg(num_args = 2, value_name = "TEAM", required_unless_present = "bracket")]
+        teams: Vec<String>,
+        /// Simulate the 12-club bracket instead of a single series
+        #[arg(long, conflicts_with_all = ["teams", "best_of", "pattern"])]
+        bracket: bool,
+        /// Take the standings, lineups and rotations on this date (Default: today)
+        #[arg(short, long, value_name = "DATE", allow_negative_numbers = true)]
+        date: Option<String>,
+        /// Number of games of the series
+        #[arg(short, long, default_value_t = 7)]
+        best_of: usize,
+        /// Games hosted in turn by each club, the one with home-field
+        /// advantage first (Default: 3, 2-2-1 or 2-3-2)
+        #[arg(short, long)]
+        pattern: Option<String>,
+        /// Starters of a club in order, e.g. LAD=477132,605141 (Default:
+        /// the starters of its last four games)
+        #[arg(short, long, value_name = "TEAM=ID,...")]
+        rotation: Vec<String>,
+    },
+    /// Compare the predictions of a day with sportsbook moneylines, run
+    /// lines and totals, and show the edge and Kelly stake of each bet
+    Odds {
+        /// CSV or JSON file of quotes, with fields date, game_id, away, home,
+        /// market, side, line and price (American, decimal or fractional)
+        #[arg(value_name = "FILE")]
+        path: PathBuf,
+        /// Compare the games on this date (Default: today)
+        #[arg(value_name = "DATE", allow_negative_numbers = true)]
+        date: Option<String>,
+        /// Scale the Kelly stakes, e.g. 0.25 for quarter Kelly
+        #[arg(short, long, default_value_t = 1.0)]
+        kelly: f64,
+        #[command(flatten)]
+        filter: FilterArgs,
+    },
+    /// Serve schedules, predictions and explanations as JSON over HTTP.
+    /// Predictions are simulated in the background and cached.
+    Serve {
+        /// Address to listen on
+        #[arg(short, long, value_name = "ADDR", default_value = "127.0.0.1:8080")]
+        bind: String,
+        /// Seconds before a cached result is refreshed
+        #[arg(long, value_name = "SECONDS", default_value_t = 300)]
+        ttl: u64,
+    },
+    /// Write a day's predictions as a standalone HTML (the default) or
+    /// Markdown document, with lineups and charts of the run distributions
+    Report {
+        /// Report on the games of this date (Default: today)
+        #[arg(short, long, value_name = "DATE", allow_negative_numbers = true)]
+        date: Option<String>,
+        /// Write the document to this file instead of stdout
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+        #[command(flatten)]
+        filter: FilterArgs,
+    },
+    /// Inspect the configuration
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ConfigAction {
+    /// Print the configuration merged from the defaults, the config file
+    /// and the flags, in the format of the config file
+    Show,
+}
+
+
+fn parse_date(datestr: &str) -> Result<String, String> {
+    parse_naive_date(datestr).map(|v| v.to_string())
+}
+
+fn parse_naive_date(datestr: &str) -> Result<NaiveDate, String> {
+    dates::parse(datestr, dates::today())
+}
+
+/// $XDG_CACHE_HOME/mlb-oracle, or ~/.cache/mlb-oracle
+pub fn default_cache_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CACHE_HOME")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .map(|dir| dir.join("mlb-oracle"))
+}
+
+/// $XDG_CONFIG_HOME/mlb-oracle/config.toml, or under ~/.config
+pub fn default_config_path() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|dir| dir.join("mlb-oracle").join("config.toml"))
+}
+
+/// $XDG_DATA_HOME/mlb-oracle/history.ndjson, or under ~/.local/share
+pub fn default_history_path() -> Option<PathBuf> {
+    env::var_os("XDG_DATA_HOME")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+        .map(|dir| dir.join("mlb-oracle").join("history.ndjson"))
+}
+
+fn today() -> String {
+    dates::today().format("%F").to_string()
+}
+
 impl Cli {
     fn to_config(&self) -> Result<Config, String> {
-        let date = match &self.date {
+        self.merge(Settings::load(self.config.as_deref())?)
+    }
+
+    /// Flags take precedence over the settings of the config file
+    fn merge(&self, settings: Settings) -> Result<Config, String> {
+        let (date, mode) = match &self.command {
+            None => (&self.date, Mode::Predict),
+            Some(Command::Predict { date, .. }) => (date, Mode::Predict),
+            Some(Command::Game { game_id }) => (
+                &None,
+                Mode::Game {
+                    game_id: game_id.clone(),
+                },
+            ),
+            Some(Command::Player { player }) => (
+                &None,
+                Mode::Player {
+                    query: player.clone(),
+                },
+            ),
+            Some(Command::Schedule { date, .. }) => (date, Mode::Schedule),
+            Some(Command::Teams) => (&None, Mode::Teams),
+            Some(Command::Watch {
+                date,
+                interval,
+                swing,
+                ..
+            }) => (
+                date,
+                Mode::Watch {
+                    interval: Duration::from_secs(*interval),
+                    swing: *swing,
+                },
+            ),
+            Some(Command::Dashboard { date, interval, .. }) => (
+                date,
+                Mode::Dashboard {
+                    interval: Duration::from_secs(*interval),
+                },
+            ),
+            Some(Command::Explain { game_id }) => (
+                &None,
+                Mode::Explain {
+                    game_id: game_id.clone(),
+                },
+            ),
+            Some(Command::Replay { paths }) => (
+                &None,
+                Mode::Replay {
+                    paths: paths.clone(),
+                },
+            ),
+            Some(Command::Backtest {
+                from, to, output, ..
+            }) => {
+                let from = parse_naive_date(from)?;
+                let to = parse_naive_date(to)?;
+                if from > to {
+                    return Err(format!("Empty date range: {} to {}", from, to));
+                }
+                (
+                    &None,
+                    Mode::Backtest {
+                        from,
+                        to,
+                        output: output.clone(),
+                    },
+                )
+            }
+            Some(Command::Calibrate {
+                input,
+                bins,
+                method,
+                output,
+            }) => {
+                if *bins == 0 {
+                    return Err("Need at least one bin".to_string());
+                }
+                (
+                    &None,
+                    Mode::Calibrate {
+                        input: input.clone(),
+                        bins: *bins,
+                        method: *method,
+                        output: output.clone(),
+                    },
+                )
+            }
+            Some(Command::Report { date, output, .. }) => (
+                date,
+                Mode::Report {
+                    output: output.clone(),
+                },
+            ),
+            Some(Command::Serve { bind, ttl }) => (
+                &None,
+                Mode::Serve {
+                    bind: bind.clone(),
+                    ttl: Duration::from_secs(*ttl),
+                },
+            ),
+            Some(Command::Grade) => (&None, Mode::Grade),
+            Some(Command::Config {
+                action: ConfigAction::Show,
+            }) => (&None, Mode::ShowConfig),
+            Some(Command::History) => (&None, Mode::History),
+            Some(Command::Season { date, simulations }) => {
+                if *simulations == 0 {
+                    return Err("Need at least one simulation".to_string());
+                }
+                (
+                    date,
+                    Mode::Season {
+                        simulations: *simulations,
+                    },
+                )
+            }
+            Some(Command::Odds {
+                path, date, kelly, ..
+            }) => {
+                if !(*kelly > 0.0 && *kelly <= 1.0) {
+                    return Err(format!("Invalid Kelly fraction: {}", kelly));
+                }
+                (
+                    date,
+                    Mode::Odds {
+                        path: path.clone(),
+                        kelly: *kelly,
+                    },
+                )
+            }
+            Some(Command::Series {
+                teams,
+                bracket,
+                date,
+                best_of,
+                pattern,
+                rotation,
+            }) => {
+                let teams = match teams.as_slice() {
+                    [away, home] if !bracket => Some([away.to_uppercase(), home.to_uppercase()]),
+                    _ => None,
+                };
+                let mut rotations = Vec::new();
+                for s in rotation {
+                    rotations.push(series::parse_rotation(s)?);
+                }
+                (
+                    date,
+                    Mode::Series {
+                        teams,
+                        format: series::Format::new(*best_of, pattern.as_deref())?,
+                        rotations,
+                    },
+                )
+            }
+            Some(Command::WhatIf {
+                game_id,
+                swap,
+                starter,
+                bench,
+            }) => {
+                let mut changes = Vec::new();
+                for s in swap {
+                    changes.push(Change::swap(s)?);
+                }
+                for s in starter {
+                    changes.push(Change::starter(s)?);
+                }
+                for s in bench {
+                    changes.push(Change::bench(s)?);
+                }
+                if changes.is_empty() {
+                    return Err("Nothing to change: use --swap, --starter or --bench".to_string());
+                }
+                (
+                    &None,
+                    Mode::WhatIf {
+                        game_id: game_id.clone(),
+                        scenario: Scenario { changes },
+                    },
+                )
+            }
+            Some(Command::OptimizeLineup {
+                game_id,
+                team,
+                objective,
+            }) => (
+                &None,
+                Mode::OptimizeLineup {
+                    game_id: game_id.clone(),
+                    side: *team,
+                    objective: *objective,
+                },
+            ),
+        };
+        let filter = match &self.command {
+            None => self.filter.to_filter(),
+            Some(
+                Command::Predict { filter, .. }
+                | Command::Schedule { filter, .. }
+                | Command::Watch { filter, .. }
+                | Command::Dashboard { filter, .. }
+                | Command::Backtest { filter, .. }
+                | Command::Odds { filter, .. }
+                | Command::Report { filter, .. },
+            ) => filter.to_filter(),
+            Some(_) => Filter::default(),
+        };
+        let cache_dir = self.cache_dir.clone().or(settings.cache_dir);
+        let cache_dir = match mode {
+            Mode::Backtest { .. } => cache_dir.or_else(default_cache_dir),
+            _ => cache_dir,
+        };
+        let date = match date {
             Some(s) => parse_date(s)?,
-            None => chrono::offset::Local::now().format("%m/%d/%Y").to_string(),
+            None => today(),
+        };
+        let history = match mode {
+            Mode::Predict | Mode::Grade | Mode::History if !self.no_history => {
+                self.history.clone().or_else(default_history_path)
+            }
+            _ => None,
+        };
+        let recalibration = match &self.recalibrate {
+            Some(path) => Some(Recalibration::load(path)?),
+            None => None,
+        };
+        let park_factors = match &self.park_factors {
+            Some(path) => ParkTable::load(path)?,
+            None => ParkTable::bundled(),
+        };
+        let defaults = Config::default();
+        // The format of the config file is that of the predictions, so
+        // reports only take the flag
+        let format = match mode {
+            Mode::Report { .. } => match self.format {
+                Some(format) if !format.is_document() => {
+                    return Err("Reports are written in html or md".to_string());
+                }
+                format => format.unwrap_or(Format::Html),
+            },
+            _ => {
+                let format = self.format.or(settings.format).unwrap_or(defaults.format);
+                if format.is_document() {
+                    return Err("The html and md formats are only for report".to_string());
+                }
+                format
+            }
+        };
+        let home_advantage = self
+            .home_advantage
+            .or(settings.home_advantage)
+            .unwrap_or(DEFAULT_HOME_ADVANTAGE);
+        if !(0.0..1.0).contains(&home_advantage) {
+            return Err(format!("Invalid home advantage: {}", home_advantage));
+        }
+        let fatigue = Fatigue {
+            times_through_order: self
+                .tto_penalty
+                .or(settings.tto_penalty)
+                .unwrap_or(defaults.fatigue.times_through_order),
+            threshold: self
+                .fatigue_threshold
+                .or(settings.fatigue_threshold)
+                .unwrap_or(defaults.fatigue.threshold),
+            per_pitch: self
+                .fatigue_rate
+                .or(settings.fatigue_rate)
+                .unwrap_or(defaults.fatigue.per_pitch),
+            pull_at: self
+                .pull_at
+                .or(settings.pull_at)
+                .unwrap_or(defaults.fatigue.pull_at),
+            ..defaults.fatigue
+        };
+        if fatigue.times_through_order < 0.0 || fatigue.per_pitch < 0.0 {
+            return Err("Fatigue penalties cannot be negative".to_string());
+        }
+        if fatigue.pull_at <= 0 {
+            return Err(format!("Invalid pitch count to pull pitchers at: {}", fatigue.pull_at));
+        }
+        let matchup = Matchup {
+            model: self
+                .matchup
+                .or(settings.matchup)
+                .unwrap_or(defaults.matchup.model),
+            batter_regression: settings
+                .batter_regression
+                .unwrap_or(defaults.matchup.batter_regression),
+            pitcher_regression: settings
+                .pitcher_regression
+                .unwrap_or(defaults.matchup.pitcher_regression),
+        };
+        if matchup.batter_regression < 0.0 || matchup.pitcher_regression < 0.0 {
+            return Err("Regression constants cannot be negative".to_string());
+        }
+        let colormap = Colormap {
+            thresholds: settings.thresholds.unwrap_or(defaults.colormap.thresholds),
+            colors: settings.colors.unwrap_or(defaults.colormap.colors),
+        };
+        colormap.validate()?;
+        let iterations = self
+            .iterations
+            .or(settings.iterations)
+            .unwrap_or(defaults.iterations);
+        if iterations == 0 {
+            return Err("Need at least one iteration".to_string());
+        }
+        let tz = match self.tz.as_ref().or(settings.timezone.as_ref()) {
+            Some(name) => dates::parse_tz(name)?,
+            None => None,
         };
         Ok(Config {
             date,
             verbose: self.verbose,
+            mode,
+            format,
+            color: self.color.or(settings.color).unwrap_or(defaults.color),
+            display: self.display.or(settings.display).unwrap_or(defaults.display),
+            iterations,
+            seed: self.seed.or(settings.seed),
+            cache_dir,
+            offline: self.offline || settings.offline.unwrap_or(false),
+            as_of: None,
+            recalibration,
+            history,
+            park_factors,
+            home_advantage,
+            fatigue,
+            matchup,
+            stats: self.stats.or(settings.stats).unwrap_or(defaults.stats),
+            colormap,
+            api_url: settings.api_url.unwrap_or(defaults.api_url),
+            filter,
+            tz,
         })
     }
 }
@@ -63,9 +930,360 @@ mod tests {
         assert!(v.is_err());
     }
 
+    #[test]
+    fn cfg_watch() {
+        let cfg = Cli::parse_from(["mlb-oracle", "watch", "2024-04-01", "-i", "10"])
+            .merge(Settings::default())
+            .unwrap();
+        assert_eq!(cfg.date, "2024-04-01");
+        match cfg.mode {
+            Mode::Watch { interval, .. } => assert_eq!(interval.as_secs(), 10),
+            _ => panic!("expected watch mode"),
+        }
+
+        assert!(Cli::try_parse_from(["mlb-oracle", "watch", "-i", "0"]).is_err());
+
+        let cfg = Cli::parse_from(["mlb-oracle", "2024-04-01"]).merge(Settings::default()).unwrap();
+        assert_eq!(cfg.date, "2024-04-01");
+        assert!(matches!(cfg.mode, Mode::Predict));
+    }
+
+    #[test]
+    fn cfg_format() {
+        let cfg = Cli::parse_from(["mlb-oracle", "--format", "ndjson", "--seed", "7"])
+            .merge(Settings::default())
+            .unwrap();
+        assert_eq!(cfg.format, Format::Ndjson);
+        assert_eq!(cfg.seed, Some(7));
+        assert_eq!(cfg.iterations, 1000);
+        assert!(Cli::try_parse_from(["mlb-oracle", "--format", "xml"]).is_err());
+
+        let cfg = Cli::parse_from(["mlb-oracle", "watch", "--color=never", "--display", "compact"])
+            .merge(Settings::default())
+            .unwrap();
+        assert_eq!(cfg.color, ColorChoice::Never);
+        assert_eq!(cfg.display, Display::Compact);
+    }
+
+    #[test]
+    fn cfg_backtest() {
+        let cfg = Cli::parse_from([
+            "mlb-oracle", "backtest", "--from", "2023-04-01", "--to", "2023-04-30",
+            "--cache-dir", "/tmp/cache", "--offline",
+        ])
+        .merge(Settings::default())
+        .unwrap();
+        assert!(cfg.offline);
+        assert_eq!(cfg.cache_dir, Some(PathBuf::from("/tmp/cache")));
+        match cfg.mode {
+            Mode::Backtest { from, to, .. } => {
+                assert_eq!(from.to_string(), "2023-04-01");
+                assert_eq!(to.to_string(), "2023-04-30");
+            }
+            _ => panic!("expected backtest mode"),
+        }
+
+        let res = Cli::parse_from(["mlb-oracle", "backtest", "--from", "2023-05-01", "--to", "2023-04-01"])
+            .merge(Settings::default());
+        assert!(res.is_err());
+    }
+
     #[test]
     fn cfg_date_valid() {
         let v = parse_date("2024-01-01").unwrap();
         assert_eq!(v, "2024-01-01");
+        assert_eq!(Config::default().date, today());
+        assert!(Config::default().naive_date().is_ok());
+    }
+
+    #[test]
+    fn cfg_date_relative() {
+        let cfg = Cli::parse_from(["mlb-oracle", "-1", "--tz", "America/Chicago"])
+            .merge(Settings::default())
+            .unwrap();
+        let yesterday = dates::today() - chrono::Days::new(1);
+        assert_eq!(cfg.naive_date(), Ok(yesterday));
+        assert_eq!(cfg.tz, Some(chrono_tz::America::Chicago));
+        let cfg = Cli::parse_from(["mlb-oracle", "schedule", "tomorrow"])
+            .merge(Settings::default())
+            .unwrap();
+        assert_eq!(cfg.naive_date(), Ok(dates::today() + chrono::Days::new(1)));
+        assert!(Cli::parse_from(["mlb-oracle", "--tz", "Nowhere"])
+            .merge(Settings::default())
+            .is_err());
+    }
+
+    #[test]
+    fn cfg_calibrate() {
+        let cfg = Cli::parse_from(["mlb-oracle", "calibrate", "bt.csv", "-m", "platt", "-o", "map.json"])
+            .merge(Settings::default())
+            .unwrap();
+        match cfg.mode {
+            Mode::Calibrate {
+                input,
+                bins,
+                method,
+                output,
+            } => {
+                assert_eq!(input, PathBuf::from("bt.csv"));
+                assert_eq!(bins, 10);
+                assert_eq!(method, Method::Platt);
+                assert_eq!(output, Some(PathBuf::from("map.json")));
+            }
+            _ => panic!("expected calibrate mode"),
+        }
+        assert!(cfg.recalibration.is_none());
+        assert!(Cli::parse_from(["mlb-oracle", "calibrate", "bt.csv", "-b", "0"])
+            .merge(Settings::default())
+            .is_err());
+        assert!(Cli::parse_from(["mlb-oracle", "--recalibrate", "/nonexistent/map.json"])
+            .merge(Settings::default())
+            .is_err());
+    }
+
+    #[test]
+    fn cfg_history() {
+        let cfg = Cli::parse_from(["mlb-oracle", "--history", "/tmp/h.ndjson"])
+            .merge(Settings::default())
+            .unwrap();
+        assert_eq!(cfg.history, Some(PathBuf::from("/tmp/h.ndjson")));
+        let cfg = Cli::parse_from(["mlb-oracle", "--no-history"])
+            .merge(Settings::default())
+            .unwrap();
+        assert_eq!(cfg.history, None);
+        let cfg = Cli::parse_from(["mlb-oracle", "replay", "x.EVA", "--history", "/tmp/h.ndjson"])
+            .merge(Settings::default())
+            .unwrap();
+        assert_eq!(cfg.history, None);
+        let cfg = Cli::parse_from(["mlb-oracle", "grade", "--history", "/tmp/h.ndjson"])
+            .merge(Settings::default())
+            .unwrap();
+        assert!(matches!(cfg.mode, Mode::Grade));
+        assert!(Cli::try_parse_from(["mlb-oracle", "--history", "h", "--no-history"]).is_err());
+    }
+
+    #[test]
+    fn cfg_optimize_lineup() {
+        let cfg = Cli::parse_from(["mlb-oracle", "optimize-lineup", "745123", "--team", "home"])
+            .merge(Settings::default())
+            .unwrap();
+        match cfg.mode {
+            Mode::OptimizeLineup {
+                game_id,
+                side,
+                objective,
+            } => {
+                assert_eq!(game_id, "745123");
+                assert_eq!(side, Side::Home);
+                assert_eq!(objective, Objective::Runs);
+            }
+            _ => panic!("expected optimize-lineup mode"),
+        }
+        assert!(Cli::try_parse_from(["mlb-oracle", "optimize-lineup", "745123"]).is_err());
+    }
+
+    #[test]
+    fn cfg_what_if() {
+        let cfg = Cli::parse_from([
+            "mlb-oracle", "what-if", "745123", "--swap", "home:3=660271", "--bench", "592450",
+            "--starter", "away=543037",
+        ])
+        .merge(Settings::default())
+        .unwrap();
+        match cfg.mode {
+            Mode::WhatIf { game_id, scenario } => {
+                assert_eq!(game_id, "745123");
+                assert_eq!(scenario.changes.len(), 3);
+                assert_eq!(scenario.changes[2], Change::bench("592450").unwrap());
+            }
+            _ => panic!("expected what-if mode"),
+        }
+        assert!(Cli::parse_from(["mlb-oracle", "what-if", "745123"])
+            .merge(Settings::default())
+            .is_err());
+        assert!(Cli::parse_from(["mlb-oracle", "what-if", "745123", "--swap", "home:0=1"])
+            .merge(Settings::default())
+            .is_err());
+    }
+
+    #[test]
+    fn cfg_season() {
+        let cfg = Cli::parse_from(["mlb-oracle", "season", "2024-08-01", "-s", "500"])
+            .merge(Settings::default())
+            .unwrap();
+        assert_eq!(cfg.naive_date(), Ok(NaiveDate::from_ymd_opt(2024, 8, 1).unwrap()));
+        assert!(matches!(cfg.mode, Mode::Season { simulations: 500 }));
+        let cfg = Config::default();
+        assert!(cfg.naive_date().is_ok());
+    }
+
+    #[test]
+    fn cfg_odds() {
+        let cfg = Cli::parse_from(["mlb-oracle", "odds", "lines.csv", "2024-06-01", "-k", "0.25"])
+            .merge(Settings::default())
+            .unwrap();
+        assert_eq!(cfg.date, "2024-06-01");
+        assert!(matches!(cfg.mode, Mode::Odds { kelly, .. } if kelly == 0.25));
+        assert!(Cli::parse_from(["mlb-oracle", "odds", "lines.csv", "-k", "2"])
+            .merge(Settings::default())
+            .is_err());
+    }
+
+    #[test]
+    fn cfg_lookups() {
+        let cfg = Cli::parse_from(["mlb-oracle", "predict", "2024-04-01", "-f", "json"])
+            .merge(Settings::default())
+            .unwrap();
+        assert!(matches!(cfg.mode, Mode::Predict));
+        assert_eq!(cfg.date, "2024-04-01");
+        assert_eq!(cfg.format, Format::Json);
+        let cfg = Cli::parse_from(["mlb-oracle", "game", "745123"])
+            .merge(Settings::default())
+            .unwrap();
+        assert!(matches!(cfg.mode, Mode::Game { game_id } if game_id == "745123"));
+        let cfg = Cli::parse_from(["mlb-oracle", "player", "Shohei Ohtani"])
+            .merge(Settings::default())
+            .unwrap();
+        assert!(matches!(cfg.mode, Mode::Player { query } if query == "Shohei Ohtani"));
+        let cfg = Cli::parse_from(["mlb-oracle", "schedule", "2024-04-02"])
+            .merge(Settings::default())
+            .unwrap();
+        assert!(matches!(cfg.mode, Mode::Schedule));
+        assert_eq!(cfg.date, "2024-04-02");
+        assert!(Cli::try_parse_from(["mlb-oracle", "teams", "AL"]).is_err());
+        assert!(Cli::try_parse_from(["mlb-oracle", "game"]).is_err());
+    }
+
+    #[test]
+    fn cfg_dashboard() {
+        let cfg = Cli::parse_from(["mlb-oracle", "dashboard", "-1", "--league", "NL"])
+            .merge(Settings::default())
+            .unwrap();
+        assert!(matches!(cfg.mode, Mode::Dashboard { interval }
+            if interval == Duration::from_secs(30)));
+        assert_eq!(cfg.date, (dates::today() - chrono::Days::new(1)).format("%F").to_string());
+        assert_eq!(cfg.filter.league, Some(League::National));
+        assert!(Cli::try_parse_from(["mlb-oracle", "dashboard", "-i", "0"]).is_err());
+    }
+
+    #[test]
+    fn cfg_report() {
+        let cfg = Cli::parse_from(["mlb-oracle", "report", "--date", "2024-07-01", "-o", "r.html"])
+            .merge(Settings::default())
+            .unwrap();
+        assert!(matches!(cfg.mode, Mode::Report { output: Some(ref p) } if p == &PathBuf::from("r.html")));
+        assert_eq!(cfg.date, "2024-07-01");
+        assert_eq!(cfg.format, Format::Html);
+        let cfg = Cli::parse_from(["mlb-oracle", "report", "--format", "md"])
+            .merge(Settings::default())
+            .unwrap();
+        assert_eq!(cfg.format, Format::Markdown);
+        assert!(Cli::parse_from(["mlb-oracle", "report", "-f", "csv"])
+            .merge(Settings::default())
+            .is_err());
+        assert!(Cli::parse_from(["mlb-oracle", "--format", "html"])
+            .merge(Settings::default())
+            .is_err());
+        // Predictions in JSON do not get in the way of reports
+        let settings = Settings::parse("[output]\nformat = \"json\"").unwrap();
+        let cfg = Cli::parse_from(["mlb-oracle", "report"]).merge(settings).unwrap();
+        assert_eq!(cfg.format, Format::Html);
+    }
+
+    #[test]
+    fn cfg_serve() {
+        let cfg = Cli::parse_from(["mlb-oracle", "serve", "--ttl", "60"])
+            .merge(Settings::default())
+            .unwrap();
+        assert!(matches!(cfg.mode, Mode::Serve { ref bind, ttl }
+            if bind == "127.0.0.1:8080" && ttl == Duration::from_secs(60)));
+        let cfg = Cli::parse_from(["mlb-oracle", "serve", "-b", "0.0.0.0:3000"])
+            .merge(Settings::default())
+            .unwrap();
+        assert!(matches!(cfg.mode, Mode::Serve { bind, .. } if bind == "0.0.0.0:3000"));
+    }
+
+    #[test]
+    fn cfg_filter() {
+        let cfg = Cli::parse_from(["mlb-oracle", "--team", "NYY,bos", "--league", "al"])
+            .merge(Settings::default())
+            .unwrap();
+        assert_eq!(cfg.filter.teams, ["NYY", "bos"]);
+        assert_eq!(cfg.filter.league, Some(League::American));
+        let cfg = Cli::parse_from(["mlb-oracle", "watch", "--division", "West"])
+            .merge(Settings::default())
+            .unwrap();
+        assert_eq!(cfg.filter.division, Some(Division::West));
+        assert!(Cli::try_parse_from(["mlb-oracle", "--league", "AA"]).is_err());
+        assert!(Cli::try_parse_from(["mlb-oracle", "explain", "1", "--league", "AL"]).is_err());
+    }
+
+    #[test]
+    fn cfg_settings() {
+        let settings = Settings::parse(
+            "[model]\niterations = 200\nseed = 3\nmatchup = \"log5\"\n\
+             [output]\ndisplay = \"numbers\"\n[cache]\ndir = \"/tmp/cache\"",
+        )
+        .unwrap();
+        let cfg = Cli::parse_from(["mlb-oracle", "-n", "50", "--display", "bar"])
+            .merge(settings)
+            .unwrap();
+        assert_eq!(cfg.iterations, 50);
+        assert_eq!(cfg.display, Display::Bar);
+        assert_eq!(cfg.seed, Some(3));
+        assert_eq!(cfg.matchup.model, MatchupModel::Log5);
+        assert_eq!(cfg.cache_dir, Some(PathBuf::from("/tmp/cache")));
+        assert_eq!(cfg.format, Format::Text);
+
+        let settings = Settings::parse("[model]\nhome_advantage = 2.0").unwrap();
+        assert!(Cli::parse_from(["mlb-oracle"]).merge(settings).is_err());
+        let settings = Settings::parse("[model]\niterations = 0").unwrap();
+        assert!(Cli::parse_from(["mlb-oracle"]).merge(settings).is_err());
+        assert!(Cli::parse_from(["mlb-oracle", "-n", "0"])
+            .merge(Settings::default())
+            .is_err());
+        assert!(Cli::parse_from(["mlb-oracle", "--config", "/nonexistent/config.toml"])
+            .to_config()
+            .is_err());
+    }
+
+    #[test]
+    fn cfg_config_show() {
+        let cfg = Cli::parse_from(["mlb-oracle", "config", "show", "--stats", "season"])
+            .merge(Settings::default())
+            .unwrap();
+        assert!(matches!(cfg.mode, Mode::ShowConfig));
+        assert_eq!(cfg.stats, StatSource::Season);
+        assert!(Cli::try_parse_from(["mlb-oracle", "config"]).is_err());
+    }
+
+    #[test]
+    fn cfg_series() {
+        let cfg = Cli::parse_from([
+            "mlb-oracle", "series", "sd", "lad", "-b", "5", "-r", "LAD=477132,605141",
+        ])
+        .merge(Settings::default())
+        .unwrap();
+        match cfg.mode {
+            Mode::Series {
+                teams,
+                format,
+                rotations,
+            } => {
+                assert_eq!(teams, Some(["SD".to_string(), "LAD".to_string()]));
+                assert_eq!(format.pattern, "2-2-1");
+                assert_eq!(rotations[0].1.len(), 2);
+            }
+            _ => panic!("Expected series mode"),
+        }
+        let cfg = Cli::parse_from(["mlb-oracle", "series", "--bracket", "-d", "2024-09-30"])
+            .merge(Settings::default())
+            .unwrap();
+        assert!(matches!(cfg.mode, Mode::Series { teams: None, .. }));
+        assert!(Cli::try_parse_from(["mlb-oracle", "series"]).is_err());
+        assert!(Cli::try_parse_from(["mlb-oracle", "series", "SD", "LAD", "--bracket"]).is_err());
+        assert!(Cli::parse_from(["mlb-oracle", "series", "SD", "LAD", "-b", "6"])
+            .merge(Settings::default())
+            .is_err());
     }
 }
@@ -0,0 +1,871 @@
+//! A full-screen dashboard of a day's games: the slate with win probability
+//! bars, the details of the selected game and the scores of live ones
+
+use std::io::{self, IsTerminal, Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use chrono::{Datelike, Days, NaiveDate};
+use chrono_tz::Tz;
+
+use crate::{
+    api::Oracle,
+    config::Config,
+    dates,
+    explain::{self, Matchup},
+    filter::Slate,
+    lookup,
+    mlbstats::{Game, Situation, Team},
+    odds,
+    output::{Display, Style},
+    watch, GameLine,
+};
+
+/// Bounds of the number of simulated games that `+` and `-` step through
+const MIN_ITERATIONS: usize = 100;
+const MAX_ITERATIONS: usize = 1_000_000;
+
+/// Width of the game list, on the left of the details
+const LIST_WIDTH: usize = 58;
+
+/// Most live games shown in the score panel
+const N_LIVE: usize = 6;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    Char(char),
+}
+
+/// Keys pressed, from the bytes read off the terminal
+fn keys(bytes: &[u8]) -> Vec<Key> {
+    let mut keys = Vec::new();
+    let mut idx = 0;
+    while idx < bytes.len() {
+        match bytes[idx..] {
+            // Arrows, in normal and application cursor mode
+            [0x1B, b'[' | b'O', arrow, ..] if (b'A'..=b'D').contains(&arrow) => {
+                keys.push(match arrow {
+                    b'A' => Key::Up,
+                    b'B' => Key::Down,
+                    b'C' => Key::Right,
+                    _ => Key::Left,
+                });
+                idx += 3;
+                continue;
+            }
+            [b, ..] if b.is_ascii() => keys.push(Key::Char(b as char)),
+            _ => (),
+        }
+        idx += 1;
+    }
+    keys
+}
+
+/// Work done off the drawing thread
+enum Job {
+    Load(NaiveDate),
+    /// Simulate a game of a date with some number of iterations
+    Predict(NaiveDate, Box<Game>, usize),
+}
+
+enum Event {
+    Key(Key),
+    /// Time to refresh the scores and live games
+    Tick,
+    Games(NaiveDate, Result<Vec<Game>, String>),
+    Predicted {
+        date: NaiveDate,
+        game_id: String,
+        result: Result<Option<Box<Detail>>, String>,
+    },
+}
+
+/// Everything shown about a simulated game
+struct Detail {
+    away: Team,
+    home: Team,
+    situation: Option<Situation>,
+    home_win_probability: Option<f64>,
+    expected_runs: [f64; 2],
+    iterations: usize,
+    /// Each team's lineup against the other team's starter
+    matchups: [Vec<Matchup>; 2],
+}
+
+enum RowState {
+    Pending,
+    MissingLineups,
+    Failed(String),
+    Ready(Box<Detail>),
+}
+
+struct Row {
+    game: Game,
+    state: RowState,
+    /// Whether a simulation of the game is queued or running
+    running: bool,
+}
+
+struct App {
+    date: NaiveDate,
+    iterations: usize,
+    rows: Vec<Row>,
+    selected: usize,
+    loading: bool,
+    error: Option<String>,
+    quit: bool,
+}
+
+impl App {
+    fn new(date: NaiveDate, iterations: usize) -> App {
+        App {
+            date,
+            iterations,
+            rows: Vec::new(),
+            selected: 0,
+            loading: true,
+            error: None,
+            quit: false,
+        }
+    }
+
+    /// Update the state, and return the jobs to run
+    fn handle(&mut self, event: Event) -> Vec<Job> {
+        match event {
+            Event::Key(key) => self.key(key),
+            Event::Tick if !self.loading => {
+                self.loading = true;
+                vec![Job::Load(self.date)]
+            }
+            Event::Tick => Vec::new(),
+            Event::Games(date, _) if date != self.date => Vec::new(),
+            Event::Games(_, Ok(games)) => {
+                self.loading = false;
+                self.error = None;
+                self.merge(games)
+            }
+            Event::Games(_, Err(e)) => {
+                self.loading = false;
+                self.error = Some(e);
+                Vec::new()
+            }
+            Event::Predicted {
+                date,
+                game_id,
+                result,
+            } => {
+                let row = self.rows.iter_mut().find(|r| r.game.game_id == game_id);
+                if let (true, Some(row)) = (date == self.date, row) {
+                    row.running = false;
+                    row.state = match result {
+                        Ok(Some(detail)) => RowState::Ready(detail),
+                        Ok(None) => RowState::MissingLineups,
+                        Err(e) => RowState::Failed(e),
+                    };
+                }
+                Vec::new()
+            }
+        }
+    }
+
+    fn key(&mut self, key: Key) -> Vec<Job> {
+        match key {
+            Key::Up | Key::Char('k') => {
+                self.selected = self.selected.saturating_sub(1);
+                Vec::new()
+            }
+            Key::Down | Key::Char('j') => {
+                self.selected = (self.selected + 1).min(self.rows.len().saturating_sub(1));
+                Vec::new()
+            }
+            Key::Left | Key::Char('h') => self.show(self.date.checked_sub_days(Days::new(1))),
+            Key::Right | Key::Char('l') => self.show(self.date.checked_add_days(Days::new(1))),
+            Key::Char('t') => self.show(Some(dates::today())),
+            Key::Char('+') => {
+                self.iterations = (self.iterations * 10).min(MAX_ITERATIONS);
+                self.rerun(Some(self.selected))
+            }
+            Key::Char('-') => {
+                self.iterations = (self.iterations / 10).max(MIN_ITERATIONS);
+                self.rerun(Some(self.selected))
+            }
+            Key::Char('r') => self.rerun(Some(self.selected)),
+            Key::Char('a') => self.rerun(None),
+            // Ctrl-C, as the terminal is in raw mode
+            Key::Char('q') | Key::Char('\x1B') | Key::Char('\x03') => {
+                self.quit = true;
+                Vec::new()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Switch to the games of another date
+    fn show(&mut self, date: Option<NaiveDate>) -> Vec<Job> {
+        let Some(date) = date else {
+            return Vec::new();
+        };
+        self.date = date;
+        self.rows.clear();
+        self.selected = 0;
+        self.loading = true;
+        self.error = None;
+        vec![Job::Load(date)]
+    }
+
+    /// Take in a fresh schedule, keeping what is known of the games, and
+    /// simulate the games that are new or in progress
+    fn merge(&mut self, games: Vec<Game>) -> Vec<Job> {
+        let selected = self.rows.get(self.selected).map(|r| r.game.game_id.clone());
+        let mut old: Vec<Row> = self.rows.drain(..).collect();
+        for game in games {
+            let state = match old.iter().position(|r| r.game.game_id == game.game_id) {
+                Some(idx) => {
+                    let row = old.swap_remove(idx);
+                    (row.state, row.running)
+                }
+                None => (RowState::Pending, false),
+            };
+            self.rows.push(Row {
+                game,
+                state: state.0,
+                running: state.1,
+            });
+        }
+        self.selected = selected
+            .and_then(|id| self.rows.iter().position(|r| r.game.game_id == id))
+            .unwrap_or(0);
+
+        let mut jobs = Vec::new();
+        for row in self.rows.iter_mut() {
+            let stale = match row.state {
+                RowState::Pending | RowState::MissingLineups => true,
+                RowState::Failed(_) => false,
+                RowState::Ready(_) => row.game.is_live(),
+            };
+            if stale && !row.running && row.game.status != "Postponed" {
+                row.running = true;
+                jobs.push(Job::Predict(
+                    self.date,
+                    Box::new(row.game.clone()),
+                    self.iterations,
+                ));
+            }
+        }
+        jobs
+    }
+
+    /// Simulate one game again, or all of them
+    fn rerun(&mut self, idx: Option<usize>) -> Vec<Job> {
+        let mut jobs = Vec::new();
+        for (k, row) in self.rows.iter_mut().enumerate() {
+            if idx.is_none_or(|idx| idx == k) && !row.running {
+                row.running = true;
+                jobs.push(Job::Predict(
+                    self.date,
+                    Box::new(row.game.clone()),
+                    self.iterations,
+                ));
+            }
+        }
+        jobs
+    }
+}
+
+/// Simulate a game, or None if its lineups are not posted yet
+fn predict(oracle: &Oracle, game: &Game) -> Result<Option<Box<Detail>>, String> {
+    let prediction = oracle.predict(game)?;
+    let (Some([away, home]), Some(result)) = (prediction.teams, prediction.result) else {
+        return Ok(None);
+    };
+    let cfg = oracle.config();
+    let venue = cfg.venue(game.venue_id.as_deref());
+    let matchups = [
+        explain::matchups(cfg, &away, &home.starting_pitcher, &venue, false),
+        explain::matchups(cfg, &home, &away.starting_pitcher, &venue, true),
+    ];
+    Ok(Some(Box::new(Detail {
+        away,
+        home,
+        situation: prediction.situation,
+        home_win_probability: result.home_win_probability,
+        expected_runs: [result.expected_away_runs, result.expected_home_runs],
+        iterations: result.iterations,
+        matchups,
+    })))
+}
+
+/// Run jobs one after the other, skipping simulations of dates that are no
+/// longer shown
+fn spawn_worker(
+    cfg: Config,
+    slate: Slate,
+    shown: Arc<AtomicI32>,
+    events: Sender<Event>,
+) -> Sender<Job> {
+    let (tx, jobs) = mpsc::channel();
+    thread::spawn(move || {
+        for job in jobs {
+            let event = match job {
+                Job::Load(date) => {
+                    let cfg = Config {
+                        date: date.format("%F").to_string(),
+                        ..cfg.clone()
+                    };
+                    Event::Games(date, slate.games(&cfg))
+                }
+                Job::Predict(date, game, iterations) => {
+                    if shown.load(Ordering::Relaxed) != date.num_days_from_ce() {
+                        continue;
+                    }
+                    let cfg = Config {
+                        date: date.format("%F").to_string(),
+                        iterations,
+                        ..cfg.clone()
+                    };
+                    Event::Predicted {
+                        date,
+                        game_id: game.game_id.clone(),
+                        result: predict(&Oracle::with_config(cfg), &game),
+                    }
+                }
+            };
+            if events.send(event).is_err() {
+                break;
+            }
+        }
+    });
+    tx
+}
+
+fn spawn_input(events: Sender<Event>) {
+    thread::spawn(move || {
+        let mut buf = [0u8; 64];
+        while let Ok(n) = io::stdin().read(&mut buf) {
+            if n == 0 {
+                break;
+            }
+            for key in keys(&buf[..n]) {
+                if events.send(Event::Key(key)).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+}
+
+fn spawn_ticker(events: Sender<Event>, interval: Duration) {
+    thread::spawn(move || loop {
+        thread::sleep(interval);
+        if events.send(Event::Tick).is_err() {
+            break;
+        }
+    });
+}
+
+/// The terminal in raw mode on the alternate screen, restored when dropped
+struct Terminal {
+    /// Settings to restore, as printed by `stty -g`
+    saved: String,
+}
+
+impl Terminal {
+    fn enter() -> Result<Terminal, String> {
+        if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+            return Err("The dashboard needs a terminal".to_string());
+        }
+        let saved = stty(&["-g"])?.trim().to_string();
+        stty(&["raw", "-echo"])?;
+        print!("\x1B[?1049h\x1B[?25l");
+        let _ = io::stdout().flush();
+        Ok(Terminal { saved })
+    }
+
+    /// Rows and columns
+    fn size() -> (usize, usize) {
+        let size = stty(&["size"]).unwrap_or_default();
+        let mut dims = size.split_whitespace().filter_map(|v| v.parse().ok());
+        match (dims.next(), dims.next()) {
+            (Some(rows), Some(cols)) if rows > 0 && cols > 0 => (rows, cols),
+            _ => (24, 80),
+        }
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        print!("\x1B[?25h\x1B[?1049l");
+        let _ = io::stdout().flush();
+        let _ = stty(&[&self.saved]);
+    }
+}
+
+/// Run stty on the terminal, which it finds on its standard input
+fn stty(args: &[&str]) -> Result<String, String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .output()
+        .map_err(|e| format!("Cannot run stty: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// `s` cut or padded to `width` columns. Escape codes take no room, and are
+/// all kept so that colors are always reset.
+fn fit(s: &str, width: usize) -> String {
+    let mut out = String::new();
+    let mut n = 0;
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1B' {
+            out.push(c);
+            for c in chars.by_ref() {
+                out.push(c);
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else if n < width {
+            out.push(c);
+            n += 1;
+        }
+    }
+    out.extend(std::iter::repeat_n(' ', width - n));
+    out
+}
+
+fn row_line(row: &Row, style: Style) -> String {
+    let mut gline = GameLine::new(&row.game, style);
+    match &row.state {
+        _ if row.game.status == "Postponed" => gline.postponed(),
+        RowState::Pending => gline.predicting(),
+        RowState::MissingLineups => gline.missing_lineups(),
+        RowState::Failed(_) => gline.backend_error(),
+        RowState::Ready(d) => {
+            if row.game.is_final() {
+                gline.settled();
+            }
+            gline.expected_runs(d.expected_runs);
+            gline.prediction(d.home_win_probability);
+        }
+    }
+    if let (Some(a), Some(h)) = (row.game.away_score, row.game.home_score) {
+        gline.detail(format!("{}-{}", a, h));
+    }
+    gline.render()
+}
+
+fn detail_lines(row: &Row, tz: Option<Tz>) -> Vec<String> {
+    let game = &row.game;
+    let mut status = format!(
+        "Game {}, first pitch {}, {}",
+        game.game_id,
+        lookup::start(game, tz),
+        game.status
+    );
+    if let (Some(a), Some(h)) = (game.away_score, game.home_score) {
+        status.push_str(&format!(" {}-{}", a, h));
+    }
+    let mut lines = vec![
+        format!("{} @ {}", game.away_name, game.home_name),
+        status,
+        String::new(),
+    ];
+    let d = match &row.state {
+        RowState::Ready(d) => d,
+        RowState::Pending => {
+            lines.push("Simulating...".to_string());
+            return lines;
+        }
+        RowState::MissingLineups => {
+            lines.push("Lineups are not posted yet".to_string());
+            return lines;
+        }
+        RowState::Failed(e) => {
+            lines.push(format!("Error: {}", e));
+            return lines;
+        }
+    };
+
+    // Once the game is over, there are no odds or runs left to expect
+    let settled = game.is_final();
+    let table = |label: &str, a: String, h: String| format!("{:<14}{:>9}{:>9}", label, a, h);
+    lines.push(table("", d.away.name.clone(), d.home.name.clone()));
+    if let Some(hwp) = d.home_win_probability {
+        lines.push(table(
+            "Win",
+            format!("{:.1}%", 100.0 * (1.0 - hwp)),
+            format!("{:.1}%", 100.0 * hwp),
+        ));
+        if !settled {
+            lines.push(table(
+                "Fair odds",
+                odds::american(1.0 - hwp),
+                odds::american(hwp),
+            ));
+        }
+    }
+    if !settled {
+        lines.push(table(
+            "Expected runs",
+            format!("{:.2}", d.expected_runs[0]),
+            format!("{:.2}", d.expected_runs[1]),
+        ));
+    }
+    let mut sims = format!("{} simulations", d.iterations);
+    if row.running {
+        sims.push_str(", rerunning...");
+    }
+    lines.push(sims);
+
+    for (idx, team) in [&d.away, &d.home].into_iter().enumerate() {
+        let opponent = if idx == 0 { &d.home } else { &d.away };
+        lines.push(String::new());
+        lines.push(format!(
+            "{} vs {}",
+            team.name, opponent.starting_pitcher.name
+        ));
+        lines.push(format!(
+            "{:>2}  {:<16}{:>6}{:>6}{:>6}{:>6}{:>6}{:>6}",
+            "#", "Batter", "BB", "1B", "2B", "3B", "HR", "K"
+        ));
+        for m in &d.matchups[idx] {
+            let p = &m.probs;
+            let pcts: String = [
+                p.prob_walk,
+                p.prob_single,
+                p.prob_double,
+                p.prob_triple,
+                p.prob_homerun,
+                p.prob_strikeout,
+            ]
+            .iter()
+            .map(|x| format!("{:>6.1}", 100.0 * x))
+            .collect();
+            lines.push(format!("{:>2}  {:<16.16}{}", m.slot, m.batter, pcts));
+        }
+    }
+    lines
+}
+
+/// Score, inning and runners of a game in progress
+fn live_line(row: &Row) -> Option<String> {
+    let RowState::Ready(d) = &row.state else {
+        return None;
+    };
+    let s = d.situation.as_ref().filter(|_| row.game.is_live())?;
+    let bases: String = s.bases.iter().map(|&b| if b { '◆' } else { '◇' }).collect();
+    let mut line = format!(
+        "{:<4}@ {:<4}{:<20} {}",
+        d.away.name,
+        d.home.name,
+        watch::describe(s),
+        bases
+    );
+    if let Some(hwp) = d.home_win_probability {
+        line.push_str(&format!("  {} {:.0}%", d.home.name, 100.0 * hwp));
+    }
+    Some(line)
+}
+
+/// The whole screen, as `height` lines of `width` columns
+fn screen(app: &App, style: Style, tz: Option<Tz>, width: usize, height: usize) -> Vec<String> {
+    let mut header = format!(
+        " MLB Oracle   {}   {} games   {} simulations",
+        app.date.format("%a %F"),
+        app.rows.len(),
+        app.iterations
+    );
+    if app.loading {
+        header.push_str("   loading...");
+    }
+    if let Some(e) = &app.error {
+        header.push_str(&format!("   {}", e));
+    }
+    let footer =
+        " ↑↓ game  ←→ day  t today  +/- simulations  r rerun  a rerun all  q quit".to_string();
+    let rule = "─".repeat(width);
+
+    let live: Vec<String> = app.rows.iter().filter_map(live_line).take(N_LIVE).collect();
+    let mut bottom = Vec::new();
+    if !live.is_empty() {
+        bottom.push(rule.clone());
+        bottom.push(" Live".to_string());
+        bottom.extend(live.into_iter().map(|l| format!(" {}", l)));
+    }
+    bottom.push(rule.clone());
+    bottom.push(footer);
+
+    let body = height.saturating_sub(2 + bottom.len());
+    // Scroll the list to keep the selected game in view
+    let first = (app.selected + 1).saturating_sub(body);
+    let list: Vec<String> = app
+        .rows
+        .iter()
+        .enumerate()
+        .skip(first)
+        .take(body)
+        .map(|(idx, row)| {
+            let marker = if idx == app.selected { "▶" } else { " " };
+            format!("{} {}", marker, row_line(row, style))
+        })
+        .collect();
+    let details = app
+        .rows
+        .get(app.selected)
+        .map_or(Vec::new(), |row| detail_lines(row, tz));
+
+    let list_width = LIST_WIDTH.min(width);
+    let details_width = width.saturating_sub(list_width + 3);
+    let mut lines = vec![fit(&header, width), rule];
+    for k in 0..body {
+        let left = list.get(k).map_or("", |s| s.as_str());
+        let right = details.get(k).map_or("", |s| s.as_str());
+        let line = format!("{} │ {}", fit(left, list_width), fit(right, details_width));
+        lines.push(fit(&line, width));
+    }
+    lines.extend(bottom.iter().map(|l| fit(l, width)));
+    lines.truncate(height);
+    lines
+}
+
+pub fn dashboard(cfg: &Config, interval: Duration) -> Result<(), String> {
+    let date = cfg.naive_date()?;
+    let slate = Slate::new(cfg)?;
+    let mut style = Style::detect(cfg.color, Display::Compact, cfg.colormap);
+    style.interactive = true;
+
+    let (events_tx, events) = mpsc::channel();
+    let shown = Arc::new(AtomicI32::new(date.num_days_from_ce()));
+    let jobs = spawn_worker(cfg.clone(), slate, Arc::clone(&shown), events_tx.clone());
+    let terminal = Terminal::enter()?;
+    spawn_input(events_tx.clone());
+    spawn_ticker(events_tx, interval);
+
+    let mut app = App::new(date, cfg.iterations);
+    let _ = jobs.send(Job::Load(date));
+    while !app.quit {
+        let (height, width) = Terminal::size();
+        let lines = screen(&app, style, cfg.tz, width, height);
+        print!("\x1B[H{}\x1B[J", lines.join("\r\n"));
+        let _ = io::stdout().flush();
+
+        let Ok(event) = events.recv() else {
+            break;
+        };
+        let new = app.handle(event);
+        shown.store(app.date.num_days_from_ce(), Ordering::Relaxed);
+        for job in new {
+            let _ = jobs.send(job);
+        }
+    }
+    drop(terminal);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, team};
+    use crate::output::Colormap;
+
+    fn game(id: &str, status: &str, abstract_status: &str) -> Game {
+        Game {
+            game_id: id.to_string(),
+            status: status.to_string(),
+            abstract_status: abstract_status.to_string(),
+            ..fixtures::game("Chicago Cubs", "Texas Rangers")
+        }
+    }
+
+    fn detail() -> Detail {
+        let cfg = Config::default();
+        let (away, home) = (team("CHC"), team("TEX"));
+        let venue = cfg.venue(None);
+        Detail {
+            matchups: [
+                explain::matchups(&cfg, &away, &home.starting_pitcher, &venue, false),
+                explain::matchups(&cfg, &home, &away.starting_pitcher, &venue, true),
+            ],
+            away,
+            home,
+            situation: None,
+            home_win_probability: Some(0.6),
+            expected_runs: [4.1, 4.6],
+            iterations: 1000,
+        }
+    }
+
+    fn date() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 7, 1).unwrap()
+    }
+
+    #[test]
+    fn dashboard_keys() {
+        assert_eq!(
+            keys(b"\x1B[A\x1BOBjq"),
+            [Key::Up, Key::Down, Key::Char('j'), Key::Char('q')]
+        );
+        assert_eq!(
+            keys(b"\x1B[D\x1B[C+"),
+            [Key::Left, Key::Right, Key::Char('+')]
+        );
+        assert_eq!(keys(b"\x1B"), [Key::Char('\x1B')]);
+        assert!(keys("é".as_bytes()).is_empty());
+    }
+
+    #[test]
+    fn dashboard_navigation() {
+        let mut app = App::new(date(), 1000);
+        let jobs = app.handle(Event::Games(
+            date(),
+            Ok(vec![
+                game("1", "Scheduled", "Preview"),
+                game("2", "Postponed", "Final"),
+                game("3", "In Progress", "Live"),
+            ]),
+        ));
+        // Postponed games are not simulated
+        assert_eq!(jobs.len(), 2);
+        assert!(!app.loading);
+        app.handle(Event::Key(Key::Down));
+        app.handle(Event::Key(Key::Down));
+        app.handle(Event::Key(Key::Down));
+        assert_eq!(app.selected, 2);
+
+        // Results of another date are dropped
+        app.handle(Event::Predicted {
+            date: date().succ_opt().unwrap(),
+            game_id: "3".to_string(),
+            result: Ok(None),
+        });
+        assert!(matches!(app.rows[2].state, RowState::Pending));
+        app.handle(Event::Predicted {
+            date: date(),
+            game_id: "3".to_string(),
+            result: Ok(Some(Box::new(detail()))),
+        });
+        assert!(matches!(app.rows[2].state, RowState::Ready(_)));
+
+        // Live games are simulated again on each refresh, and the selection
+        // follows the game
+        let jobs = app.handle(Event::Tick);
+        assert!(matches!(jobs[..], [Job::Load(d)] if d == date()));
+        let jobs = app.handle(Event::Games(
+            date(),
+            Ok(vec![
+                game("3", "In Progress", "Live"),
+                game("1", "Scheduled", "Preview"),
+            ]),
+        ));
+        assert!(matches!(&jobs[..], [Job::Predict(_, g, 1000)] if g.game_id == "3"));
+        assert_eq!(app.selected, 0);
+
+        // More simulations rerun the selected game, once it is done
+        assert!(app.handle(Event::Key(Key::Char('+'))).is_empty());
+        app.handle(Event::Predicted {
+            date: date(),
+            game_id: "3".to_string(),
+            result: Ok(Some(Box::new(detail()))),
+        });
+        let jobs = app.handle(Event::Key(Key::Char('+')));
+        assert!(matches!(&jobs[..], [Job::Predict(_, g, 100000)] if g.game_id == "3"));
+
+        let jobs = app.handle(Event::Key(Key::Left));
+        assert!(matches!(jobs[..], [Job::Load(d)] if d == date().pred_opt().unwrap()));
+        assert!(app.rows.is_empty());
+        app.handle(Event::Key(Key::Char('q')));
+        assert!(app.quit);
+    }
+
+    #[test]
+    fn dashboard_screen() {
+        assert_eq!(fit("\x1B[31mabc\x1B[0m", 2), "\x1B[31mab\x1B[0m");
+        assert_eq!(fit("ab", 4), "ab  ");
+
+        let mut app = App::new(date(), 1000);
+        app.handle(Event::Games(
+            date(),
+            Ok(vec![
+                game("1", "In Progress", "Live"),
+                game("2", "Scheduled", "Preview"),
+            ]),
+        ));
+        let mut d = detail();
+        d.situation = Some(Situation {
+            inning: 7,
+            top: true,
+            outs: 1,
+            bases: [true, false, true],
+            away_score: 3,
+            home_score: 2,
+            batting_order: [0, 0],
+            pitchers: [None, None],
+            batters_faced: [0, 0],
+            pitch_counts: [0, 0],
+        });
+        app.handle(Event::Predicted {
+            date: date(),
+            game_id: "1".to_string(),
+            result: Ok(Some(Box::new(d))),
+        });
+        let style = Style {
+            color: false,
+            interactive: true,
+            display: Display::Compact,
+            colormap: Colormap::default(),
+        };
+        let lines = screen(&app, style, None, 120, 40);
+        assert_eq!(lines.len(), 40);
+        assert!(lines.iter().all(|l| l.chars().count() == 120));
+        assert!(lines[0].starts_with(" MLB Oracle   Mon 2024-07-01   2 games   1000 simulations"));
+        assert!(lines[2].starts_with("▶         Cubs  40% □□□■■ ■■■□□  60% Rangers       8.7"));
+        assert!(lines[2].contains("│ Chicago Cubs @ Texas Rangers"));
+        assert!(lines[3].starts_with("          Cubs     PREDICTING...     Rangers"));
+        assert!(lines
+            .iter()
+            .any(|l| l.contains("│ Win               40.0%    60.0%")));
+        assert!(lines.iter().any(|l| l.contains("│ CHC vs TEX Starter")));
+        assert!(lines
+            .iter()
+            .any(|l| l.starts_with(" CHC @ TEX 3-2 Top 7, 1 out     ◆◇◆  TEX 60%")));
+        assert!(lines[39].starts_with(" ↑↓ game"));
+    }
+
+    #[test]
+    fn dashboard_final() {
+        let row = Row {
+            game: Game {
+                away_score: Some(5),
+                home_score: Some(3),
+                ..game("1", "Final", "Final")
+            },
+            state: RowState::Ready(Box::new(detail())),
+            running: false,
+        };
+        let lines = detail_lines(&row, None);
+        assert!(lines.iter().any(|l| l.starts_with("Win")));
+        assert!(!lines
+            .iter()
+            .any(|l| l.starts_with("Fair odds") || l.starts_with("Expected runs")));
+        let style = Style {
+            color: false,
+            interactive: true,
+            display: Display::Numbers,
+            colormap: Colormap::default(),
+        };
+        let line = row_line(&row, style);
+        assert!(!line.contains("Fair") && !line.contains("Runs"));
+    }
+}
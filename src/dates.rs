@@ -0,0 +1,107 @@
+//! Dates of the slate and times of first pitch
+
+use chrono::{DateTime, Days, Duration, Local, NaiveDate, Utc};
+use chrono_tz::Tz;
+
+/// Hours past midnight Pacific time at which the baseball day rolls over,
+/// once the latest West Coast games are over
+const ROLLOVER_HOURS: i64 = 3;
+
+/// The date of the current slate: until 3 AM Pacific time, that of the
+/// day before, whatever the local time zone
+pub fn today() -> NaiveDate {
+    slate_date(Utc::now())
+}
+
+fn slate_date(now: DateTime<Utc>) -> NaiveDate {
+    (now.with_timezone(&chrono_tz::America::Los_Angeles) - Duration::hours(ROLLOVER_HOURS))
+        .date_naive()
+}
+
+/// A date as YYYY-MM-DD, or relative to `today`: today, yesterday,
+/// tomorrow, or a number of days such as +2 or -1
+pub fn parse(s: &str, today: NaiveDate) -> Result<NaiveDate, String> {
+    let invalid = || format!("Invalid date: {}", s);
+    let shift = |days: u64, forward: bool| {
+        let days = Days::new(days);
+        if forward {
+            today.checked_add_days(days)
+        } else {
+            today.checked_sub_days(days)
+        }
+        .ok_or_else(invalid)
+    };
+    match s.to_lowercase().as_str() {
+        "today" => Ok(today),
+        "yesterday" => shift(1, false),
+        "tomorrow" => shift(1, true),
+        _ => match s.as_bytes().first() {
+            Some(b'+') | Some(b'-') => {
+                let days = s[1..].parse().map_err(|_| invalid())?;
+                shift(days, s.starts_with('+'))
+            }
+            _ => NaiveDate::parse_from_str(s, "%F").map_err(|_| invalid()),
+        },
+    }
+}
+
+/// A time zone by IANA name, e.g. America/Chicago, or None for the local
+/// time zone
+pub fn parse_tz(s: &str) -> Result<Option<Tz>, String> {
+    if s.eq_ignore_ascii_case("local") {
+        return Ok(None);
+    }
+    s.parse()
+        .map(Some)
+        .map_err(|_| format!("Unknown time zone: {}", s))
+}
+
+/// A time of day in the given time zone, or the local one
+pub fn format_time(t: DateTime<Utc>, tz: Option<Tz>) -> String {
+    match tz {
+        Some(tz) => t.with_timezone(&tz).format("%H:%M %Z").to_string(),
+        None => t.with_timezone(&Local).format("%H:%M").to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn dates_relative() {
+        let today = date(2024, 3, 1);
+        assert_eq!(parse("2024-04-01", today), Ok(date(2024, 4, 1)));
+        assert_eq!(parse("Yesterday", today), Ok(date(2024, 2, 29)));
+        assert_eq!(parse("tomorrow", today), Ok(date(2024, 3, 2)));
+        assert_eq!(parse("+2", today), Ok(date(2024, 3, 3)));
+        assert_eq!(parse("-1", today), Ok(date(2024, 2, 29)));
+        assert_eq!(parse("today", today), Ok(today));
+        for s in ["04/01/2024", "+", "+x", "2024-04", "someday"] {
+            assert!(parse(s, today).is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn dates_rollover() {
+        let utc = |s: &str| s.parse::<DateTime<Utc>>().unwrap();
+        // 1 AM Pacific: the late games of the day before are still on
+        assert_eq!(slate_date(utc("2024-07-02T08:00:00Z")), date(2024, 7, 1));
+        assert_eq!(slate_date(utc("2024-07-02T11:00:00Z")), date(2024, 7, 2));
+    }
+
+    #[test]
+    fn dates_time_zones() {
+        let t = "2024-07-01T23:05:00Z".parse::<DateTime<Utc>>().unwrap();
+        let tz = parse_tz("America/New_York").unwrap();
+        assert_eq!(format_time(t, tz), "19:05 EDT");
+        let tz = parse_tz("America/Los_Angeles").unwrap();
+        assert_eq!(format_time(t, tz), "16:05 PDT");
+        assert_eq!(parse_tz("local"), Ok(None));
+        assert!(parse_tz("Mars/Olympus_Mons").is_err());
+    }
+}
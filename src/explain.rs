@@ -0,0 +1,355 @@
+use serde_json as json;
+
+use crate::{
+    config::Config,
+    mlbstats::{self, BatterStats, PitcherStats, Team},
+    park::Venue,
+    simba::{GameState, OutcomeProbs, Rates, SimbaConfig},
+};
+
+/// How many of the most influential players to list
+const N_IMPACTS: usize = 5;
+
+/// One lineup slot against the opposing starter
+pub struct Matchup {
+    pub slot: usize,
+    pub batter: String,
+    pub probs: OutcomeProbs,
+    pub batter_rates: Rates,
+}
+
+/// Change in a player's team win probability when they are replaced by a
+/// player with the average rates of both lineups (or both starters)
+pub struct Impact {
+    pub team: String,
+    pub name: String,
+    pub delta: f64,
+}
+
+pub struct Explanation {
+    /// Per-team arrays are indexed away first, then home
+    pub teams: [String; 2],
+    pub starters: [String; 2],
+    pub starter_rates: [Rates; 2],
+    /// Each team's lineup against the other team's starter
+    pub matchups: [Vec<Matchup>; 2],
+    pub home_win_probability: f64,
+    pub expected_runs: [f64; 2],
+    /// Sorted by decreasing magnitude
+    pub impacts: Vec<Impact>,
+}
+
+impl Explanation {
+    pub fn compute(
+        cfg: &Config,
+        away: &Team,
+        home: &Team,
+        venue: &Venue,
+    ) -> Result<Explanation, String> {
+        let mut gs = GameState::new(away, home);
+        gs.venue = *venue;
+        gs.fatigue = cfg.fatigue;
+        gs.matchup = cfg.matchup;
+        let base = SimbaConfig {
+            n_iter: cfg.iterations,
+            seed: cfg.seed,
+        }
+        .run(&gs)?;
+        let hwp = base.home_win_probability.ok_or("No prediction")?;
+
+        // Replacements are simulated with the same seed as the baseline so
+        // that the differences are not swamped by sampling noise.
+        let sim = SimbaConfig {
+            n_iter: cfg.iterations,
+            seed: Some(base.seed),
+        };
+        let avg_batter = average_batter(away.batters.iter().chain(home.batters.iter()));
+        let avg_pitcher = average_pitcher([&away.starting_pitcher, &home.starting_pitcher]);
+
+        let mut impacts = Vec::new();
+        for (idx, team) in [away, home].into_iter().enumerate() {
+            // Win probability of `team` with `replaced` in its place
+            let team_wp = |replaced: &Team| -> Result<f64, String> {
+                let mut gs = if idx == 0 {
+                    GameState::new(replaced, home)
+                } else {
+                    GameState::new(away, replaced)
+                };
+                gs.venue = *venue;
+                gs.fatigue = cfg.fatigue;
+                gs.matchup = cfg.matchup;
+                let hwp = sim.run(&gs)?.home_win_probability.ok_or("No prediction")?;
+                Ok(if idx == 0 { 1.0 - hwp } else { hwp })
+            };
+            let actual = if idx == 0 { 1.0 - hwp } else { hwp };
+
+            for (slot, batter) in team.batters.iter().enumerate() {
+                let mut replaced = team.clone();
+                replaced.batters[slot] = avg_batter.clone();
+                impacts.push(Impact {
+                    team: team.name.clone(),
+                    name: batter.name.clone(),
+                    delta: actual - team_wp(&replaced)?,
+                });
+            }
+
+            let mut replaced = team.clone();
+            replaced.starting_pitcher = avg_pitcher.clone();
+            impacts.push(Impact {
+                team: team.name.clone(),
+                name: team.starting_pitcher.name.clone(),
+                delta: actual - team_wp(&replaced)?,
+            });
+        }
+        impacts.sort_by(|a, b| b.delta.abs().total_cmp(&a.delta.abs()));
+
+        Ok(Explanation {
+            teams: [away.name.clone(), home.name.clone()],
+            starters: [
+                away.starting_pitcher.name.clone(),
+                home.starting_pitcher.name.clone(),
+            ],
+            starter_rates: [
+                Rates::pitcher(&away.starting_pitcher),
+                Rates::pitcher(&home.starting_pitcher),
+            ],
+            matchups: [
+                matchups(cfg, away, &home.starting_pitcher, venue, false),
+                matchups(cfg, home, &away.starting_pitcher, venue, true),
+            ],
+            home_win_probability: hwp,
+            expected_runs: [base.expected_away_runs, base.expected_home_runs],
+            impacts,
+        })
+    }
+
+    pub fn to_json(&self) -> json::Value {
+        let matchups = |idx: usize| -> Vec<json::Value> {
+            self.matchups[idx]
+                .iter()
+                .map(|m| {
+                    let p = &m.probs;
+                    json::json!({
+                        "slot": m.slot,
+                        "batter": m.batter,
+                        "walk": p.prob_walk,
+                        "single": p.prob_single,
+                        "double": p.prob_double,
+                        "triple": p.prob_triple,
+                        "homerun": p.prob_homerun,
+                        "strikeout": p.prob_strikeout,
+                        "out": p.prob_tagout + p.prob_flyout,
+                    })
+                })
+                .collect()
+        };
+        let impacts: Vec<json::Value> = self
+            .impacts
+            .iter()
+            .map(|imp| json::json!({"team": imp.team, "name": imp.name, "delta": imp.delta}))
+            .collect();
+        json::json!({
+            "teams": self.teams,
+            "starters": self.starters,
+            "home_win_probability": self.home_win_probability,
+            "expected_runs": self.expected_runs,
+            "matchups": {"away": matchups(0), "home": matchups(1)},
+            "impacts": impacts,
+        })
+    }
+
+    pub fn print(&self) {
+        println!(
+            "{} @ {}: {} {:.1}%, {} {:.1}%",
+            self.teams[0],
+            self.teams[1],
+            self.teams[0],
+            100.0 * (1.0 - self.home_win_probability),
+            self.teams[1],
+            100.0 * self.home_win_probability
+        );
+        println!(
+            "Expected runs: {} {:.2}, {} {:.2}",
+            self.teams[0], self.expected_runs[0], self.teams[1], self.expected_runs[1]
+        );
+
+        for idx in 0..2 {
+            let opp = 1 - idx;
+            println!();
+            println!("{} batting against {}", self.teams[idx], self.starters[opp]);
+            println!("{:>66}{}", "pitcher rates  |", rates(&self.starter_rates[opp]));
+            println!(
+                "{:>2}  {:<16}{:>6}{:>6}{:>6}{:>6}{:>6}{:>6}{:>6}  |{:>6}{:>6}{:>6}{:>6}{:>6}{:>6}",
+                "#", "Batter", "BB", "1B", "2B", "3B", "HR", "K", "OUT",
+                "BB", "K", "H", "2B/H", "3B/H", "HR/H"
+            );
+            for m in &self.matchups[idx] {
+                let p = &m.probs;
+                println!(
+                    "{:>2}  {:<16}{}{}{}{}{}{}{}  |{}",
+                    m.slot,
+                    m.batter,
+                    pct(p.prob_walk),
+                    pct(p.prob_single),
+                    pct(p.prob_double),
+                    pct(p.prob_triple),
+                    pct(p.prob_homerun),
+                    pct(p.prob_strikeout),
+                    pct(p.prob_tagout + p.prob_flyout),
+                    rates(&m.batter_rates)
+                );
+            }
+        }
+
+        println!();
+        println!("Most influential players (win probability vs. an average replacement):");
+        for imp in self.impacts.iter().take(N_IMPACTS) {
+            println!("{:>+7.1}%  {:<4}{}", 100.0 * imp.delta, imp.team, imp.name);
+        }
+    }
+}
+
+/// Each batter of a lineup against a pitcher
+pub(crate) fn matchups(
+    cfg: &Config,
+    team: &Team,
+    pitcher: &PitcherStats,
+    venue: &Venue,
+    batting_home: bool,
+) -> Vec<Matchup> {
+    team.batters
+        .iter()
+        .enumerate()
+        .map(|(slot, b)| Matchup {
+            slot: slot + 1,
+            batter: b.name.clone(),
+            probs: cfg.matchup.probs(pitcher, b, &venue.park, venue.edge(batting_home)),
+            batter_rates: Rates::batter(b),
+        })
+        .collect()
+}
+
+fn pct(p: f64) -> String {
+    format!("{:>6.1}", 100.0 * p)
+}
+
+fn rates(r: &Rates) -> String {
+    [r.walk, r.strikeout, r.hit, r.double, r.triple, r.homerun]
+        .iter()
+        .map(|x| pct(*x))
+        .collect()
+}
+
+/// A batter whose rates are those of all the given batters combined
+pub(crate) fn average_batter<'a>(batters: impl Iterator<Item = &'a BatterStats>) -> BatterStats {
+    batters.fold(
+        BatterStats {
+            id: String::new(),
+            name: "average".to_string(),
+            hand: String::new(),
+            plate_appearances: 0,
+            bases_on_balls: 0,
+            hits: 0,
+            doubles: 0,
+            triples: 0,
+            homeruns: 0,
+            strikeouts: 0,
+        },
+        |mut acc, b| {
+            acc.plate_appearances += b.plate_appearances;
+            acc.bases_on_balls += b.bases_on_balls;
+            acc.hits += b.hits;
+            acc.doubles += b.doubles;
+            acc.triples += b.triples;
+            acc.homeruns += b.homeruns;
+            acc.strikeouts += b.strikeouts;
+            acc
+        },
+    )
+}
+
+/// A pitcher whose rates are those of all the given pitchers combined
+fn average_pitcher<'a>(pitchers: impl IntoIterator<Item = &'a PitcherStats>) -> PitcherStats {
+    pitchers.into_iter().fold(
+        PitcherStats {
+            id: String::new(),
+            name: "average".to_string(),
+            hand: String::new(),
+            batters_faced: 0,
+            bases_on_balls: 0,
+            hits: 0,
+            doubles: 0,
+            triples: 0,
+            homeruns: 0,
+            strikeouts: 0,
+        },
+        |mut acc, p| {
+            acc.batters_faced += p.batters_faced;
+            acc.bases_on_balls += p.bases_on_balls;
+            acc.hits += p.hits;
+            acc.doubles += p.doubles;
+            acc.triples += p.triples;
+            acc.homeruns += p.homeruns;
+            acc.strikeouts += p.strikeouts;
+            acc
+        },
+    )
+}
+
+pub fn explain(cfg: &Config, game_id: &str) -> Result<(), String> {
+    let feed = mlbstats::feed(cfg, game_id)?;
+    match feed.teams(cfg)? {
+        (Some(away), Some(home)) => {
+            let venue = cfg.venue(feed.venue().as_deref());
+            Explanation::compute(cfg, &away, &home, &venue)?.print();
+            Ok(())
+        }
+        _ => Err(format!("Lineups are not available for game {}", game_id)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{batter, team};
+
+    fn slugger(name: &str, homeruns: i32) -> BatterStats {
+        BatterStats {
+            homeruns,
+            ..batter(name)
+        }
+    }
+
+    #[test]
+    fn test_average_batter() {
+        let batters = [slugger("A", 10), slugger("B", 30)];
+        let avg = average_batter(batters.iter());
+        assert_eq!(avg.plate_appearances, 1200);
+        assert_eq!(avg.homeruns, 40);
+        let rates = Rates::batter(&avg);
+        assert!((rates.homerun - 40.0 / 300.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_explanation() {
+        let away = team("AWY");
+        let mut home = team("HOM");
+        home.batters[3] = slugger("Slugger", 60);
+
+        let cfg = Config {
+            iterations: 300,
+            seed: Some(1),
+            ..Config::default()
+        };
+        let expl = Explanation::compute(&cfg, &away, &home, &Venue::neutral()).unwrap();
+        assert_eq!(expl.matchups[0].len(), 9);
+        assert_eq!(expl.matchups[1][3].batter, "Slugger");
+        assert!(expl.matchups[1][3].probs.prob_homerun > expl.matchups[0][3].probs.prob_homerun);
+        assert!(expl.expected_runs.iter().all(|r| *r > 0.0));
+        assert_eq!(expl.impacts.len(), 20);
+        assert!(expl
+            .impacts
+            .windows(2)
+            .all(|w| w[0].delta.abs() >= w[1].delta.abs()));
+    }
+}
@@ -0,0 +1,258 @@
+//! Narrowing the slate down to some clubs, a league or a division
+
+use clap::ValueEnum;
+
+use crate::{
+    config::Config,
+    mlbstats::{self, Club, Game},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum League {
+    #[value(name = "AL")]
+    American,
+    #[value(name = "NL")]
+    National,
+}
+
+impl League {
+    fn abbreviation(self) -> &'static str {
+        match self {
+            League::American => "AL",
+            League::National => "NL",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Division {
+    East,
+    Central,
+    West,
+}
+
+/// Which clubs' games to keep. Criteria combine, e.g. `--league AL
+/// --division east` is the AL East.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Filter {
+    /// Abbreviations, full names or IDs
+    pub teams: Vec<String>,
+    pub league: Option<League>,
+    pub division: Option<Division>,
+}
+
+impl Filter {
+    pub fn is_empty(&self) -> bool {
+        self.teams.is_empty() && self.league.is_none() && self.division.is_none()
+    }
+
+    /// The clubs that pass the filter
+    pub fn select<'a>(&self, clubs: &'a [Club]) -> Result<Vec<&'a Club>, String> {
+        let mut selected: Vec<&Club> = if self.teams.is_empty() {
+            clubs.iter().collect()
+        } else {
+            let mut named = Vec::new();
+            for name in &self.teams {
+                named.push(resolve(clubs, name)?);
+            }
+            named
+        };
+        if let Some(league) = self.league {
+            selected.retain(|c| c.league == league.abbreviation());
+        }
+        if let Some(division) = self.division {
+            let region = format!("{:?}", division);
+            selected.retain(|c| c.division.ends_with(&region));
+        }
+        Ok(selected)
+    }
+}
+
+/// The club with this abbreviation, full name or ID, ignoring case
+pub fn resolve<'a>(clubs: &'a [Club], name: &str) -> Result<&'a Club, String> {
+    let name = name.trim();
+    let found = clubs.iter().find(|c| {
+        c.id == name || c.name.eq_ignore_ascii_case(name) || c.full_name.eq_ignore_ascii_case(name)
+    });
+    match found {
+        Some(club) => Ok(club),
+        None => {
+            let close = suggestions(clubs, name);
+            if close.is_empty() {
+                Err(format!("Unknown team: {}", name))
+            } else {
+                Err(format!(
+                    "Unknown team: {} (did you mean {}?)",
+                    name,
+                    close.join(", ")
+                ))
+            }
+        }
+    }
+}
+
+/// Abbreviations of the clubs whose names look like `name`
+fn suggestions(clubs: &[Club], name: &str) -> Vec<String> {
+    let name = name.to_lowercase();
+    clubs
+        .iter()
+        .filter(|c| {
+            let full_name = c.full_name.to_lowercase();
+            distance(&c.name.to_lowercase(), &name) <= 1
+                || (name.len() >= 3 && full_name.contains(&name))
+                || full_name
+                    .split_whitespace()
+                    .any(|word| distance(word, &name) <= 2)
+        })
+        .map(|c| c.name.clone())
+        .collect()
+}
+
+/// Levenshtein distance between two strings
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Fetches the games of the selected clubs. The clubs are resolved once,
+/// so that e.g. `watch` can refresh the games without doing it again.
+pub struct Slate {
+    /// None when every game is kept
+    team_ids: Option<Vec<String>>,
+}
+
+impl Slate {
+    pub fn new(cfg: &Config) -> Result<Slate, String> {
+        if cfg.filter.is_empty() {
+            return Ok(Slate { team_ids: None });
+        }
+        let season = cfg.naive_date()?.format("%Y").to_string();
+        let clubs = mlbstats::clubs(cfg, &season)?;
+        let selected = cfg.filter.select(&clubs)?;
+        if selected.is_empty() {
+            return Err("No club matches the filters".to_string());
+        }
+        Ok(Slate {
+            team_ids: Some(selected.iter().map(|c| c.id.clone()).collect()),
+        })
+    }
+
+    /// The games of the configured date that involve a selected club
+    pub fn games(&self, cfg: &Config) -> Result<Vec<Game>, String> {
+        let Some(ids) = &self.team_ids else {
+            return mlbstats::schedule(cfg);
+        };
+        // The endpoint already filters by club, but does not promise to
+        let games = mlbstats::team_schedule(cfg, &ids.join(","))?;
+        Ok(games
+            .into_iter()
+            .filter(|g| ids.contains(&g.away_id) || ids.contains(&g.home_id))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clubs() -> Vec<Club> {
+        let club = |id: &str, name: &str, full_name: &str, league: &str, division: &str| Club {
+            id: id.to_string(),
+            name: name.to_string(),
+            full_name: full_name.to_string(),
+            league: league.to_string(),
+            division: division.to_string(),
+        };
+        vec![
+            club(
+                "147",
+                "NYY",
+                "New York Yankees",
+                "AL",
+                "American League East",
+            ),
+            club("111", "BOS", "Boston Red Sox", "AL", "American League East"),
+            club(
+                "145",
+                "CWS",
+                "Chicago White Sox",
+                "AL",
+                "American League Central",
+            ),
+            club("121", "NYM", "New York Mets", "NL", "National League East"),
+            club(
+                "119",
+                "LAD",
+                "Los Angeles Dodgers",
+                "NL",
+                "National League West",
+            ),
+        ]
+    }
+
+    #[test]
+    fn filter_resolve() {
+        let clubs = clubs();
+        assert_eq!(resolve(&clubs, "nyy").unwrap().id, "147");
+        assert_eq!(resolve(&clubs, "Boston Red Sox").unwrap().id, "111");
+        assert_eq!(resolve(&clubs, "119").unwrap().name, "LAD");
+        assert_eq!(
+            resolve(&clubs, "NYN").unwrap_err(),
+            "Unknown team: NYN (did you mean NYY, NYM?)"
+        );
+        assert_eq!(
+            resolve(&clubs, "Dodgres").unwrap_err(),
+            "Unknown team: Dodgres (did you mean LAD?)"
+        );
+        assert_eq!(resolve(&clubs, "Expos").unwrap_err(), "Unknown team: Expos");
+    }
+
+    #[test]
+    fn filter_select() {
+        let clubs = clubs();
+        let names = |filter: Filter| -> Vec<String> {
+            let selected = filter.select(&clubs).unwrap();
+            selected.iter().map(|c| c.name.clone()).collect()
+        };
+        let filter = Filter {
+            teams: vec!["NYY".to_string(), "lad".to_string()],
+            ..Filter::default()
+        };
+        assert_eq!(names(filter), ["NYY", "LAD"]);
+        let filter = Filter {
+            division: Some(Division::East),
+            ..Filter::default()
+        };
+        assert_eq!(names(filter), ["NYY", "BOS", "NYM"]);
+        let filter = Filter {
+            league: Some(League::American),
+            division: Some(Division::East),
+            ..Filter::default()
+        };
+        assert_eq!(names(filter), ["NYY", "BOS"]);
+        let filter = Filter {
+            teams: vec!["NYM".to_string()],
+            league: Some(League::American),
+            ..Filter::default()
+        };
+        assert!(names(filter).is_empty());
+        assert!(Filter::default().is_empty());
+    }
+
+    #[test]
+    fn filter_distance() {
+        assert_eq!(distance("kitten", "sitting"), 3);
+        assert_eq!(distance("", "abc"), 3);
+        assert_eq!(distance("nyy", "nyy"), 0);
+    }
+}
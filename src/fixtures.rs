@@ -0,0 +1,65 @@
+//! Games, players and teams shared by the unit tests
+
+use crate::mlbstats::{BatterStats, Game, PitcherStats, Team};
+
+/// A scheduled game, with no score or start time yet
+pub(crate) fn game(away_name: &str, home_name: &str) -> Game {
+    Game {
+        away_name: away_name.to_string(),
+        home_name: home_name.to_string(),
+        game_id: "745123".to_string(),
+        status: "Scheduled".to_string(),
+        abstract_status: "Preview".to_string(),
+        away_score: None,
+        home_score: None,
+        venue_id: None,
+        away_id: String::new(),
+        home_id: String::new(),
+        start: None,
+    }
+}
+
+/// A batter with about league-average rates, whose ID is his name
+pub(crate) fn batter(name: &str) -> BatterStats {
+    BatterStats {
+        id: name.to_string(),
+        name: name.to_string(),
+        hand: "RHB".to_string(),
+        plate_appearances: 600,
+        bases_on_balls: 60,
+        hits: 150,
+        doubles: 30,
+        triples: 3,
+        homeruns: 21,
+        strikeouts: 120,
+    }
+}
+
+/// A pitcher with about league-average rates, e.g. "P7" for ID 7
+pub(crate) fn pitcher(id: &str) -> PitcherStats {
+    PitcherStats {
+        id: id.to_string(),
+        name: format!("P{}", id),
+        hand: "RHP".to_string(),
+        batters_faced: 700,
+        bases_on_balls: 55,
+        hits: 160,
+        doubles: 32,
+        triples: 3,
+        homeruns: 22,
+        strikeouts: 150,
+    }
+}
+
+/// A team of `batter`s named after it, "CHC 1" to "CHC 9", and a `pitcher`
+/// named "CHC Starter"
+pub(crate) fn team(name: &str) -> Team {
+    Team {
+        name: name.to_string(),
+        starting_pitcher: PitcherStats {
+            name: format!("{} Starter", name),
+            ..pitcher("1")
+        },
+        batters: (1..=9).map(|k| batter(&format!("{} {}", name, k))).collect(),
+    }
+}
@@ -0,0 +1,560 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use chrono::NaiveDate;
+use serde_json as json;
+
+use crate::{
+    backtest::{self, GameResult},
+    config::Config,
+    mlbstats::{self, Game},
+    simba::SimResult,
+};
+
+/// Version of the model that made a prediction, e.g. "0.1.0+3f2a9c1e": the
+/// crate version, and a short hash of the settings that change predictions.
+/// Recalibrated probabilities are told apart from raw ones.
+pub fn model_version(cfg: &Config) -> String {
+    let settings = format!(
+        "{:?}{:?}{:?}{:?}{:?}{}",
+        cfg.park_factors.sorted(),
+        cfg.home_advantage,
+        cfg.fatigue,
+        cfg.matchup,
+        cfg.stats,
+        cfg.iterations,
+    );
+    let version = format!(
+        "{}+{:08x}",
+        env!("CARGO_PKG_VERSION"),
+        mlbstats::fnv1a(&settings) >> 32
+    );
+    match cfg.recalibration {
+        Some(_) => format!("{}.recalibrated", version),
+        None => version,
+    }
+}
+
+/// A prediction as it was made
+#[derive(Clone, Debug, PartialEq)]
+pub struct Prediction {
+    pub game_id: String,
+    /// Date of the schedule the game was on
+    pub date: String,
+    pub predicted_at: String,
+    pub model_version: String,
+    /// Hash of the lineups, stats and situation the prediction was made from
+    pub inputs_hash: String,
+    pub away_name: String,
+    pub home_name: String,
+    pub home_win_probability: f64,
+    /// Made while the game was in progress
+    pub live: bool,
+    pub iterations: usize,
+    pub seed: u64,
+}
+
+/// The final score of a predicted game
+#[derive(Clone, Debug, PartialEq)]
+pub struct Final {
+    pub game_id: String,
+    pub away_score: i32,
+    pub home_score: i32,
+    pub graded_at: String,
+}
+
+/// A predicted game that was called off on the date it was predicted for,
+/// e.g. postponed, so that its predictions of that date are never graded
+#[derive(Clone, Debug, PartialEq)]
+pub struct Void {
+    pub game_id: String,
+    pub date: String,
+    pub status: String,
+    pub graded_at: String,
+}
+
+/// One line of the store. Entries are only ever appended, so a game's
+/// result is a separate entry from its predictions.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Entry {
+    Prediction(Prediction),
+    Final(Final),
+    Void(Void),
+}
+
+impl Entry {
+    pub fn to_json(&self) -> json::Value {
+        match self {
+            Entry::Prediction(p) => json::json!({
+                "kind": "prediction",
+                "game_id": p.game_id,
+                "date": p.date,
+                "predicted_at": p.predicted_at,
+                "model_version": p.model_version,
+                "inputs_hash": p.inputs_hash,
+                "away_name": p.away_name,
+                "home_name": p.home_name,
+                "home_win_probability": p.home_win_probability,
+                "live": p.live,
+                "iterations": p.iterations,
+                "seed": p.seed,
+            }),
+            Entry::Final(f) => json::json!({
+                "kind": "final",
+                "game_id": f.game_id,
+                "away_score": f.away_score,
+                "home_score": f.home_score,
+                "graded_at": f.graded_at,
+            }),
+            Entry::Void(v) => json::json!({
+                "kind": "void",
+                "game_id": v.game_id,
+                "date": v.date,
+                "status": v.status,
+                "graded_at": v.graded_at,
+            }),
+        }
+    }
+
+    pub fn from_json(value: &json::Value) -> Result<Entry, String> {
+        let string = |k: &str| {
+            value[k]
+                .as_str()
+                .map(|s| s.to_string())
+                .ok_or(format!("missing \"{}\"", k))
+        };
+        let int = |k: &str| value[k].as_i64().ok_or(format!("missing \"{}\"", k));
+        match value["kind"].as_str() {
+            Some("prediction") => Ok(Entry::Prediction(Prediction {
+                game_id: string("game_id")?,
+                date: string("date")?,
+                predicted_at: string("predicted_at")?,
+                model_version: string("model_version")?,
+                inputs_hash: string("inputs_hash")?,
+                away_name: string("away_name")?,
+                home_name: string("home_name")?,
+                home_win_probability: value["home_win_probability"]
+                    .as_f64()
+                    .ok_or("missing \"home_win_probability\"")?,
+                live: value["live"].as_bool().unwrap_or(false),
+                iterations: int("iterations")? as usize,
+                seed: value["seed"].as_u64().ok_or("missing \"seed\"")?,
+            })),
+            Some("final") => Ok(Entry::Final(Final {
+                game_id: string("game_id")?,
+                away_score: int("away_score")? as i32,
+                home_score: int("home_score")? as i32,
+                graded_at: string("graded_at")?,
+            })),
+            Some("void") => Ok(Entry::Void(Void {
+                game_id: string("game_id")?,
+                date: string("date")?,
+                status: string("status")?,
+                graded_at: string("graded_at")?,
+            })),
+            _ => Err("unknown kind".to_string()),
+        }
+    }
+}
+
+/// Append-only NDJSON file of predictions and results
+pub struct Store {
+    path: PathBuf,
+}
+
+impl Store {
+    pub fn new(path: &Path) -> Store {
+        Store {
+            path: path.to_path_buf(),
+        }
+    }
+
+    pub fn append(&self, entries: &[Entry]) -> Result<(), String> {
+        let err = |e: std::io::Error| format!("{}: {}", self.path.display(), e);
+        if let Some(dir) = self.path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(err)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(err)?;
+        let lines: String = entries.iter().map(|e| format!("{}\n", e.to_json())).collect();
+        file.write_all(lines.as_bytes()).map_err(err)
+    }
+
+    /// Every entry, oldest first. A missing store is empty.
+    pub fn load(&self) -> Result<Vec<Entry>, String> {
+        let text = match fs::read_to_string(&self.path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(format!("{}: {}", self.path.display(), e)),
+        };
+        let mut entries = Vec::new();
+        for (n, line) in text.lines().enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+            // A line cut short by an interrupted write should not lose the rest
+            match json::from_str(line).map_err(|e| e.to_string()).and_then(|v| Entry::from_json(&v)) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => eprintln!("[WARNING] {}: line {}: {}", self.path.display(), n + 1, e),
+            }
+        }
+        Ok(entries)
+    }
+}
+
+/// The prediction to record of a simulated game, unless the game is over:
+/// made from stats that include the game, it would replace the pregame
+/// prediction. Predictions of games that have started are live.
+pub fn prediction(cfg: &Config, game: &Game, inputs: &str, result: &SimResult) -> Option<Prediction> {
+    if game.is_final() {
+        return None;
+    }
+    Some(Prediction {
+        game_id: game.game_id.clone(),
+        date: cfg.date.clone(),
+        predicted_at: now(),
+        model_version: model_version(cfg),
+        inputs_hash: format!("{:016x}", mlbstats::fnv1a(inputs)),
+        away_name: game.away_name.clone(),
+        home_name: game.home_name.clone(),
+        home_win_probability: result.home_win_probability?,
+        live: game.abstract_status != "Preview",
+        iterations: result.iterations,
+        seed: result.seed,
+    })
+}
+
+/// Record a prediction, warning rather than failing if it cannot be stored
+pub fn record(cfg: &Config, prediction: Prediction) {
+    if let Some(path) = &cfg.history {
+        if let Err(e) = Store::new(path).append(&[Entry::Prediction(prediction)]) {
+            eprintln!("[WARNING] Prediction not recorded: {}", e);
+        }
+    }
+}
+
+fn now() -> String {
+    chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+}
+
+/// Games and dates whose predictions were voided
+fn voided(entries: &[Entry]) -> BTreeSet<(&str, &str)> {
+    entries
+        .iter()
+        .filter_map(|e| match e {
+            Entry::Void(v) => Some((v.game_id.as_str(), v.date.as_str())),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Games whose result could change the store's scores, by schedule date
+fn ungraded(entries: &[Entry]) -> BTreeMap<String, BTreeSet<String>> {
+    let graded: BTreeSet<_> = entries
+        .iter()
+        .filter_map(|e| match e {
+            Entry::Final(f) => Some(&f.game_id),
+            _ => None,
+        })
+        .collect();
+    let voided = voided(entries);
+    let mut dates: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    for entry in entries {
+        if let Entry::Prediction(p) = entry {
+            if !graded.contains(&p.game_id) && !voided.contains(&(&p.game_id, &p.date)) {
+                dates.entry(p.date.clone()).or_default().insert(p.game_id.clone());
+            }
+        }
+    }
+    dates
+}
+
+/// Fetch the final scores of predicted games that have not been graded yet
+pub fn grade(cfg: &Config, path: &Path) -> Result<(), String> {
+    let store = Store::new(path);
+    let pending = ungraded(&store.load()?);
+
+    let (mut graded, mut void, mut waiting) = (0, 0, 0);
+    for (date, game_ids) in pending {
+        let cfg = Config {
+            date: date.clone(),
+            ..cfg.clone()
+        };
+        let games = match mlbstats::schedule(&cfg) {
+            Ok(games) => games,
+            Err(e) => {
+                eprintln!("[WARNING] {}: {}", date, e);
+                waiting += game_ids.len();
+                continue;
+            }
+        };
+        let (mut entries, mut called_off) = (Vec::new(), 0);
+        for g in games.into_iter().filter(|g| game_ids.contains(&g.game_id) && g.is_final()) {
+            match (g.away_score, g.home_score) {
+                // Games called off on this date may be played on another
+                _ if matches!(g.status.as_str(), "Postponed" | "Cancelled") => {
+                    entries.push(Entry::Void(Void {
+                        game_id: g.game_id,
+                        date: date.clone(),
+                        status: g.status,
+                        graded_at: now(),
+                    }));
+                    called_off += 1;
+                }
+                (Some(away_score), Some(home_score)) => entries.push(Entry::Final(Final {
+                    game_id: g.game_id,
+                    away_score,
+                    home_score,
+                    graded_at: now(),
+                })),
+                _ => (),
+            }
+        }
+        store.append(&entries)?;
+        graded += entries.len() - called_off;
+        void += called_off;
+        waiting += game_ids.len() - entries.len();
+    }
+    println!(
+        "Graded {} games, {} called off, {} still waiting for a result",
+        graded, void, waiting
+    );
+    Ok(())
+}
+
+/// Graded pregame predictions by model version. When a game was predicted
+/// more than once by the same version, the last prediction counts.
+fn scored(entries: &[Entry]) -> BTreeMap<String, Vec<GameResult>> {
+    let finals: HashMap<_, _> = entries
+        .iter()
+        .filter_map(|e| match e {
+            Entry::Final(f) => Some((f.game_id.as_str(), f)),
+            _ => None,
+        })
+        .collect();
+
+    let voided = voided(entries);
+    let mut latest: BTreeMap<(&str, &str), &Prediction> = BTreeMap::new();
+    for entry in entries {
+        if let Entry::Prediction(p) = entry {
+            if !p.live && !voided.contains(&(&p.game_id, &p.date)) {
+                latest.insert((&p.model_version, &p.game_id), p);
+            }
+        }
+    }
+
+    let mut versions: BTreeMap<String, Vec<GameResult>> = BTreeMap::new();
+    for ((version, game_id), p) in latest {
+        let Some(f) = finals.get(game_id) else {
+            continue;
+        };
+        if f.away_score == f.home_score {
+            continue;
+        }
+        let Ok(date) = NaiveDate::parse_from_str(&p.date, "%F")
+            .or_else(|_| NaiveDate::parse_from_str(&p.date, "%m/%d/%Y"))
+        else {
+            continue;
+        };
+        versions.entry(version.to_string()).or_default().push(GameResult {
+            date,
+            game_id: p.game_id.clone(),
+            away_name: p.away_name.clone(),
+            home_name: p.home_name.clone(),
+            home_win_probability: p.home_win_probability,
+            away_score: f.away_score,
+            home_score: f.home_score,
+        });
+    }
+    for results in versions.values_mut() {
+        results.sort_by(|a, b| a.date.cmp(&b.date).then(a.game_id.cmp(&b.game_id)));
+    }
+    versions
+}
+
+/// Print the record, Brier score and calibration of each model version
+pub fn history(path: &Path) -> Result<(), String> {
+    let entries = Store::new(path).load()?;
+    let predictions = entries
+        .iter()
+        .filter(|e| matches!(e, Entry::Prediction(_)))
+        .count();
+    println!("{} predictions in {}", predictions, path.display());
+
+    for (version, results) in scored(&entries) {
+        let wins = results
+            .iter()
+            .filter(|r| (r.home_win_probability > 0.5) == r.home_won())
+            .count();
+        println!();
+        println!("Model {}", version);
+        println!("Record:   {}-{}", wins, results.len() - wins);
+        backtest::summarize(&results).print();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fixtures, simba::MatchupModel};
+
+    fn prediction(game_id: &str, version: &str, hwp: f64, live: bool) -> Entry {
+        Entry::Prediction(Prediction {
+            game_id: game_id.to_string(),
+            date: "2024-04-01".to_string(),
+            predicted_at: "2024-04-01T16:00:00Z".to_string(),
+            model_version: version.to_string(),
+            inputs_hash: "0123456789abcdef".to_string(),
+            away_name: "Away".to_string(),
+            home_name: "Home".to_string(),
+            home_win_probability: hwp,
+            live,
+            iterations: 1000,
+            seed: 7,
+        })
+    }
+
+    fn final_(game_id: &str, away: i32, home: i32) -> Entry {
+        Entry::Final(Final {
+            game_id: game_id.to_string(),
+            away_score: away,
+            home_score: home,
+            graded_at: "2024-04-02T08:00:00Z".to_string(),
+        })
+    }
+
+    fn void(game_id: &str) -> Entry {
+        Entry::Void(Void {
+            game_id: game_id.to_string(),
+            date: "2024-04-01".to_string(),
+            status: "Postponed".to_string(),
+            graded_at: "2024-04-02T08:00:00Z".to_string(),
+        })
+    }
+
+    #[test]
+    fn test_model_version() {
+        let cfg = Config::default();
+        let version = model_version(&cfg);
+        assert!(version.starts_with(concat!(env!("CARGO_PKG_VERSION"), "+")));
+        assert_eq!(version, model_version(&cfg.clone()));
+
+        let mut log5 = cfg.clone();
+        log5.matchup.model = MatchupModel::Log5;
+        assert_ne!(model_version(&log5), version);
+        let mut regressed = cfg.clone();
+        regressed.matchup.batter_regression = 200.0;
+        assert_ne!(model_version(&regressed), version);
+        let mut more = cfg.clone();
+        more.iterations *= 2;
+        assert_ne!(model_version(&more), version);
+    }
+
+    #[test]
+    fn test_json_roundtrip() {
+        for entry in [prediction("1", "0.1.0", 0.6, false), final_("1", 3, 4), void("2")] {
+            assert_eq!(Entry::from_json(&entry.to_json()).unwrap(), entry);
+        }
+        assert!(Entry::from_json(&json::json!({"kind": "final"})).is_err());
+    }
+
+    #[test]
+    fn test_store() {
+        let path = std::env::temp_dir()
+            .join(format!("mlb-oracle-history-{}", std::process::id()))
+            .join("history.ndjson");
+        let store = Store::new(&path);
+        assert_eq!(store.load().unwrap(), vec![]);
+        let entries = vec![prediction("1", "0.1.0", 0.6, false), final_("1", 3, 4)];
+        store.append(&entries[..1]).unwrap();
+        store.append(&entries[1..]).unwrap();
+        assert_eq!(store.load().unwrap(), entries);
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn test_ungraded() {
+        let entries = [
+            prediction("1", "0.1.0", 0.6, false),
+            prediction("2", "0.1.0", 0.6, false),
+            final_("1", 3, 4),
+        ];
+        let pending = ungraded(&entries);
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending["2024-04-01"], BTreeSet::from(["2".to_string()]));
+    }
+
+    #[test]
+    fn test_scored() {
+        let entries = [
+            prediction("1", "0.1.0", 0.4, false),
+            prediction("1", "0.1.0", 0.7, false),
+            prediction("1", "0.1.0", 0.9, true),
+            prediction("1", "0.2.0", 0.55, false),
+            prediction("2", "0.1.0", 0.3, false),
+            final_("1", 3, 4),
+        ];
+        let versions = scored(&entries);
+        assert_eq!(versions.len(), 2);
+        let v1 = &versions["0.1.0"];
+        assert_eq!(v1.len(), 1);
+        assert_eq!(v1[0].home_win_probability, 0.7);
+        assert!(v1[0].home_won());
+        assert_eq!(versions["0.2.0"][0].home_win_probability, 0.55);
+    }
+
+    #[test]
+    fn test_called_off() {
+        let mut entries = vec![prediction("1", "0.1.0", 0.6, false), void("1")];
+        assert!(ungraded(&entries).is_empty());
+        // Played on a later date under the same id
+        let mut rescheduled = prediction("1", "0.1.0", 0.7, false);
+        if let Entry::Prediction(p) = &mut rescheduled {
+            p.date = "2024-04-02".to_string();
+        }
+        entries.push(rescheduled);
+        assert_eq!(ungraded(&entries)["2024-04-02"], BTreeSet::from(["1".to_string()]));
+        entries.push(final_("1", 3, 4));
+        let v1 = &scored(&entries)["0.1.0"];
+        assert_eq!(v1.len(), 1);
+        assert_eq!(v1[0].home_win_probability, 0.7);
+    }
+
+    #[test]
+    fn test_rerun_after_final() {
+        let cfg = Config {
+            date: "2024-04-01".to_string(),
+            ..Config::default()
+        };
+        let result = |hwp| SimResult {
+            home_win_probability: Some(hwp),
+            expected_away_runs: 4.0,
+            expected_home_runs: 4.5,
+            iterations: 1000,
+            seed: 7,
+            scores: Vec::new(),
+        };
+        let game = |status: &str, abstract_status: &str| Game {
+            status: status.to_string(),
+            abstract_status: abstract_status.to_string(),
+            ..fixtures::game("Away", "Home")
+        };
+        let runs = [
+            (game("Scheduled", "Preview"), 0.6),
+            (game("In Progress", "Live"), 0.8),
+            (game("Final", "Final"), 0.95),
+        ];
+        let mut entries: Vec<Entry> = runs
+            .iter()
+            .filter_map(|(game, hwp)| super::prediction(&cfg, game, "", &result(*hwp)))
+            .map(Entry::Prediction)
+            .collect();
+        assert_eq!(entries.len(), 2);
+        entries.push(final_("745123", 2, 5));
+        let versions = scored(&entries);
+        assert_eq!(versions[&model_version(&cfg)][0].home_win_probability, 0.6);
+    }
+}
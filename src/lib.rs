@@ -1,233 +1,582 @@
+pub mod api;
+pub mod backtest;
+pub mod calibration;
 pub mod config;
+pub mod dashboard;
+pub mod dates;
+pub mod explain;
+pub mod filter;
+#[cfg(test)]
+pub(crate) mod fixtures;
+pub mod history;
+pub mod lineup;
+pub mod lookup;
 pub mod mlbstats;
+pub mod odds;
+pub mod output;
+pub mod park;
+pub mod report;
+pub mod retrosheet;
+pub mod scenario;
+pub mod season;
+pub mod serve;
+pub mod series;
+pub mod settings;
 pub mod utils;
 pub mod simba;
+pub mod watch;
 
 use std::io::{self, Write};
 
-use log;
+use chrono_tz::Tz;
+
+pub use api::{Oracle, Outcome, Prediction};
 
 use crate::{
-    config::Config,
-    mlbstats::Game,
-    simba::{GameState, SimbaConfig},
+    config::{Config, Mode},
+    mlbstats::{Game, Situation, Team},
+    output::{Color, Display, Format, Record, Style},
+    simba::{GameState, SimResult, SimbaConfig},
 };
 
 pub fn run(cfg: Config) -> Result<(), String> {
     utils::init_log(cfg.verbose);
     log::debug!(target: "mlb_oracle::run", "date={:?}", cfg.date);
-    for game in mlbstats::schedule(&cfg)? {
-        oracle(&cfg, &game)?
+    match cfg.mode {
+        Mode::Predict => {
+            let mut writer = output::Writer::new(cfg.format);
+            let games = filter::Slate::new(&cfg)?.games(&cfg)?;
+            if games.is_empty() {
+                no_games();
+            }
+            let oracle = Oracle::with_config(cfg.clone());
+            for game in games {
+                let mut record = Record::new(&game);
+                let result = crate::oracle(&oracle, &game, &mut record);
+                writer.write(&record);
+                if result.is_err() {
+                    writer.finish();
+                    return result;
+                }
+            }
+            writer.finish();
+            Ok(())
+        }
+        Mode::Watch { interval, swing } => watch::watch(&cfg, interval, swing),
+        Mode::Dashboard { interval } => dashboard::dashboard(&cfg, interval),
+        Mode::Explain { ref game_id } => explain::explain(&cfg, game_id),
+        Mode::Replay { ref paths } => retrosheet::report(paths),
+        Mode::Backtest { from, to, ref output } => {
+            backtest::backtest(&cfg, from, to, output.as_deref())
+        }
+        Mode::Calibrate {
+            ref input,
+            bins,
+            method,
+            ref output,
+        } => calibration::calibrate(input, bins, method, output.as_deref()),
+        Mode::OptimizeLineup {
+            ref game_id,
+            side,
+            objective,
+        } => lineup::optimize_lineup(&cfg, game_id, side, objective),
+        Mode::WhatIf {
+            ref game_id,
+            ref scenario,
+        } => scenario::what_if(&cfg, game_id, scenario),
+        Mode::Season { simulations } => season::season(&cfg, simulations),
+        Mode::Series {
+            ref teams,
+            ref format,
+            ref rotations,
+        } => series::series(&cfg, teams.as_ref(), format, rotations),
+        Mode::Odds { ref path, kelly } => odds::odds(&cfg, path, kelly),
+        Mode::Report { ref output } => report::report(&cfg, output.as_deref()),
+        Mode::Game { ref game_id } => lookup::game(&cfg, game_id),
+        Mode::Player { ref query } => lookup::player(&cfg, query),
+        Mode::Schedule => lookup::schedule(&cfg),
+        Mode::Teams => lookup::teams(&cfg),
+        Mode::Serve { ref bind, ttl } => serve::serve(&cfg, bind, ttl),
+        Mode::ShowConfig => {
+            print!("{}", settings::show(&cfg));
+            Ok(())
+        }
+        Mode::Grade | Mode::History => {
+            let path = cfg
+                .history
+                .clone()
+                .ok_or("No history file: set --history or $HOME".to_string())?;
+            match cfg.mode {
+                Mode::Grade => history::grade(&cfg, &path),
+                _ => history::history(&path),
+            }
+        }
     }
-    Ok(())
 }
 
-#[derive(Clone)]
-enum TTYColor {
-    Black = 30,
-    Red = 31,
-    Green = 32,
-    Yellow = 33,
-    Blue = 34,
-    Cyan = 36,
-    White = 37,
+pub(crate) fn no_games() {
+    eprintln!("[WARNING] No games found on this date.");
 }
 
-fn colormap(winprob: f64) -> TTYColor {
-    if winprob >= 0.75 {
-	TTYColor::Cyan
-    } else if winprob >= 0.55 {
-	TTYColor::Green
-    } else if winprob >= 0.45 {
-	TTYColor::Yellow
-    } else if winprob >= 0.25 {
-	TTYColor::Red
-    } else {
-	TTYColor::White
-    }
-} 
-
-struct GameLine<'a> {
+pub(crate) struct GameLine<'a> {
     game: &'a Game,
     status: Option<String>,
-    color: Option<TTYColor>,
+    color: Option<Color>,
+    detail: Option<String>,
+    hwp: Option<f64>,
+    runs: Option<[f64; 2]>,
+    /// Time of first pitch, padded to a fixed width
+    start: Option<String>,
+    /// The bars show a result rather than a prediction
+    settled: bool,
+    style: Style,
+    /// Draw nothing, e.g. when a machine format is written instead
+    silent: bool,
 }
 
 impl<'a> GameLine<'a> {
-    fn new(game: &'a Game) -> GameLine<'a> {
+    pub(crate) fn new(game: &'a Game, style: Style) -> GameLine<'a> {
         GameLine {
-            game:&game,
+            game,
             status: None,
             color: None,
+            detail: None,
+            hwp: None,
+            runs: None,
+            start: None,
+            settled: false,
+            style,
+            silent: false,
         }
     }
 
-    fn fetching(&mut self) {
+    pub(crate) fn fetching(&mut self) {
 	self.status = Some("FETCHING DATA...".to_string());
-	self.color = Some(TTYColor::Black);
+	self.color = Some(Color::Black);
     }
 
-    fn postponed(&mut self) {
+    pub(crate) fn postponed(&mut self) {
         self.status = Some("POSTPONED".to_string());
-        self.color = Some(TTYColor::Blue)
+        self.color = Some(Color::Blue)
     }
 
-    fn frontend_error(&mut self) {
+    pub(crate) fn frontend_error(&mut self) {
 	self.status = Some("FRONTEND ERROR".to_string());
-	self.color = Some(TTYColor::Red);
+	self.color = Some(Color::Red);
     }
 
-    fn missing_lineups(&mut self) {
+    pub(crate) fn missing_lineups(&mut self) {
 	self.status = Some("MISSING LINEUPS".to_string());
-	self.color = Some(TTYColor::Yellow);
+	self.color = Some(Color::Yellow);
     }
 
-    fn missing_lineup_away(&mut self) {
+    pub(crate) fn missing_lineup_away(&mut self) {
 	self.status = Some("MISSING LINEUP A".to_string());
-	self.color = Some(TTYColor::Yellow);
+	self.color = Some(Color::Yellow);
     }
 
-    fn missing_lineup_home(&mut self) {
+    pub(crate) fn missing_lineup_home(&mut self) {
 	self.status = Some("MISSING LINEUP H".to_string());
-	self.color = Some(TTYColor::Yellow);
+	self.color = Some(Color::Yellow);
     }
 
-    fn predicting(&mut self) {
+    pub(crate) fn predicting(&mut self) {
 	self.status = Some("PREDICTING...".to_string());
-	self.color = Some(TTYColor::Black);
+	self.color = Some(Color::Black);
     }
 
-    fn backend_error(&mut self) {
+    pub(crate) fn backend_error(&mut self) {
 	self.status = Some("BACKEND ERROR".to_string());
-	self.color = Some(TTYColor::Red);
+	self.color = Some(Color::Red);
     }
 
-    fn prediction(&mut self, hwp: Option<f64>) {
+    pub(crate) fn prediction(&mut self, hwp: Option<f64>) {
 	match hwp {
 	    None => {
 		self.status = Some("NO PREDICTION".to_string());
-		self.color = Some(TTYColor::Red);
+		self.color = Some(Color::Red);
 	    }
 	    Some(hwp) => {
-		let nboxes_per_team = 10;
-		let awp = 1.0 - hwp;
-		let nfull_h = (hwp * nboxes_per_team as f64).round() as i32;
-		let nfull_a = (awp * nboxes_per_team as f64).round() as i32;
-		let mut line = String::new();
-		for _ in 0..(nboxes_per_team - nfull_a) {
-                    line.push('□');
-		}
-		let mut subline = String::new();
-		for _ in 0..nfull_a {
-                    subline.push('■');
-		}
-		line.push_str(&colored_msg(colormap(awp), &subline));
-
-		// Separator
-		line.push(' ');
-
-		// Home
-		let mut subline = String::new();
-		for _ in 0..nfull_h {
-                    subline.push('■')
-		}
-			
-		line.push_str(&colored_msg(colormap(hwp), &subline));
-		for _ in 0..(nboxes_per_team - nfull_h) {
-                    line.push('□');
-		}
-
+		let line = match self.style.display {
+		    Display::Bar => self.bars(hwp, 10),
+		    Display::Numbers => format!(
+			"{:>5.1}% {} {:>5.1}%",
+			100.0 * (1.0 - hwp),
+			self.bars(hwp, 10),
+			100.0 * hwp
+		    ),
+		    Display::Compact => format!(
+			"{:>3.0}% {} {:>3.0}%",
+			100.0 * (1.0 - hwp),
+			self.bars(hwp, 5),
+			100.0 * hwp
+		    ),
+		};
+		self.hwp = Some(hwp);
 		self.color = None;
 		self.status = Some(line);
 	    }
 	}
     }
 
-    fn update(&self) {
-        print!("\x1B[2k\r");
+    /// Expected final runs of each team, away first
+    pub(crate) fn expected_runs(&mut self, runs: [f64; 2]) {
+	self.runs = Some(runs);
+    }
 
-        let status = self.status.clone().unwrap_or("UNKNOWN".to_string());
-        let status = if let Some(col) = &self.color {
-            colored_msg(col.clone(), &bold(&format!("{:^21}", status)))
+    /// Lead the line with the time of first pitch. Compact lines leave it
+    /// out, to fit in 80 columns.
+    pub(crate) fn first_pitch(&mut self, tz: Option<Tz>) {
+	let width = if tz.is_some() { 9 } else { 5 };
+	self.start = Some(format!("{:<width$}", lookup::start(self.game, tz)));
+    }
+
+    /// The game is over, so there are no odds or expected runs to show
+    pub(crate) fn settled(&mut self) {
+	self.settled = true;
+    }
+
+    /// Win probability bars of both teams, with `nboxes_per_team` boxes each
+    fn bars(&self, hwp: f64, nboxes_per_team: i32) -> String {
+	let awp = 1.0 - hwp;
+	let nfull_h = (hwp * nboxes_per_team as f64).round() as i32;
+	let nfull_a = (awp * nboxes_per_team as f64).round() as i32;
+	let mut line = String::new();
+	for _ in 0..(nboxes_per_team - nfull_a) {
+	    line.push('□');
+	}
+	let mut subline = String::new();
+	for _ in 0..nfull_a {
+	    subline.push('■');
+	}
+	line.push_str(&self.paint(self.style.colormap.color(awp), &subline));
+
+	// Separator
+	line.push(' ');
+
+	// Home
+	let mut subline = String::new();
+	for _ in 0..nfull_h {
+	    subline.push('■')
+	}
+	line.push_str(&self.paint(self.style.colormap.color(hwp), &subline));
+	for _ in 0..(nboxes_per_team - nfull_h) {
+	    line.push('□');
+	}
+	line
+    }
+
+    fn paint(&self, color: Color, msg: &String) -> String {
+        if self.style.color {
+            colored_msg(color, msg)
         } else {
-            status
+            msg.clone()
+        }
+    }
+
+    pub(crate) fn render(&self) -> String {
+        let display = self.style.display;
+        // Statuses take the place of the bars, and of their percentages
+        let width = if display == Display::Numbers { 35 } else { 21 };
+        let status = self.status.clone().unwrap_or("UNKNOWN".to_string());
+        let status = match &self.color {
+            Some(col) if self.style.color => {
+                colored_msg(*col, &bold(&format!("{:^width$}", status)))
+            }
+            Some(_) => format!("{:^width$}", status),
+            None => status,
+        };
+
+        let (away, home) = (&self.game.away_name, &self.game.home_name);
+        let mut line = match (display, &self.start) {
+            (Display::Bar | Display::Numbers, Some(start)) => format!("{} ", start),
+            _ => String::new(),
         };
+        line += &match display {
+            Display::Bar => format!("{:>25} {} {}", away, status, home),
+            Display::Numbers => format!("{:>25} {} {:<25}", away, status, home),
+            Display::Compact => format!(
+                "{:>12.12} {} {:<12.12}",
+                nickname(away),
+                status,
+                nickname(home)
+            ),
+        };
+        match (display, self.hwp, self.runs) {
+            _ if self.settled => (),
+            (Display::Numbers, Some(hwp), runs) => {
+                line.push_str(&format!(
+                    "  Fair {} {}",
+                    fair_odds(1.0 - hwp),
+                    fair_odds(hwp)
+                ));
+                if let Some([a, h]) = runs {
+                    line.push_str(&format!("  Runs {:.1}-{:.1}  Total {:.1}", a, h, a + h));
+                }
+            }
+            (Display::Compact, Some(_), Some([a, h])) => {
+                line.push_str(&format!(" {:>4.1}", a + h));
+            }
+            _ => (),
+        }
+        if let Some(detail) = &self.detail {
+            line.push_str(&format!("  {}", detail));
+        }
+        if display != Display::Bar {
+            line.truncate(line.trim_end().len());
+        }
+        line
+    }
 
-	let line = format!("{:>25} {} {}", self.game.away_name, status, self.game.home_name);
-	print!("{}", line);
+    /// Redraw the line in place. Progress is only animated on a terminal.
+    pub(crate) fn update(&self) {
+        if self.silent || !self.style.interactive {
+            return;
+        }
+        print!("\x1B[2K\r{}", self.render());
 	let _ = io::stdout().flush();
     }
 
-    fn finalize(&self) {
-	self.update();
-	println!();
+    /// Extra information shown after the team names, such as the score
+    pub(crate) fn detail(&mut self, detail: String) {
+	self.detail = Some(detail);
+    }
+
+    pub(crate) fn finalize(&self) {
+	if self.silent {
+	    return;
+	}
+	if self.style.interactive {
+	    print!("\x1B[2K\r");
+	}
+	println!("{}", self.render());
     }
 	    
     
 }
 
+/// Club nickname, e.g. "Blue Jays" for "Toronto Blue Jays"
+fn nickname(name: &str) -> String {
+    let words: Vec<&str> = name.split_whitespace().collect();
+    let n = match words.as_slice() {
+        [.., "Red" | "White" | "Blue", _] => 2,
+        _ => 1,
+    };
+    words[words.len().saturating_sub(n)..].join(" ")
+}
+
+/// American and decimal odds paying out fairly on probability `p`
+fn fair_odds(p: f64) -> String {
+    let decimal = if p > 0.0 {
+        format!("{:.2}", 1.0 / p)
+    } else {
+        "-".to_string()
+    };
+    format!("{}/{}", odds::american(p), decimal)
+}
+
 fn bold(msg: &String) -> String {
     format!("\x1B[1m{msg}\x1B[0m")
 }
 
-fn colored_msg(color: TTYColor, msg: &String) -> String {
+fn colored_msg(color: Color, msg: &String) -> String {
     format!("\x1B[{}m{}\x1B[0m", color as isize, msg)
 }
 
-fn oracle(cfg: &Config, game: &Game) -> Result<(), String> {
-    let mut gline = GameLine::new(game);
+pub(crate) fn oracle(oracle: &Oracle, game: &Game, record: &mut Record) -> Result<(), String> {
+    let cfg = oracle.config();
+    let mut gline = GameLine::new(game, Style::detect(cfg.color, cfg.display, cfg.colormap));
+    gline.silent = cfg.format != Format::Text;
+    gline.first_pitch(cfg.tz);
 
-    if game.status == "Postponed" {
-        gline.postponed();
+    if game.status != "Postponed" {
+        gline.fetching();
+        gline.update();
+    }
+    let prediction = match oracle.predict(game) {
+        Ok(prediction) => prediction,
+        Err(e) => {
+            gline.frontend_error();
+            record.outcome = "frontend_error".to_string();
+            gline.finalize();
+            return Err(e);
+        }
+    };
+
+    let (Some([away, home]), Some(result)) = (&prediction.teams, &prediction.result) else {
+        record.outcome = match prediction.outcome {
+            Outcome::Postponed => {
+                gline.postponed();
+                "postponed"
+            }
+            Outcome::MissingLineups { away: true, home: false } => {
+                gline.missing_lineup_away();
+                "missing_lineup_away"
+            }
+            Outcome::MissingLineups { away: false, home: true } => {
+                gline.missing_lineup_home();
+                "missing_lineup_home"
+            }
+            _ => {
+                gline.missing_lineups();
+                "missing_lineups"
+            }
+        }
+        .to_string();
         gline.finalize();
         return Ok(());
-    }
+    };
+    record.teams(away, home);
+    record.simulated(result);
 
-    gline.fetching();
-    gline.update();
+    let inputs = format!("{:?}{:?}{:?}{:?}", away, home, prediction.situation, cfg.as_of);
+    if let Some(prediction) = history::prediction(cfg, game, &inputs, result) {
+        history::record(cfg, prediction);
+    }
 
-    let result = mlbstats::teams(cfg, &game.game_id);
-    if let Err(e) = result {
-	gline.frontend_error();
-	gline.finalize();
-	return Err(e);
+    if game.is_final() {
+        gline.settled();
     }
-    let (away, home) = result.unwrap();
+    gline.expected_runs([result.expected_away_runs, result.expected_home_runs]);
+    gline.prediction(result.home_win_probability);
+    gline.finalize();
 
-    if away.is_none() && home.is_none() {
-	gline.missing_lineups();
-	gline.finalize();
-	return Ok(());
+    Ok(())
+}
+
+/// Simulate a game, from its current situation if it is in progress
+pub(crate) fn simulate(
+    cfg: &Config,
+    game: &Game,
+    away: &Team,
+    home: &Team,
+    situation: Option<&Situation>,
+) -> Result<SimResult, String> {
+    let mut gs = match situation {
+        Some(s) => GameState::from_situation(away, home, s),
+        None => GameState::new(away, home),
+    };
+    gs.venue = cfg.venue(game.venue_id.as_deref());
+    gs.fatigue = cfg.fatigue;
+    gs.matchup = cfg.matchup;
+    let mut result = SimbaConfig {
+        n_iter: cfg.iterations,
+        seed: cfg.seed,
     }
-    
-    if away.is_none() {
-	gline.missing_lineup_away();
-	gline.finalize();
-	return Ok(());
+    .run(&gs)?;
+    if let Some(map) = &cfg.recalibration {
+        result.home_win_probability = result.home_win_probability.map(|p| map.apply(p));
     }
+    Ok(result)
+}
 
-    if home.is_none() {
-	gline.missing_lineup_home();
-	gline.finalize();
-	return Ok(());
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gameline_plain() {
+        let game = fixtures::game("Toronto Blue Jays", "New York Yankees");
+        let style = Style {
+            color: false,
+            interactive: false,
+            colormap: output::Colormap::default(),
+            display: Display::Bar,
+        };
+        let mut gline = GameLine::new(&game, style);
+        gline.missing_lineups();
+        assert!(gline.render().contains("MISSING LINEUPS"));
+        gline.prediction(Some(0.7));
+        let line = gline.render();
+        assert!(!line.contains('\x1B'));
+        assert!(line.contains("□□□□□□■■■ ■■■■■■■□□□"));
     }
 
-    gline.predicting();
-    gline.update();
-
-    let away = away.unwrap();
-    let home = home.unwrap();
-    let gs = GameState::new(&away, &home);
-    let sim_result = SimbaConfig::default().run(&gs);
-    if let Err(e) = sim_result {
-	gline.backend_error();
-	gline.finalize();
-	return Err(e);
+    #[test]
+    fn gameline_colored() {
+        let game = fixtures::game("Toronto Blue Jays", "New York Yankees");
+        let style = Style {
+            color: true,
+            interactive: true,
+            colormap: output::Colormap::default(),
+            display: Display::Bar,
+        };
+        let mut gline = GameLine::new(&game, style);
+        gline.postponed();
+        assert!(gline.render().contains("\x1B[34m\x1B[1m"));
     }
 
-    gline.prediction(sim_result.unwrap().home_win_probability);
-    gline.finalize();
+    #[test]
+    fn gameline_numbers() {
+        let mut game = fixtures::game("Toronto Blue Jays", "New York Yankees");
+        let style = Style {
+            color: false,
+            interactive: false,
+            colormap: output::Colormap::default(),
+            display: Display::Numbers,
+        };
+        let mut gline = GameLine::new(&game, style);
+        gline.expected_runs([3.9, 4.6]);
+        gline.prediction(Some(0.6));
+        let line = gline.render();
+        assert!(line.contains(" 40.0% □□□□□□■■■■ ■■■■■■□□□□  60.0% New York Yankees"));
+        assert!(line.ends_with("Fair +150/2.50 -150/1.67  Runs 3.9-4.6  Total 8.5"));
+
+        // The numbers line up whatever the length of the home team's name
+        let column = |line: &str, s: &str| line[..line.find(s).unwrap()].chars().count();
+        game.home_name = "Arizona Diamondbacks".to_string();
+        let mut gline = GameLine::new(&game, style);
+        gline.prediction(Some(0.6));
+        assert_eq!(column(&gline.render(), "Fair"), column(&line, "Fair"));
+        gline.missing_lineups();
+        assert_eq!(column(&gline.render(), "Arizona"), column(&line, "New York"));
+
+        // Lines lead with the time of first pitch, whether it is known or not
+        let mut gline = GameLine::new(&game, style);
+        gline.first_pitch(None);
+        gline.prediction(Some(0.6));
+        assert!(gline.render().starts_with("TBD   "));
+        game.start = "2024-04-01T23:05:00Z".parse().ok();
+        let mut gline = GameLine::new(&game, style);
+        gline.first_pitch(Some(chrono_tz::America::New_York));
+        gline.prediction(Some(0.6));
+        let timed = gline.render();
+        assert!(timed.starts_with("19:05 EDT "));
+        gline.first_pitch(Some(chrono_tz::America::Phoenix));
+        gline.missing_lineups();
+        assert_eq!(column(&gline.render(), "Arizona"), column(&timed, "Arizona"));
+
+        // Final games show the result, without odds
+        let mut gline = GameLine::new(&game, style);
+        gline.expected_runs([3.0, 5.0]);
+        gline.prediction(Some(1.0));
+        gline.settled();
+        gline.detail("3-5 Final".to_string());
+        let line = gline.render();
+        assert!(!line.contains("Fair") && !line.contains("Runs"));
+        assert!(line.ends_with("Arizona Diamondbacks       3-5 Final"));
+    }
 
-    Ok(())
+    #[test]
+    fn gameline_compact() {
+        let mut game = fixtures::game("Toronto Blue Jays", "New York Yankees");
+        game.away_name = "Arizona Diamondbacks".to_string();
+        game.home_name = "Chicago White Sox".to_string();
+        let style = Style {
+            color: false,
+            interactive: false,
+            colormap: output::Colormap::default(),
+            display: Display::Compact,
+        };
+        let mut gline = GameLine::new(&game, style);
+        gline.expected_runs([10.2, 11.4]);
+        gline.prediction(Some(0.55));
+        gline.detail(format!("{:<28}", "10-11 Bot 9, 2 out ▶ 15%"));
+        let line = gline.render();
+        assert!(line.starts_with("Diamondbacks  45% "));
+        assert!(line.contains(" 55% White Sox    21.6  10-11"));
+        assert!(line.chars().count() <= 80, "{} columns", line.chars().count());
+        assert_eq!(nickname("Toronto Blue Jays"), "Blue Jays");
+        assert_eq!(nickname("Athletics"), "Athletics");
+    }
 }
-
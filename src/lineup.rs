@@ -0,0 +1,256 @@
+use std::collections::HashMap;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    config::Config,
+    mlbstats::{self, Team},
+    park::Venue,
+    simba::{GameState, SimbaConfig},
+};
+
+/// Stop the search after this many passes over the swaps, even if it is
+/// still improving
+const MAX_PASSES: usize = 20;
+
+/// How many times the iterations of the search the posted and the best
+/// order are scored with at the end
+const CHECK_FACTOR: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Side {
+    Away,
+    Home,
+}
+
+impl Side {
+    pub(crate) fn idx(self) -> usize {
+        match self {
+            Side::Away => 0,
+            Side::Home => 1,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Objective {
+    /// Expected runs scored by the team
+    Runs,
+    /// The team's win probability
+    Win,
+}
+
+pub struct Optimized {
+    pub team: String,
+    pub objective: Objective,
+    /// Batter names in the posted and the best order found
+    pub posted: Vec<String>,
+    pub best: Vec<String>,
+    /// Values of both orders, as scored apart from the search
+    pub posted_value: f64,
+    pub best_value: f64,
+    /// Number of distinct orders simulated
+    pub evaluations: usize,
+}
+
+impl Optimized {
+    pub fn gain(&self) -> f64 {
+        self.best_value - self.posted_value
+    }
+
+    pub fn print(&self) {
+        let value = |v: f64| match self.objective {
+            Objective::Runs => format!("{:.3} runs", v),
+            Objective::Win => format!("{:.1}% win probability", 100.0 * v),
+        };
+        println!("{} lineup ({} orders simulated)", self.team, self.evaluations);
+        println!();
+        println!("{:>2}  {:<20}{:<20}", "#", "Posted", "Optimized");
+        for (slot, (p, b)) in self.posted.iter().zip(&self.best).enumerate() {
+            let mark = if p != b { "*" } else { "" };
+            println!("{:>2}  {:<20}{:<20}{}", slot + 1, p, b, mark);
+        }
+        println!();
+        println!("Posted:    {}", value(self.posted_value));
+        println!("Optimized: {}", value(self.best_value));
+        match self.objective {
+            Objective::Runs => println!("Gain:      {:+.3} runs", self.gain()),
+            Objective::Win => println!("Gain:      {:+.1}%", 100.0 * self.gain()),
+        }
+    }
+}
+
+/// Search batting orders of one team by swapping pairs of batters for as
+/// long as that improves the objective. Every order is simulated with the
+/// same seed, so that orders are compared on the same random numbers
+/// rather than on sampling noise. The order the search ends with did best
+/// on that seed, which flatters it, so the posted and the best order are
+/// then scored again on another seed and more iterations.
+pub fn optimize(
+    cfg: &Config,
+    away: &Team,
+    home: &Team,
+    venue: &Venue,
+    side: Side,
+    objective: Objective,
+) -> Result<Optimized, String> {
+    let idx = side.idx();
+    let team = [away, home][idx];
+    let seed = cfg.seed.unwrap_or_else(rand::random);
+    let sim = SimbaConfig {
+        n_iter: cfg.iterations,
+        seed: Some(seed),
+    };
+    let check = SimbaConfig {
+        n_iter: cfg.iterations * CHECK_FACTOR,
+        seed: Some(StdRng::seed_from_u64(seed).gen()),
+    };
+
+    let value = |sim: &SimbaConfig, order: &[usize]| -> Result<f64, String> {
+        let mut reordered = team.clone();
+        reordered.batters = order.iter().map(|&i| team.batters[i].clone()).collect();
+        let mut gs = if idx == 0 {
+            GameState::new(&reordered, home)
+        } else {
+            GameState::new(away, &reordered)
+        };
+        gs.venue = *venue;
+        gs.fatigue = cfg.fatigue;
+        gs.matchup = cfg.matchup;
+        let res = sim.run(&gs)?;
+        Ok(match objective {
+            Objective::Runs => [res.expected_away_runs, res.expected_home_runs][idx],
+            Objective::Win => {
+                let hwp = res.home_win_probability.ok_or("No prediction")?;
+                if idx == 0 {
+                    1.0 - hwp
+                } else {
+                    hwp
+                }
+            }
+        })
+    };
+    let mut cache: HashMap<Vec<usize>, f64> = HashMap::new();
+    let mut evaluate = |order: &[usize]| -> Result<f64, String> {
+        if let Some(v) = cache.get(order) {
+            return Ok(*v);
+        }
+        let v = value(&sim, order)?;
+        cache.insert(order.to_vec(), v);
+        Ok(v)
+    };
+
+    let posted: Vec<usize> = (0..team.batters.len()).collect();
+    let posted_value = evaluate(&posted)?;
+    let (mut best, mut best_value) = (posted.clone(), posted_value);
+    for _ in 0..MAX_PASSES {
+        let mut improved = false;
+        let current = best.clone();
+        for i in 0..current.len() {
+            for j in i + 1..current.len() {
+                let mut order = current.clone();
+                order.swap(i, j);
+                let v = evaluate(&order)?;
+                if v > best_value {
+                    best = order;
+                    best_value = v;
+                    improved = true;
+                }
+            }
+        }
+        if !improved {
+            break;
+        }
+    }
+
+    let evaluations = cache.len();
+    let posted_value = value(&check, &posted)?;
+    let best_value = if best == posted {
+        posted_value
+    } else {
+        value(&check, &best)?
+    };
+
+    let names = |order: &[usize]| order.iter().map(|&i| team.batters[i].name.clone()).collect();
+    Ok(Optimized {
+        team: team.name.clone(),
+        objective,
+        posted: names(&posted),
+        best: names(&best),
+        posted_value,
+        best_value,
+        evaluations,
+    })
+}
+
+pub fn optimize_lineup(
+    cfg: &Config,
+    game_id: &str,
+    side: Side,
+    objective: Objective,
+) -> Result<(), String> {
+    let feed = mlbstats::feed(cfg, game_id)?;
+    match feed.teams(cfg)? {
+        (Some(away), Some(home)) => {
+            let venue = cfg.venue(feed.venue().as_deref());
+            optimize(cfg, &away, &home, &venue, side, objective)?.print();
+            Ok(())
+        }
+        _ => Err(format!("Lineups are not available for game {}", game_id)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{batter, team};
+    use crate::mlbstats::BatterStats;
+
+    #[test]
+    fn test_optimize() {
+        let away = team("AWY");
+        let mut home = team("HOM");
+        // The best hitter bats last in the posted lineup
+        home.batters[8] = BatterStats {
+            hits: 220,
+            homeruns: 50,
+            ..batter("Star")
+        };
+        let cfg = Config {
+            iterations: 100,
+            seed: Some(3),
+            ..Config::default()
+        };
+        let venue = Venue::neutral();
+        let res = optimize(&cfg, &away, &home, &venue, Side::Home, Objective::Runs).unwrap();
+        assert_eq!(res.team, "HOM");
+        assert_eq!(res.posted[8], "Star");
+        assert!(res.best.iter().position(|n| n == "Star").unwrap() < 8);
+
+        // Both orders are scored apart from the search, whose best value is
+        // biased upwards
+        let runs = |sim: SimbaConfig| {
+            let mut gs = GameState::new(&away, &home);
+            gs.fatigue = cfg.fatigue;
+            gs.matchup = cfg.matchup;
+            sim.run(&gs).unwrap().expected_home_runs
+        };
+        let search = SimbaConfig {
+            n_iter: 100,
+            seed: Some(3),
+        };
+        let check = SimbaConfig {
+            n_iter: 100 * CHECK_FACTOR,
+            seed: Some(StdRng::seed_from_u64(3).gen()),
+        };
+        assert_eq!(res.posted_value, runs(check));
+        assert_ne!(res.posted_value, runs(search));
+
+        let mut sorted = res.best.clone();
+        sorted.sort();
+        let mut posted = res.posted.clone();
+        posted.sort();
+        assert_eq!(sorted, posted);
+        assert!(res.evaluations > 36);
+    }
+}
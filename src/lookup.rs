@@ -0,0 +1,314 @@
+//! Commands that look up a single game, player, day or the clubs
+
+use chrono_tz::Tz;
+
+use crate::{
+    api::Oracle,
+    config::Config,
+    dates,
+    filter::Slate,
+    mlbstats::{self, BatterStats, Game, PitcherStats, Team},
+    odds,
+    output::{Format, Record, Writer},
+    simba::{Rates, SimResult},
+};
+
+/// How many of the most likely final scores to list
+const N_SCORES: usize = 5;
+
+/// The detailed prediction of one game
+pub fn game(cfg: &Config, game_id: &str) -> Result<(), String> {
+    let feed = mlbstats::feed(cfg, game_id)?;
+    let game = feed.game()?;
+    let prediction = Oracle::with_config(cfg.clone()).predict_from(&game, &feed)?;
+    let (Some([away, home]), Some(result)) = (prediction.teams, prediction.result) else {
+        return Err(format!(
+            "Lineups are not available for {} @ {} ({})",
+            game.away_name, game.home_name, game.status
+        ));
+    };
+
+    if cfg.format == Format::Text {
+        for line in card(&game, &away, &home, &result, cfg.tz) {
+            println!("{}", line.trim_end());
+        }
+    } else {
+        let mut record = Record::new(&game);
+        record.teams(&away, &home);
+        record.simulated(&result);
+        let mut writer = Writer::new(cfg.format);
+        writer.write(&record);
+        writer.finish();
+    }
+    Ok(())
+}
+
+fn card(game: &Game, away: &Team, home: &Team, result: &SimResult, tz: Option<Tz>) -> Vec<String> {
+    let mut lines = vec![format!("{} @ {}", game.away_name, game.home_name)];
+    let mut status = format!(
+        "Game {}, first pitch {}, {}",
+        game.game_id,
+        start(game, tz),
+        game.status
+    );
+    if let (Some(a), Some(h)) = (game.away_score, game.home_score) {
+        status.push_str(&format!(" {}-{}", a, h));
+    }
+    lines.push(status);
+    lines.push(String::new());
+
+    let row = |label: &str, a: String, h: String| format!("{:<16}{:>18}{:>18}", label, a, h);
+    lines.push(row("", away.name.clone(), home.name.clone()));
+    if let Some(hwp) = result.home_win_probability {
+        let awp = 1.0 - hwp;
+        lines.push(row(
+            "Win",
+            format!("{:.1}%", 100.0 * awp),
+            format!("{:.1}%", 100.0 * hwp),
+        ));
+        lines.push(row("Fair odds", odds::american(awp), odds::american(hwp)));
+    }
+    lines.push(row(
+        "Expected runs",
+        format!("{:.2}", result.expected_away_runs),
+        format!("{:.2}", result.expected_home_runs),
+    ));
+    lines.push(row(
+        "Starter",
+        away.starting_pitcher.name.clone(),
+        home.starting_pitcher.name.clone(),
+    ));
+    for slot in 0..away.batters.len().max(home.batters.len()) {
+        let name = |team: &Team| {
+            team.batters
+                .get(slot)
+                .map_or(String::new(), |b| b.name.clone())
+        };
+        let label = if slot == 0 { "Lineup" } else { "" };
+        lines.push(row(label, name(away), name(home)));
+    }
+
+    let total = result.expected_away_runs + result.expected_home_runs;
+    let line = total.floor() + 0.5;
+    let over = result.probability(|s| (s.away + s.home) as f64 > line);
+    lines.push(String::new());
+    lines.push(format!(
+        "Total {:.1}, over {:.1} {:.1}%",
+        total,
+        line,
+        100.0 * over
+    ));
+
+    let mut scores: Vec<_> = result.scores.iter().collect();
+    scores.sort_by_key(|(_, n)| std::cmp::Reverse(*n));
+    let n: usize = result.scores.iter().map(|(_, n)| n).sum();
+    let likely: Vec<_> = scores
+        .iter()
+        .take(N_SCORES)
+        .map(|(s, k)| {
+            format!(
+                "{}-{} {:.1}%",
+                s.away,
+                s.home,
+                100.0 * *k as f64 / n.max(1) as f64
+            )
+        })
+        .collect();
+    if !likely.is_empty() {
+        lines.push(format!("Likely scores: {}", likely.join(", ")));
+    }
+    lines
+}
+
+/// The stats the model uses for a player, by ID or by name
+pub fn player(cfg: &Config, query: &str) -> Result<(), String> {
+    let id = if query.chars().all(|c| c.is_ascii_digit()) {
+        query.to_string()
+    } else {
+        let season = cfg.naive_date()?.format("%Y").to_string();
+        let found = mlbstats::find_players(cfg, &season, query)?;
+        match found.as_slice() {
+            [] => return Err(format!("No player named {} in {}", query, season)),
+            [person] => person.id.clone(),
+            _ => {
+                for person in &found {
+                    println!("{:>7}  {:<4}{}", person.id, person.position, person.name);
+                }
+                return Err(format!(
+                    "{} players match {}: pick one by ID",
+                    found.len(),
+                    query
+                ));
+            }
+        }
+    };
+
+    let batter = mlbstats::batter(cfg, &id);
+    let pitcher = mlbstats::pitcher(cfg, &id);
+    if let (Err(e), Err(_)) = (&batter, &pitcher) {
+        return Err(e.clone());
+    }
+    let source = match cfg.as_of {
+        Some(date) => format!("{:?} as of {}", cfg.stats, date),
+        None => format!("{:?}", cfg.stats),
+    };
+    println!("Player {} ({} stats)", id, source.to_lowercase());
+    println!(
+        "{:<12}{:>6}{:>6}{:>6}{:>6}{:>6}{:>6}{:>6}  |{:>6}{:>6}{:>6}{:>6}{:>6}{:>6}",
+        "", "PA", "BB", "H", "2B", "3B", "HR", "K", "BB", "K", "H", "2B/H", "3B/H", "HR/H"
+    );
+    if let Ok(b) = &batter {
+        println!("{}", batting_line(b));
+    }
+    if let Ok(p) = &pitcher {
+        println!("{}", pitching_line(p));
+    }
+    Ok(())
+}
+
+fn batting_line(b: &BatterStats) -> String {
+    format!(
+        "{:<12}{:>6}{:>6}{:>6}{:>6}{:>6}{:>6}{:>6}  |{}",
+        format!("Batting {}", b.hand),
+        b.plate_appearances,
+        b.bases_on_balls,
+        b.hits,
+        b.doubles,
+        b.triples,
+        b.homeruns,
+        b.strikeouts,
+        rates(&Rates::batter(b))
+    )
+}
+
+fn pitching_line(p: &PitcherStats) -> String {
+    format!(
+        "{:<12}{:>6}{:>6}{:>6}{:>6}{:>6}{:>6}{:>6}  |{}",
+        "Pitching",
+        p.batters_faced,
+        p.bases_on_balls,
+        p.hits,
+        p.doubles,
+        p.triples,
+        p.homeruns,
+        p.strikeouts,
+        rates(&Rates::pitcher(p))
+    )
+}
+
+fn rates(r: &Rates) -> String {
+    [r.walk, r.strikeout, r.hit, r.double, r.triple, r.homerun]
+        .iter()
+        .map(|x| format!("{:>6.1}", 100.0 * x))
+        .collect()
+}
+
+/// The games of a day, with their status and score
+pub fn schedule(cfg: &Config) -> Result<(), String> {
+    let games = Slate::new(cfg)?.games(cfg)?;
+    if games.is_empty() {
+        crate::no_games();
+    }
+    for game in games {
+        println!("{}", schedule_line(&game, cfg.tz).trim_end());
+    }
+    Ok(())
+}
+
+fn schedule_line(game: &Game, tz: Option<Tz>) -> String {
+    let score = match (game.away_score, game.home_score) {
+        (Some(a), Some(h)) => format!("{}-{}", a, h),
+        _ => String::new(),
+    };
+    format!(
+        "{:>7} {:>9}  {:>25} @ {:<25} {} {}",
+        game.game_id,
+        start(game, tz),
+        game.away_name,
+        game.home_name,
+        game.status,
+        score
+    )
+}
+
+/// Time of first pitch, or TBD
+pub(crate) fn start(game: &Game, tz: Option<Tz>) -> String {
+    game.start
+        .map_or("TBD".to_string(), |t| dates::format_time(t, tz))
+}
+
+/// Club IDs and abbreviations, by league and division
+pub fn teams(cfg: &Config) -> Result<(), String> {
+    let season = cfg.naive_date()?.format("%Y").to_string();
+    let mut clubs = mlbstats::clubs(cfg, &season)?;
+    clubs.sort_by(|a, b| (&a.league, &a.division, &a.name).cmp(&(&b.league, &b.division, &b.name)));
+    println!("{:>5}  {:<5}{:<26}Division", "ID", "Abbr", "Name");
+    for club in clubs {
+        println!(
+            "{:>5}  {:<5}{:<26}{}",
+            club.id, club.name, club.full_name, club.division
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, batter, team};
+    use crate::simba::Score;
+
+    #[test]
+    fn game_card() {
+        let game = Game {
+            start: "2024-07-01T23:05:00Z".parse().ok(),
+            ..fixtures::game("Chicago Cubs", "Texas Rangers")
+        };
+        let tz = Some(chrono_tz::America::Chicago);
+        assert_eq!(
+            schedule_line(&game, tz),
+            " 745123 18:05 CDT               Chicago Cubs @ Texas Rangers             Scheduled "
+        );
+        let result = SimResult {
+            home_win_probability: Some(0.6),
+            expected_away_runs: 4.1,
+            expected_home_runs: 4.6,
+            iterations: 4,
+            seed: 1,
+            scores: vec![
+                (Score { away: 3, home: 5 }, 2),
+                (Score { away: 6, home: 2 }, 1),
+                (Score { away: 4, home: 5 }, 1),
+            ],
+        };
+        let lines = card(&game, &team("CHC"), &team("TEX"), &result, tz);
+        assert_eq!(lines[0], "Chicago Cubs @ Texas Rangers");
+        assert_eq!(lines[1], "Game 745123, first pitch 18:05 CDT, Scheduled");
+        assert!(lines
+            .iter()
+            .any(|l| l.starts_with("Win") && l.ends_with("40.0%             60.0%")));
+        assert!(lines
+            .iter()
+            .any(|l| l.starts_with("Fair odds") && l.ends_with("+150              -150")));
+        assert!(lines
+            .iter()
+            .any(|l| l.starts_with("Lineup") && l.ends_with("TEX 1")));
+        assert!(lines.contains(&"Total 8.7, over 8.5 25.0%".to_string()));
+        assert_eq!(
+            lines.last().unwrap(),
+            "Likely scores: 3-5 50.0%, 6-2 25.0%, 4-5 25.0%"
+        );
+    }
+
+    #[test]
+    fn player_lines() {
+        let lefty = BatterStats {
+            hand: "LHB".to_string(),
+            ..batter("B")
+        };
+        let line = batting_line(&lefty);
+        assert!(line.starts_with("Batting LHB    600    60   150"));
+        assert!(line.ends_with("  10.0  20.0  25.0  20.0   2.0  14.0"));
+        assert!(pitching_line(&team("P").starting_pitcher).starts_with("Pitching       700"));
+    }
+}
@@ -1,21 +1,49 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
 use std::process::Command;
 
+use chrono::{DateTime, Utc};
 use log;
 use serde_json as json;
 
 use crate::config::Config;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Game {
     pub away_name: String,
     pub home_name: String,
     pub game_id: String,
     pub status: String,
+    pub abstract_status: String,
+    /// Runs scored so far, once the game has started
+    pub away_score: Option<i32>,
+    pub home_score: Option<i32>,
+    /// Venue ID, as in the game feed
+    pub venue_id: Option<String>,
+    /// Club IDs
+    pub away_id: String,
+    pub home_id: String,
+    /// Time of first pitch, unless it is still to be decided
+    pub start: Option<DateTime<Utc>>,
 }
 
-#[derive(Debug)]
+impl Game {
+    /// Whether the game is currently being played
+    pub fn is_live(&self) -> bool {
+        self.abstract_status == "Live"
+    }
+
+    /// Whether the game is over (or will not be played on this date)
+    pub fn is_final(&self) -> bool {
+        self.abstract_status == "Final"
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct BatterStats {
+    /// MLB player ID
+    pub id: String,
     pub name: String,
     pub hand: String,
     pub plate_appearances: i32,
@@ -27,8 +55,10 @@ pub struct BatterStats {
     pub strikeouts: i32,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct PitcherStats {
+    /// MLB player ID
+    pub id: String,
     pub name: String,
     pub hand: String,
     pub batters_faced: i32,
@@ -46,40 +76,168 @@ pub struct PitcherStats {
 //     pub player_stats: Option<PlayerStats>,
 // }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Team {
     pub name: String,
     pub starting_pitcher: PitcherStats,
     pub batters: Vec<BatterStats>,
 }
 
+/// Snapshot of an in-progress game, taken from the live feed linescore.
+/// Per-team arrays are indexed away first, then home.
+#[derive(Clone, Debug)]
+pub struct Situation {
+    pub inning: i32,
+    pub top: bool,
+    pub outs: i32,
+    pub bases: [bool; 3],
+    pub away_score: i32,
+    pub home_score: i32,
+    /// Batting order slot of the next batter due up
+    pub batting_order: [usize; 2],
+    /// Pitcher currently in the game, if the starter has been relieved
+    pub pitchers: [Option<PitcherStats>; 2],
+    /// Batters faced and pitches thrown by the pitcher currently in the game
+    pub batters_faced: [i32; 2],
+    pub pitch_counts: [i32; 2],
+}
+
+/// A club and the league and division it plays in
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Club {
+    pub id: String,
+    pub name: String,
+    /// "AL" or "NL"
+    pub league: String,
+    pub division: String,
+    pub full_name: String,
+}
+
+/// A player on a season's rosters
+#[derive(Clone, Debug, PartialEq)]
+pub struct Person {
+    pub id: String,
+    pub name: String,
+    /// Abbreviation of the primary position, e.g. "SS" or "P"
+    pub position: String,
+}
+
+/// A regular-season game, played or not
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fixture {
+    pub game_id: String,
+    pub date: String,
+    pub away_id: String,
+    pub home_id: String,
+    pub status: String,
+    pub abstract_status: String,
+    pub away_score: Option<i32>,
+    pub home_score: Option<i32>,
+}
+
+pub const DEFAULT_API_URL: &str = "https://statsapi.mlb.com/api";
+
+/// Which stats the model is fed with
+#[derive(Clone, Copy, Debug, PartialEq, clap::ValueEnum)]
+pub enum StatSource {
+    /// Career totals
+    Career,
+    /// The current season only
+    Season,
+}
+
 pub struct StatsApi<'a> {
     url: String,
-    params: HashMap<&'a str, &'a str>,
+    // Sorted, so that a request always maps to the same URL
+    params: BTreeMap<&'a str, &'a str>,
+    cache: Option<PathBuf>,
+    offline: bool,
+    base: String,
+    // Which responses are worth caching, if not all of them
+    keep: Option<fn(&json::Value) -> bool>,
 }
 
 impl<'a> StatsApi<'a> {
     pub fn schedule(date: &'a str) -> StatsApi<'a> {
 	StatsApi {
 	    url: "v1/schedule".to_string(),
-	    params: HashMap::from([
+	    params: BTreeMap::from([
 		("sportId", "1"),
 		("date", date)
-	    ])
+	    ]),
+	    cache: None,
+	    offline: false,
+	    base: DEFAULT_API_URL.to_string(),
+	    keep: None,
 	}
     }
  
+    pub fn standings(season: &'a str) -> StatsApi<'a> {
+	StatsApi {
+	    url: "v1/standings".to_string(),
+	    params: BTreeMap::from([
+		("leagueId", "103,104"),
+		("season", season),
+		("standingsTypes", "regularSeason"),
+		("hydrate", "team,division"),
+	    ]),
+	    cache: None,
+	    offline: false,
+	    base: DEFAULT_API_URL.to_string(),
+	    keep: None,
+	}
+    }
+
+    /// Every regular-season game of a season
+    pub fn season(season: &'a str) -> StatsApi<'a> {
+	StatsApi {
+	    url: "v1/schedule".to_string(),
+	    params: BTreeMap::from([
+		("sportId", "1"),
+		("season", season),
+		("gameType", "R"),
+	    ]),
+	    cache: None,
+	    offline: false,
+	    base: DEFAULT_API_URL.to_string(),
+	    keep: None,
+	}
+    }
+
     pub fn game(game_id: &str) -> StatsApi<'a> {
 	StatsApi {
 	    url: format!("v1.1/game/{game_id}/feed/live"),
-	    params: HashMap::new(),
+	    params: BTreeMap::new(),
+	    cache: None,
+	    offline: false,
+	    base: DEFAULT_API_URL.to_string(),
+	    keep: None,
+	}
+    }
+
+    /// Every player of a season
+    pub fn players(season: &'a str) -> StatsApi<'a> {
+	StatsApi {
+	    url: "v1/sports/1/players".to_string(),
+	    params: BTreeMap::from([
+		("season", season),
+		("fields", "people,id,fullName,primaryPosition,abbreviation"),
+	    ]),
+	    cache: None,
+	    offline: false,
+	    base: DEFAULT_API_URL.to_string(),
+	    keep: None,
 	}
     }
 
     pub fn player(player_id: &str) -> StatsApi<'a> {
 	StatsApi {
 	    url: format!("v1/people/{player_id}"),
-	    params: HashMap::new()
+	    params: BTreeMap::new(),
+	    cache: None,
+	    offline: false,
+	    base: DEFAULT_API_URL.to_string(),
+	    keep: None,
 	}
     }
 	    
@@ -89,8 +247,28 @@ impl<'a> StatsApi<'a> {
 	self
     }
 
+    /// Reuse responses from the configured cache directory, if any.
+    /// Cached responses never expire, so this is meant for past games.
+    pub fn cached(mut self, cfg: &Config) -> Self {
+	self.cache = cfg.cache_dir.clone();
+	self.offline = cfg.offline;
+	self.api(cfg)
+    }
+
+    /// Like `cached`, but only store the responses that `keep` accepts
+    pub fn cached_if(mut self, cfg: &Config, keep: fn(&json::Value) -> bool) -> Self {
+	self.keep = Some(keep);
+	self.cached(cfg)
+    }
+
+    /// Send requests to the configured API base URL
+    pub fn api(mut self, cfg: &Config) -> Self {
+	self.base = cfg.api_url.clone();
+	self
+    }
+
     pub fn build_url(&mut self) -> Result<String, String> {
-	let mut url = format!("https://statsapi.mlb.com/api/{}", self.url);
+	let mut url = format!("{}/{}", self.base.trim_end_matches('/'), self.url);
 
 	for (i, (k, v)) in self.params.iter().enumerate() {
 	    url = format!(
@@ -108,6 +286,21 @@ impl<'a> StatsApi<'a> {
     pub fn json(mut self) -> Result<json::Value, String> {
 	let url = self.build_url()?;
 	log::debug!(target: "StatsApi.json", "url={:?}", url);
+
+	let path = self
+	    .cache
+	    .as_ref()
+	    .map(|dir| dir.join(format!("{:016x}.json", fnv1a(&url))));
+	if let Some(path) = &path {
+	    if let Ok(data) = fs::read_to_string(path) {
+		log::debug!(target: "StatsApi.json", "cached={:?}", path);
+		return json::from_str(&data).map_err(|err| format!("{:?}", err));
+	    }
+	}
+	if self.offline {
+	    return Err(format!("Not in the cache while offline: {}", url));
+	}
+
 	let data = match Command::new("curl").arg(&url).output() {
             Ok(out) => {
 		if out.status.success() {
@@ -118,22 +311,63 @@ impl<'a> StatsApi<'a> {
             }
             Err(err) => Err(format!("Error in StatsApi.json: {}", err)),
 	};
-	json::from_str(&data?).map_err(|err| format!("{:?}", err))
+	let data = data?;
+	let value = json::from_str(&data).map_err(|err| format!("{:?}", err))?;
+
+	if let Some(path) = path.as_ref().filter(|_| self.keep.is_none_or(|keep| keep(&value))) {
+	    let res = path
+		.parent()
+		.map_or(Ok(()), fs::create_dir_all)
+		.and_then(|_| fs::write(path, &data));
+	    if let Err(e) = res {
+		log::warn!(target: "StatsApi.json", "Cannot cache {:?}: {}", path, e);
+	    }
+	}
+	Ok(value)
     }
 }
 
+/// 64-bit FNV-1a, a hash that is stable across runs and Rust versions
+pub(crate) fn fnv1a(s: &str) -> u64 {
+    s.bytes().fold(0xcbf29ce484222325, |h, b| {
+	(h ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
 
 
 
 // TODO: decouple from config
 pub fn schedule(cfg: &Config) -> Result<Vec<Game>, String> {
-    let data = StatsApi::schedule(&cfg.date).json()?;
+    parse_schedule(&StatsApi::schedule(&cfg.date).cached_if(cfg, all_final).json()?)
+}
+
+/// The games of some clubs, by comma-separated club IDs
+pub fn team_schedule(cfg: &Config, team_ids: &str) -> Result<Vec<Game>, String> {
+    let data = StatsApi::schedule(&cfg.date)
+	.param("teamId", team_ids)
+	.cached_if(cfg, all_final)
+	.json()?;
+    parse_schedule(&data)
+}
+
+/// Whether every game of a schedule is over, so that it no longer changes
+fn all_final(data: &json::Value) -> bool {
+    let mut games = data["dates"]
+	.as_array()
+	.into_iter()
+	.flatten()
+	.flat_map(|date| date["games"].as_array().into_iter().flatten())
+	.peekable();
+    games.peek().is_some() && games.all(|g| g["status"]["abstractGameState"] == "Final")
+}
+
+fn parse_schedule(data: &json::Value) -> Result<Vec<Game>, String> {
 
     let mut games: Vec<Game> = Vec::new();
 
     if let json::Value::Array(games_data) = &data["dates"] {
         if games_data.is_empty() {
-	    println!("[WARNING] No games found on this date.");
 	    return Ok(Vec::new());
         } else if games_data.len() > 1 {
             return Err(String::from("Ambiguous data for this date!"));
@@ -146,6 +380,13 @@ pub fn schedule(cfg: &Config) -> Result<Vec<Game>, String> {
                     home_name: value_to_string(&obj["teams"]["home"]["team"]["name"]),
                     game_id: value_to_string(&obj["gamePk"]),
                     status: value_to_string(&obj["status"]["detailedState"]),
+                    abstract_status: value_to_string(&obj["status"]["abstractGameState"]),
+                    away_score: score(&obj["teams"]["away"]["score"]),
+                    home_score: score(&obj["teams"]["home"]["score"]),
+                    venue_id: obj["venue"]["id"].as_i64().map(|id| id.to_string()),
+                    away_id: value_to_string(&obj["teams"]["away"]["team"]["id"]),
+                    home_id: value_to_string(&obj["teams"]["home"]["team"]["id"]),
+                    start: start_time(&obj["gameDate"], &obj["status"]),
                 });
             }
         }
@@ -154,91 +395,395 @@ pub fn schedule(cfg: &Config) -> Result<Vec<Game>, String> {
     Ok(games)
 }
 
-pub fn teams(cfg: &Config, game_id: &String) -> Result<(Option<Team>, Option<Team>), String> {
+/// One game by ID, with its current status and score
+pub fn game(cfg: &Config, game_id: &str) -> Result<Game, String> {
+    feed(cfg, game_id)?.game()
+}
+
+fn parse_game(data: &json::Value) -> Option<Game> {
+    let game_data = &data["gameData"];
+    let runs = &data["liveData"]["linescore"]["teams"];
+    Some(Game {
+	away_name: game_data["teams"]["away"]["name"].as_str()?.to_string(),
+	home_name: game_data["teams"]["home"]["name"].as_str()?.to_string(),
+	game_id: value_to_string(&data["gamePk"]),
+	status: value_to_string(&game_data["status"]["detailedState"]),
+	abstract_status: value_to_string(&game_data["status"]["abstractGameState"]),
+	away_score: score(&runs["away"]["runs"]),
+	home_score: score(&runs["home"]["runs"]),
+	venue_id: game_data["venue"]["id"].as_i64().map(|id| id.to_string()),
+	away_id: value_to_string(&game_data["teams"]["away"]["id"]),
+	home_id: value_to_string(&game_data["teams"]["home"]["id"]),
+	start: start_time(&game_data["datetime"]["dateTime"], &game_data["status"]),
+    })
+}
+
+/// Time of first pitch, e.g. from "2024-04-01T23:05:00Z"
+fn start_time(date: &json::Value, status: &json::Value) -> Option<DateTime<Utc>> {
+    if status["startTimeTBD"].as_bool() == Some(true) {
+	return None;
+    }
+    date.as_str()?.parse().ok()
+}
+
+/// Players of a season whose name contains `name`, ignoring case
+pub fn find_players(cfg: &Config, season: &str, name: &str) -> Result<Vec<Person>, String> {
+    let data = StatsApi::players(season).cached(cfg).json()?;
+    Ok(parse_players(&data, name))
+}
+
+fn parse_players(data: &json::Value, name: &str) -> Vec<Person> {
+    let name = name.to_lowercase();
+    data["people"]
+	.as_array()
+	.into_iter()
+	.flatten()
+	.filter_map(|p| {
+	    let full_name = p["fullName"].as_str()?;
+	    full_name.to_lowercase().contains(&name).then(|| Person {
+		id: value_to_string(&p["id"]),
+		name: full_name.to_string(),
+		position: value_to_string(&p["primaryPosition"]["abbreviation"]),
+	    })
+	})
+	.collect()
+}
+
+/// The clubs of both leagues, with their divisions. The standings are not
+/// cached, as they change with every game.
+pub fn clubs(cfg: &Config, season: &str) -> Result<Vec<Club>, String> {
+    parse_clubs(&StatsApi::standings(season).api(cfg).json()?)
+}
+
+fn parse_clubs(data: &json::Value) -> Result<Vec<Club>, String> {
+    let mut clubs = Vec::new();
+    for record in data["records"].as_array().ok_or("No standings")? {
+	let league = match record["league"]["id"].as_i64() {
+	    Some(103) => "AL",
+	    Some(104) => "NL",
+	    _ => return Err("Unknown league in standings".to_string()),
+	};
+	let division = record["division"]["name"]
+	    .as_str()
+	    .map(|s| s.to_string())
+	    .unwrap_or_else(|| value_to_string(&record["division"]["id"]));
+	for team in record["teamRecords"].as_array().ok_or("No team records")? {
+	    let team = &team["team"];
+	    clubs.push(Club {
+		id: value_to_string(&team["id"]),
+		name: team["abbreviation"]
+		    .as_str()
+		    .or(team["name"].as_str())
+		    .unwrap_or("?")
+		    .to_string(),
+		league: league.to_string(),
+		division: division.clone(),
+		full_name: value_to_string(&team["name"]),
+	    });
+	}
+    }
+    Ok(clubs)
+}
+
+/// Every regular-season game of a season, cached once the season is over
+pub fn fixtures(cfg: &Config, season: &str) -> Result<Vec<Fixture>, String> {
+    Ok(parse_fixtures(&StatsApi::season(season).cached_if(cfg, all_final).json()?))
+}
+
+fn parse_fixtures(data: &json::Value) -> Vec<Fixture> {
+    let mut fixtures = Vec::new();
+    for date in data["dates"].as_array().into_iter().flatten() {
+	for obj in date["games"].as_array().into_iter().flatten() {
+	    fixtures.push(Fixture {
+		game_id: value_to_string(&obj["gamePk"]),
+		date: obj["officialDate"]
+		    .as_str()
+		    .or(date["date"].as_str())
+		    .unwrap_or("")
+		    .to_string(),
+		away_id: value_to_string(&obj["teams"]["away"]["team"]["id"]),
+		home_id: value_to_string(&obj["teams"]["home"]["team"]["id"]),
+		status: value_to_string(&obj["status"]["detailedState"]),
+		abstract_status: value_to_string(&obj["status"]["abstractGameState"]),
+		away_score: score(&obj["teams"]["away"]["score"]),
+		home_score: score(&obj["teams"]["home"]["score"]),
+	    });
+	}
+    }
+    fixtures
+}
+
+/// One response of a game's live feed, from which its lineups, venue and
+/// situation are all read
+pub struct Feed {
+    game_id: String,
+    data: json::Value,
+}
+
+/// Fetch the live feed of a game. It is cached once the game is over, so
+/// that in-progress games are always fetched afresh.
+pub fn feed(cfg: &Config, game_id: &str) -> Result<Feed, String> {
     let data = StatsApi::game(game_id)
-	.param("fields", "gameData,liveData,boxscore,teams,players,id,abbreviation")
+	.param(
+	    "fields",
+	    "gamePk,gameData,teams,away,home,name,abbreviation,id,status,\
+	     detailedState,abstractGameState,startTimeTBD,datetime,dateTime,\
+	     venue,liveData,linescore,runs,currentInning,isTopInning,\
+	     inningState,outs,offense,defense,batter,first,second,third,\
+	     boxscore,pitchers,battingOrder,players,stats,pitching,\
+	     battersFaced,numberOfPitches",
+	)
+	.cached_if(cfg, |data| data["gameData"]["status"]["abstractGameState"] == "Final")
 	.json()?;
+    Ok(Feed {
+	game_id: game_id.to_string(),
+	data,
+    })
+}
 
-    // Heuristic to check if the lineup exists
-    let away = if !data["liveData"]["boxscore"]["teams"]["away"]["pitchers"][0].is_null() {
-	Some(Team {
-	    name: data["gameData"]["teams"]["away"]["abbreviation"].as_str().unwrap().to_string(),
-	    starting_pitcher: pitcher_stats(cfg, &data["liveData"]["boxscore"]["teams"]["away"]["pitchers"][0])?,
-	    batters: batter_stats(cfg, &data["liveData"]["boxscore"]["teams"]["away"]["battingOrder"])?
-	})
-    } else {
-	None
-    };
+pub fn teams(cfg: &Config, game_id: &str) -> Result<(Option<Team>, Option<Team>), String> {
+    feed(cfg, game_id)?.teams(cfg)
+}
 
-    let home = if !data["liveData"]["boxscore"]["teams"]["home"]["pitchers"][0].is_null() {
-	Some(Team {
-	    name: data["gameData"]["teams"]["home"]["abbreviation"].as_str().unwrap().to_string(),
-	    starting_pitcher: pitcher_stats(cfg, &data["liveData"]["boxscore"]["teams"]["home"]["pitchers"][0])?,
-	    batters: batter_stats(cfg, &data["liveData"]["boxscore"]["teams"]["home"]["battingOrder"])?
-	})
-    } else {
-	None
-    };
+impl Feed {
+    /// The game, with its current status and score
+    pub fn game(&self) -> Result<Game, String> {
+	parse_game(&self.data).ok_or(format!("Unknown game: {}", self.game_id))
+    }
+
+    pub fn teams(&self, cfg: &Config) -> Result<(Option<Team>, Option<Team>), String> {
+	let data = &self.data;
+
+	// Heuristic to check if the lineup exists
+	let away = if !data["liveData"]["boxscore"]["teams"]["away"]["pitchers"][0].is_null() {
+	    Some(Team {
+		name: data["gameData"]["teams"]["away"]["abbreviation"].as_str().unwrap().to_string(),
+		starting_pitcher: pitcher_stats(cfg, &data["liveData"]["boxscore"]["teams"]["away"]["pitchers"][0])?,
+		batters: batter_stats(cfg, &data["liveData"]["boxscore"]["teams"]["away"]["battingOrder"])?
+	    })
+	} else {
+	    None
+	};
+
+	let home = if !data["liveData"]["boxscore"]["teams"]["home"]["pitchers"][0].is_null() {
+	    Some(Team {
+		name: data["gameData"]["teams"]["home"]["abbreviation"].as_str().unwrap().to_string(),
+		starting_pitcher: pitcher_stats(cfg, &data["liveData"]["boxscore"]["teams"]["home"]["pitchers"][0])?,
+		batters: batter_stats(cfg, &data["liveData"]["boxscore"]["teams"]["home"]["battingOrder"])?
+	    })
+	} else {
+	    None
+	};
 
-    Ok((away, home))
+	Ok((away, home))
+    }
+
+    /// Venue ID of the game, if known
+    pub fn venue(&self) -> Option<String> {
+	self.data["gameData"]["venue"]["id"].as_i64().map(|id| id.to_string())
+    }
+
+    /// Starting pitchers, away first, once they are known
+    pub fn starters(&self, cfg: &Config) -> Result<[Option<PitcherStats>; 2], String> {
+	let starter = |side: &str| {
+	    let id = &self.data["liveData"]["boxscore"]["teams"][side]["pitchers"][0];
+	    if id.is_null() {
+		Ok(None)
+	    } else {
+		pitcher_stats(cfg, id).map(Some)
+	    }
+	};
+	Ok([starter("away")?, starter("home")?])
+    }
+
+    /// Runs of each team, away first, and the inning the game is in, as
+    /// reported: unlike `situation`, an inning that has ended is not moved
+    /// on from, so this is how a final game is shown
+    pub fn score(&self) -> Option<([i32; 2], i32)> {
+	let linescore = &self.data["liveData"]["linescore"];
+	let inning = value_to_int(&linescore["currentInning"]).ok()?;
+	let runs = |side: &str| value_to_int(&linescore["teams"][side]["runs"]).unwrap_or(0);
+	Some(([runs("away"), runs("home")], inning))
+    }
+
+    /// The current situation of the game, or None if it has not started
+    pub fn situation(&self, cfg: &Config) -> Result<Option<Situation>, String> {
+	let Some(mut situation) = parse_situation(&self.data)? else {
+	    return Ok(None);
+	};
+	// Relievers need their stats; a starter is already in the lineup
+	let boxscore = &self.data["liveData"]["boxscore"]["teams"];
+	for (idx, team) in ["away", "home"].iter().enumerate() {
+	    if let json::Value::Array(used) = &boxscore[team]["pitchers"] {
+		if used.len() > 1 {
+		    situation.pitchers[idx] = Some(pitcher_stats(cfg, used.last().unwrap())?);
+		}
+	    }
+	}
+	Ok(Some(situation))
+    }
+}
+
+/// The situation in a live feed, except for the stats of relievers
+fn parse_situation(data: &json::Value) -> Result<Option<Situation>, String> {
+    let linescore = &data["liveData"]["linescore"];
+    if linescore["currentInning"].is_null() {
+	return Ok(None);
+    }
+
+    let boxscore = &data["liveData"]["boxscore"]["teams"];
+    let orders = [
+	&boxscore["away"]["battingOrder"],
+	&boxscore["home"]["battingOrder"],
+    ];
+
+    // Between half-innings the linescore still points at the half that
+    // just ended, so move on to the upcoming one with a clean slate.
+    let mut inning = value_to_int(&linescore["currentInning"])?;
+    let mut top = linescore["isTopInning"].as_bool().unwrap_or(true);
+    let mut outs = value_to_int(&linescore["outs"]).unwrap_or(0);
+    let mut bases = [
+	!linescore["offense"]["first"].is_null(),
+	!linescore["offense"]["second"].is_null(),
+	!linescore["offense"]["third"].is_null(),
+    ];
+    match linescore["inningState"].as_str() {
+	Some("Middle") => {
+	    top = false;
+	    outs = 0;
+	    bases = [false; 3];
+	}
+	Some("End") => {
+	    inning += 1;
+	    top = true;
+	    outs = 0;
+	    bases = [false; 3];
+	}
+	_ => (),
+    }
+
+    // The linescore names the next batter for both the offense and the
+    // defense; look each of them up in whichever lineup holds them.
+    let mut batting_order = [0, 0];
+    for side in ["offense", "defense"] {
+	let batter = &linescore[side]["batter"]["id"];
+	if batter.is_null() {
+	    continue;
+	}
+	for (idx, order) in orders.iter().enumerate() {
+	    if let Some(slot) = batting_slot(order, batter) {
+		batting_order[idx] = slot;
+	    }
+	}
+    }
+
+    let mut batters_faced = [0, 0];
+    let mut pitch_counts = [0, 0];
+    for (idx, team) in ["away", "home"].iter().enumerate() {
+	if let Some(current) = boxscore[team]["pitchers"].as_array().and_then(|p| p.last()) {
+	    let key = format!("ID{}", value_to_string(current));
+	    let line = &boxscore[team]["players"][&key]["stats"]["pitching"];
+	    batters_faced[idx] = value_to_int(&line["battersFaced"]).unwrap_or(0);
+	    pitch_counts[idx] = value_to_int(&line["numberOfPitches"]).unwrap_or(0);
+	}
+    }
+
+    Ok(Some(Situation {
+	inning,
+	top,
+	outs,
+	bases,
+	away_score: value_to_int(&linescore["teams"]["away"]["runs"]).unwrap_or(0),
+	home_score: value_to_int(&linescore["teams"]["home"]["runs"]).unwrap_or(0),
+	batting_order,
+	pitchers: [None, None],
+	batters_faced,
+	pitch_counts,
+    }))
 }
 
+fn batting_slot(order: &json::Value, player_id: &json::Value) -> Option<usize> {
+    order
+	.as_array()?
+	.iter()
+	.position(|id| value_to_string(id) == value_to_string(player_id))
+}
 
 fn batter_stats(cfg: &Config, data: &json::Value) -> Result<Vec<BatterStats>, String> {
     let mut bats: Vec<BatterStats> = Vec::new();
 
     if let json::Value::Array(data) = data {
 	for obj in data {
-            let player_id = value_to_string(obj);
-            let raw_stats = fetch_batter_stats(cfg, &player_id)?;
-
-            let obj = &raw_stats["people"][0]["stats"][0]["splits"][0]["stat"];
-
-	    let name = raw_stats["people"][0]["initLastName"]
-		.as_str()
-		.unwrap()
-		.to_string();
-
-	    let hand = format!(
-		"{}HB",
-		raw_stats["people"][0]["batSide"]["code"].as_str().unwrap()
-	    );
-
-            bats.push(BatterStats {
-		name,
-		hand,
-		plate_appearances: value_to_int(&obj["plateAppearances"])?,
-		bases_on_balls: value_to_int(&obj["baseOnBalls"])?,
-		hits: value_to_int(&obj["hits"])?,
-		doubles: value_to_int(&obj["doubles"])?,
-		triples: value_to_int(&obj["triples"])?,
-		homeruns: value_to_int(&obj["homeRuns"])?,
-		strikeouts: value_to_int(&obj["strikeOuts"])?,
-            })
+	    bats.push(batter(cfg, &value_to_string(obj))?);
 	}
     }
     Ok(bats)
 }
 
+/// Stats of one batter, by player ID
+pub fn batter(cfg: &Config, player_id: &str) -> Result<BatterStats, String> {
+    let raw_stats = fetch_batter_stats(cfg, player_id)?;
+
+    let obj = &stat_line(cfg, &raw_stats);
+
+    let name = raw_stats["people"][0]["initLastName"]
+	.as_str()
+	.ok_or(format!("Unknown player: {}", player_id))?
+	.to_string();
+
+    let hand = format!(
+	"{}HB",
+	raw_stats["people"][0]["batSide"]["code"].as_str().unwrap_or("?")
+    );
+
+    let plate_appearances = value_to_int(&obj["plateAppearances"])?;
+    if plate_appearances == 0 {
+	return Err(format!("No plate appearances for {}", name));
+    }
+
+    Ok(BatterStats {
+	id: player_id.to_string(),
+	name,
+	hand,
+	plate_appearances,
+	bases_on_balls: value_to_int(&obj["baseOnBalls"])?,
+	hits: value_to_int(&obj["hits"])?,
+	doubles: value_to_int(&obj["doubles"])?,
+	triples: value_to_int(&obj["triples"])?,
+	homeruns: value_to_int(&obj["homeRuns"])?,
+	strikeouts: value_to_int(&obj["strikeOuts"])?,
+    })
+}
+
 fn pitcher_stats(cfg: &Config, data: &json::Value) -> Result<PitcherStats, String> {
-    let player_id = value_to_string(data);
-    let raw_stats = fetch_pitcher_stats(cfg, &player_id)?;
-    let obj = &raw_stats["people"][0]["stats"][0]["splits"][0]["stat"];
+    pitcher(cfg, &value_to_string(data))
+}
+
+/// Stats of one pitcher, by player ID
+pub fn pitcher(cfg: &Config, player_id: &str) -> Result<PitcherStats, String> {
+    let raw_stats = fetch_pitcher_stats(cfg, player_id)?;
+    let obj = &stat_line(cfg, &raw_stats);
 
     let name = raw_stats["people"][0]["initLastName"]
 	.as_str()
-	.unwrap()
+	.ok_or(format!("Unknown player: {}", player_id))?
 	.to_string();
     
     let hand = format!(
 	"{}HP",
-	raw_stats["people"][0]["batSide"]["code"].as_str().unwrap()
+	raw_stats["people"][0]["batSide"]["code"].as_str().unwrap_or("?")
     );
 
+    let batters_faced = value_to_int(&obj["battersFaced"])?;
+    if batters_faced == 0 {
+	return Err(format!("No batters faced for {}", name));
+    }
+
     Ok(PitcherStats {
+	id: player_id.to_string(),
 	name,
 	hand,
-	batters_faced: value_to_int(&obj["battersFaced"])?,
+	batters_faced,
 	bases_on_balls: value_to_int(&obj["baseOnBalls"])?,
 	hits: value_to_int(&obj["hits"])?,
 	doubles: value_to_int(&obj["doubles"])?,
@@ -248,16 +793,108 @@ fn pitcher_stats(cfg: &Config, data: &json::Value) -> Result<PitcherStats, Strin
     })
 }
 							  
-fn fetch_batter_stats(_cfg: &Config, player_id: &String) -> Result<json::Value, String> {
-    StatsApi::player(player_id)
-	.param("hydrate", "stats(group=hitting,type=career,sportId=1),currentTeam")
-	.json()
+fn fetch_batter_stats(cfg: &Config, player_id: &str) -> Result<json::Value, String> {
+    let hydrate = stats_hydrate(cfg, "hitting");
+    stats_cached(cfg, StatsApi::player(player_id).param("hydrate", &hydrate)).json()
 }
 
-fn fetch_pitcher_stats(_cfg: &Config, player_id: &String) -> Result<json::Value, String> {
-    StatsApi::player(player_id)
-	.param("hydrate", "stats(group=pitching,type=career,sportId=1),currentTeam")
-	.json()
+fn fetch_pitcher_stats(cfg: &Config, player_id: &str) -> Result<json::Value, String> {
+    let hydrate = stats_hydrate(cfg, "pitching");
+    stats_cached(cfg, StatsApi::player(player_id).param("hydrate", &hydrate)).json()
+}
+
+/// Stats up to a past date never change, but current ones do
+fn stats_cached<'a>(cfg: &Config, api: StatsApi<'a>) -> StatsApi<'a> {
+    match cfg.as_of {
+	Some(_) => api.cached(cfg),
+	None => api.api(cfg),
+    }
+}
+
+fn stats_hydrate(cfg: &Config, group: &str) -> String {
+    match (&cfg.as_of, cfg.stats) {
+	(None, StatSource::Career) => {
+	    format!("stats(group={group},type=career,sportId=1),currentTeam")
+	}
+	(None, StatSource::Season) => format!(
+	    "stats(group={group},type=season,season={},sportId=1),currentTeam",
+	    cfg.naive_date().map_or(String::new(), |d| d.format("%Y").to_string()),
+	),
+	// The current season up to the day before
+	(Some(date), StatSource::Season) => format!(
+	    "stats(group={group},type=byDateRange,startDate={}-01-01,\
+	     endDate={},sportId=1),currentTeam",
+	    date.format("%Y"),
+	    (*date - chrono::Days::new(1)).format("%F"),
+	),
+	// Past seasons year by year, plus the current season up to the
+	// day before, i.e. the career as it stood on that day
+	(Some(date), StatSource::Career) => format!(
+	    "stats(group={group},type=[yearByYear,byDateRange],startDate={}-01-01,\
+	     endDate={},sportId=1),currentTeam",
+	    date.format("%Y"),
+	    (*date - chrono::Days::new(1)).format("%F"),
+	),
+    }
+}
+
+/// The counting stats the model uses, summed over the relevant splits
+fn stat_line(cfg: &Config, raw_stats: &json::Value) -> json::Value {
+    let stats = &raw_stats["people"][0]["stats"];
+    let Some(date) = &cfg.as_of else {
+	return stats[0]["splits"][0]["stat"].clone();
+    };
+    let year = date.format("%Y").to_string();
+
+    // A season split without a team is the total of a traded player's
+    // per-team splits, and takes their place.
+    let mut seasons: BTreeMap<String, Vec<&json::Value>> = BTreeMap::new();
+    let mut totals: BTreeMap<String, &json::Value> = BTreeMap::new();
+    let mut lines = Vec::new();
+    for group in stats.as_array().into_iter().flatten() {
+	let splits = group["splits"].as_array().into_iter().flatten();
+	match group["type"]["displayName"].as_str() {
+	    Some("yearByYear") => {
+		for split in splits {
+		    let season = value_to_string(&split["season"]);
+		    if season >= year {
+			continue;
+		    }
+		    if split["team"].is_null() {
+			totals.insert(season, &split["stat"]);
+		    } else {
+			seasons.entry(season).or_default().push(&split["stat"]);
+		    }
+		}
+	    }
+	    Some("byDateRange") => lines.extend(splits.map(|s| &s["stat"])),
+	    _ => (),
+	}
+    }
+    for (season, splits) in seasons {
+	match totals.get(&season) {
+	    Some(total) => lines.push(total),
+	    None => lines.extend(splits),
+	}
+    }
+    sum_stats(lines)
+}
+
+/// Add up the integer fields of stat objects
+fn sum_stats<'a>(lines: impl IntoIterator<Item = &'a json::Value>) -> json::Value {
+    let mut sum: BTreeMap<String, i64> = BTreeMap::new();
+    for line in lines {
+	for (k, v) in line.as_object().into_iter().flatten() {
+	    if let Some(v) = v.as_i64() {
+		*sum.entry(k.clone()).or_default() += v;
+	    }
+	}
+    }
+    json::json!(sum)
+}
+
+fn score(value: &json::Value) -> Option<i32> {
+    value.as_i64().map(|v| v as i32)
 }
 
 
@@ -274,6 +911,7 @@ fn value_to_string(value: &json::Value) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chrono::NaiveDate;
     #[test]
     fn test_value_to_string() {
         let val = json::Value::String(String::from("Toronto Blue Jays"));
@@ -281,4 +919,177 @@ mod tests {
         let val = json::json!(12345);
         assert_eq!("12345", value_to_string(&val))
     }
+
+    #[test]
+    fn test_sum_stats() {
+        let lines = [
+            json::json!({"hits": 150, "homeRuns": 20, "avg": ".300"}),
+            json::json!({"hits": 10, "doubles": 2}),
+        ];
+        let sum = sum_stats(lines.iter());
+        assert_eq!(sum, json::json!({"hits": 160, "homeRuns": 20, "doubles": 2}));
+    }
+
+    #[test]
+    fn test_stat_line_as_of() {
+        let raw = json::json!({"people": [{"stats": [
+            {"type": {"displayName": "yearByYear"}, "splits": [
+                {"season": "2021", "team": {"id": 1}, "stat": {"hits": 100}},
+                {"season": "2022", "team": {"id": 1}, "stat": {"hits": 30}},
+                {"season": "2022", "team": {"id": 2}, "stat": {"hits": 40}},
+                {"season": "2022", "stat": {"hits": 70}},
+                {"season": "2023", "team": {"id": 2}, "stat": {"hits": 90}},
+            ]},
+            {"type": {"displayName": "byDateRange"}, "splits": [
+                {"stat": {"hits": 5}},
+            ]},
+        ]}]});
+        let cfg = Config {
+            as_of: NaiveDate::from_ymd_opt(2023, 4, 10),
+            ..Config::default()
+        };
+        assert_eq!(stat_line(&cfg, &raw)["hits"], json::json!(175));
+        let hydrate = stats_hydrate(&cfg, "hitting");
+        assert!(hydrate.contains("startDate=2023-01-01"));
+        assert!(hydrate.contains("endDate=2023-04-09"));
+    }
+
+    #[test]
+    fn test_fnv1a() {
+        assert_eq!(fnv1a(""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a("a"), 0xaf63dc4c8601ec8c);
+    }
+
+    #[test]
+    fn test_batting_slot() {
+        let order = json::json!([660271, 592450, 665742]);
+        assert_eq!(Some(1), batting_slot(&order, &json::json!(592450)));
+        assert_eq!(Some(2), batting_slot(&order, &json::json!("665742")));
+        assert_eq!(None, batting_slot(&order, &json::json!(1)));
+        assert_eq!(None, batting_slot(&json::Value::Null, &json::json!(1)));
+    }
+
+    #[test]
+    fn test_parse_clubs() {
+        let data = json::json!({"records": [
+            {"league": {"id": 103}, "division": {"id": 201, "name": "American League East"},
+             "teamRecords": [{"team": {"id": 147, "name": "New York Yankees", "abbreviation": "NYY"}}]},
+            {"league": {"id": 104}, "division": {"id": 204},
+             "teamRecords": [{"team": {"id": 121, "name": "New York Mets"}}]},
+        ]});
+        let clubs = parse_clubs(&data).unwrap();
+        assert_eq!(clubs.len(), 2);
+        assert_eq!(clubs[0].name, "NYY");
+        assert_eq!(clubs[0].league, "AL");
+        assert_eq!(clubs[0].division, "American League East");
+        assert_eq!(clubs[1].name, "New York Mets");
+        assert_eq!(clubs[1].division, "204");
+        assert_eq!(clubs[0].full_name, "New York Yankees");
+    }
+
+    #[test]
+    fn test_parse_game() {
+        let data = json::json!({
+            "gamePk": 745123,
+            "gameData": {
+                "status": {"detailedState": "In Progress", "abstractGameState": "Live"},
+                "datetime": {"dateTime": "2024-04-01T23:05:00Z"},
+                "teams": {"away": {"name": "Chicago Cubs"}, "home": {"name": "Texas Rangers"}},
+                "venue": {"id": 5325},
+            },
+            "liveData": {"linescore": {"teams": {"away": {"runs": 2}, "home": {"runs": 0}}}},
+        });
+        let game = parse_game(&data).unwrap();
+        assert_eq!(game.game_id, "745123");
+        assert!(game.is_live());
+        assert_eq!((game.away_score, game.home_score), (Some(2), Some(0)));
+        assert_eq!(game.venue_id.as_deref(), Some("5325"));
+        assert_eq!(game.start.unwrap().to_rfc3339(), "2024-04-01T23:05:00+00:00");
+        let status = json::json!({"startTimeTBD": true});
+        assert_eq!(start_time(&json::json!("2024-04-01T17:10:00Z"), &status), None);
+        assert!(parse_game(&json::json!({"messageNumber": 10})).is_none());
+    }
+
+    #[test]
+    fn test_parse_situation() {
+        let mut data = json::json!({"liveData": {
+            "linescore": {
+                "currentInning": 6, "isTopInning": true, "inningState": "Top", "outs": 1,
+                "offense": {"batter": {"id": 592450}, "second": {"id": 660271}},
+                "defense": {"batter": {"id": 665742}},
+                "teams": {"away": {"runs": 3}, "home": {"runs": 2}},
+            },
+            "boxscore": {"teams": {
+                "away": {"battingOrder": [660271, 592450], "pitchers": [1]},
+                "home": {"battingOrder": [665742], "pitchers": [2, 3],
+                         "players": {"ID3": {"stats": {"pitching":
+                             {"battersFaced": 4, "numberOfPitches": 17}}}}},
+            }},
+        }});
+        let s = parse_situation(&data).unwrap().unwrap();
+        assert_eq!((s.inning, s.top, s.outs), (6, true, 1));
+        assert_eq!(s.bases, [false, true, false]);
+        assert_eq!(s.batting_order, [1, 0]);
+        assert_eq!((s.away_score, s.home_score), (3, 2));
+        assert_eq!((s.batters_faced, s.pitch_counts), ([0, 4], [0, 17]));
+
+        // At the end of an inning, the next one is about to start
+        data["liveData"]["linescore"]["inningState"] = json::json!("End");
+        let s = parse_situation(&data).unwrap().unwrap();
+        assert_eq!((s.inning, s.top, s.outs, s.bases), (7, true, 0, [false; 3]));
+        // ...but a final score is shown as the inning the game ended in
+        let feed = Feed {
+            game_id: "745123".to_string(),
+            data,
+        };
+        assert_eq!(feed.score(), Some(([3, 2], 6)));
+        assert!(parse_situation(&json::json!({})).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_parse_players() {
+        let data = json::json!({"people": [
+            {"id": 660271, "fullName": "Shohei Ohtani", "primaryPosition": {"abbreviation": "TWP"}},
+            {"id": 808967, "fullName": "Yoshinobu Yamamoto", "primaryPosition": {"abbreviation": "P"}},
+        ]});
+        let found = parse_players(&data, "ohtani");
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].id, "660271");
+        assert_eq!(found[0].position, "TWP");
+        assert_eq!(parse_players(&data, "o").len(), 2);
+    }
+
+    #[test]
+    fn test_parse_fixtures() {
+        let data = json::json!({"dates": [{"date": "2024-04-01", "games": [
+            {"gamePk": 1, "officialDate": "2024-04-01",
+             "status": {"detailedState": "Final", "abstractGameState": "Final"},
+             "teams": {"away": {"team": {"id": 147}, "score": 3},
+                       "home": {"team": {"id": 121}, "score": 5}}},
+            {"gamePk": 2,
+             "status": {"detailedState": "Scheduled", "abstractGameState": "Preview"},
+             "teams": {"away": {"team": {"id": 121}}, "home": {"team": {"id": 147}}}},
+        ]}]});
+        let fixtures = parse_fixtures(&data);
+        assert_eq!(fixtures.len(), 2);
+        assert_eq!(fixtures[0].home_score, Some(5));
+        assert_eq!(fixtures[1].date, "2024-04-01");
+        assert_eq!(fixtures[1].home_id, "147");
+        assert_eq!(fixtures[1].away_score, None);
+    }
+
+    #[test]
+    fn test_all_final() {
+        let schedule = |states: &[&str]| {
+            let games: Vec<_> = states
+                .iter()
+                .map(|s| json::json!({"status": {"abstractGameState": s}}))
+                .collect();
+            json::json!({"dates": [{"date": "2024-04-01", "games": games}]})
+        };
+        assert!(all_final(&schedule(&["Final", "Final"])));
+        assert!(!all_final(&schedule(&["Final", "Live"])));
+        assert!(!all_final(&schedule(&[])));
+        assert!(!all_final(&json::json!({"dates": []})));
+    }
 }
@@ -0,0 +1,588 @@
+use std::fs;
+use std::path::Path;
+
+use chrono::NaiveDate;
+use serde_json as json;
+
+use crate::{
+    api::Oracle,
+    config::Config,
+    filter::Slate,
+    mlbstats::Game,
+    output::csv_split,
+    simba::SimResult,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Market {
+    Moneyline,
+    RunLine,
+    Total,
+}
+
+impl Market {
+    fn parse(s: &str) -> Result<Market, String> {
+        match s.to_lowercase().replace(['_', '-', ' '], "").as_str() {
+            "moneyline" | "ml" => Ok(Market::Moneyline),
+            "runline" | "rl" | "spread" => Ok(Market::RunLine),
+            "total" | "totals" | "ou" => Ok(Market::Total),
+            _ => Err(format!(
+                "Invalid market \"{}\": expected moneyline, runline or total",
+                s
+            )),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Market::Moneyline => "Moneyline",
+            Market::RunLine => "Run line",
+            Market::Total => "Total",
+        }
+    }
+}
+
+/// One price on one side of a market
+#[derive(Clone, Debug, PartialEq)]
+pub struct Quote {
+    pub date: Option<NaiveDate>,
+    pub game_id: Option<String>,
+    pub away: String,
+    pub home: String,
+    pub market: Market,
+    /// 0 for the away club or the over, 1 for the home club or the under
+    pub side: usize,
+    /// The home club's handicap for run lines, the number of runs for totals
+    pub line: Option<f64>,
+    /// Decimal odds
+    pub price: f64,
+}
+
+impl Quote {
+    /// Build a quote from named fields, as found in a CSV row or a JSON object
+    fn from_fields(get: impl Fn(&str) -> Option<String>) -> Result<Quote, String> {
+        let required = |name: &str| get(name).ok_or(format!("No \"{}\"", name));
+        let market = Market::parse(&required("market")?)?;
+        let side = required("side")?.to_lowercase();
+        let side = match (market, side.as_str()) {
+            (Market::Total, "over") => 0,
+            (Market::Total, "under") => 1,
+            (Market::Moneyline | Market::RunLine, "away") => 0,
+            (Market::Moneyline | Market::RunLine, "home") => 1,
+            _ => return Err(format!("Invalid side \"{}\" for {}", side, market.name())),
+        };
+        let line = match (market, get("line")) {
+            (Market::Moneyline, _) => None,
+            (_, Some(line)) => {
+                let line: f64 = line
+                    .trim_start_matches('+')
+                    .parse()
+                    .map_err(|_| format!("Invalid line \"{}\"", line))?;
+                // Run lines are kept from the home club's side
+                Some(if market == Market::RunLine && side == 0 {
+                    -line
+                } else {
+                    line
+                })
+            }
+            (_, None) => return Err(format!("No line for {}", market.name())),
+        };
+        let date = match get("date") {
+            Some(d) => Some(
+                NaiveDate::parse_from_str(&d, "%F").map_err(|_| format!("Invalid date: {}", d))?,
+            ),
+            None => None,
+        };
+        Ok(Quote {
+            date,
+            game_id: get("game_id"),
+            away: required("away")?,
+            home: required("home")?,
+            market,
+            side,
+            line,
+            price: parse_price(&required("price")?)?,
+        })
+    }
+
+    /// Whether the quote is for `game`, played on `date` between clubs
+    /// known by their full names, nicknames or abbreviations
+    fn matches(&self, game: &Game, date: NaiveDate, abbreviations: [&str; 2]) -> bool {
+        if let Some(id) = &self.game_id {
+            return *id == game.game_id;
+        }
+        let same = |q: &str, full: &str, abbreviation: &str| {
+            let (q, full) = (q.to_lowercase(), full.to_lowercase());
+            q == full || full.ends_with(&format!(" {}", q)) || q.eq_ignore_ascii_case(abbreviation)
+        };
+        self.date.is_none_or(|d| d == date)
+            && same(&self.away, &game.away_name, abbreviations[0])
+            && same(&self.home, &game.home_name, abbreviations[1])
+    }
+}
+
+/// Decimal odds from American (-150, +130), decimal (2.30) or fractional
+/// (13/10) odds
+pub fn parse_price(s: &str) -> Result<f64, String> {
+    let s = s.trim();
+    let err = || format!("Invalid odds: \"{}\"", s);
+    if s.eq_ignore_ascii_case("even") || s.eq_ignore_ascii_case("ev") {
+        return Ok(2.0);
+    }
+    if let Some((num, den)) = s.split_once('/') {
+        let num: f64 = num.trim().parse().map_err(|_| err())?;
+        let den: f64 = den.trim().parse().map_err(|_| err())?;
+        if num <= 0.0 || den <= 0.0 {
+            return Err(err());
+        }
+        return Ok(1.0 + num / den);
+    }
+    let x: f64 = s.parse().map_err(|_| err())?;
+    if s.starts_with(['+', '-']) || x.abs() >= 100.0 {
+        if x >= 100.0 {
+            Ok(1.0 + x / 100.0)
+        } else if x <= -100.0 {
+            Ok(1.0 - 100.0 / x)
+        } else {
+            Err(err())
+        }
+    } else if x > 1.0 {
+        Ok(x)
+    } else {
+        Err(err())
+    }
+}
+
+/// American odds paying out fairly on probability `p`
+pub fn american(p: f64) -> String {
+    if !(p > 0.0 && p < 1.0) {
+        return "-".to_string();
+    }
+    let profit = 1.0 / p - 1.0;
+    if profit >= 1.0 {
+        format!("+{:.0}", 100.0 * profit)
+    } else {
+        format!("{:.0}", -100.0 / profit)
+    }
+}
+
+/// Probabilities implied by the prices of both sides of a market, scaled
+/// so that they add up to one
+pub fn devig(prices: [f64; 2]) -> [f64; 2] {
+    let implied = prices.map(|d| 1.0 / d);
+    let total: f64 = implied.iter().sum();
+    implied.map(|p| p / total)
+}
+
+/// Share of the bankroll to stake on a bet at decimal odds `price` that
+/// wins with probability `p`, or zero without an edge
+pub fn kelly(p: f64, price: f64) -> f64 {
+    ((p * price - 1.0) / (price - 1.0)).max(0.0)
+}
+
+/// Read quotes from a JSON array of objects if the file name ends in
+/// .json, or else from a CSV file with a header row. Fields are date,
+/// game_id, away, home, market, side, line and price.
+pub fn read_quotes(path: &Path) -> Result<Vec<Quote>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut quotes = Vec::new();
+    if path
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("json"))
+    {
+        let data: json::Value =
+            json::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let rows = data
+            .as_array()
+            .ok_or(format!("{}: expected an array of quotes", path.display()))?;
+        for (n, row) in rows.iter().enumerate() {
+            let get = |name: &str| match &row[name] {
+                json::Value::String(s) if !s.trim().is_empty() => Some(s.trim().to_string()),
+                json::Value::Number(x) => Some(x.to_string()),
+                _ => None,
+            };
+            quotes.push(
+                Quote::from_fields(get)
+                    .map_err(|e| format!("{}: quote {}: {}", path.display(), n + 1, e))?,
+            );
+        }
+    } else {
+        let mut lines = text.lines();
+        let header = csv_split(lines.next().unwrap_or(""));
+        for (n, line) in lines.enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+            let row = csv_split(line);
+            let get = |name: &str| {
+                header
+                    .iter()
+                    .position(|h| h.trim().eq_ignore_ascii_case(name))
+                    .and_then(|i| row.get(i))
+                    .map(|v| v.trim().to_string())
+                    .filter(|v| !v.is_empty())
+            };
+            quotes.push(
+                Quote::from_fields(get)
+                    .map_err(|e| format!("{}: line {}: {}", path.display(), n + 2, e))?,
+            );
+        }
+    }
+    Ok(quotes)
+}
+
+/// One side of a market, priced by the model and by the book. On integer
+/// totals and run lines, the probabilities leave out pushes, so they and
+/// the figures derived from them are conditional on the bet not pushing.
+pub struct Bet {
+    pub market: Market,
+    pub pick: String,
+    pub price: f64,
+    pub model: f64,
+    pub market_probability: f64,
+    /// Whether the other side was quoted, so that the vig could be removed
+    pub devigged: bool,
+}
+
+impl Bet {
+    pub fn edge(&self) -> f64 {
+        self.model - self.market_probability
+    }
+
+    /// Expected profit per unit staked, unless the bet pushes
+    pub fn expected_value(&self) -> f64 {
+        self.model * self.price - 1.0
+    }
+
+    pub fn kelly(&self) -> f64 {
+        kelly(self.model, self.price)
+    }
+}
+
+/// Probability of each side of a market, leaving out pushes
+fn model_probabilities(
+    result: &SimResult,
+    market: Market,
+    line: Option<f64>,
+) -> Result<[f64; 2], String> {
+    let line = line.unwrap_or(0.0);
+    let (a, b) = match market {
+        Market::Moneyline => {
+            let hwp = result.home_win_probability.ok_or("No prediction")?;
+            (1.0 - hwp, hwp)
+        }
+        Market::RunLine => (
+            result.probability(|s| (s.home as f64 + line) < s.away as f64),
+            result.probability(|s| (s.home as f64 + line) > s.away as f64),
+        ),
+        Market::Total => (
+            result.probability(|s| (s.away + s.home) as f64 > line),
+            result.probability(|s| ((s.away + s.home) as f64) < line),
+        ),
+    };
+    if a + b == 0.0 {
+        return Err("Every simulated game is a push".to_string());
+    }
+    Ok([a / (a + b), b / (a + b)])
+}
+
+/// Price every quoted side of a game's markets
+pub fn bets(result: &SimResult, game: &Game, quotes: &[&Quote]) -> Result<Vec<Bet>, String> {
+    // Both sides of a market share its kind and line
+    let mut markets: Vec<(Market, Option<f64>, [Option<f64>; 2])> = Vec::new();
+    for q in quotes {
+        match markets
+            .iter_mut()
+            .find(|(m, line, _)| *m == q.market && *line == q.line)
+        {
+            Some((_, _, prices)) => prices[q.side] = Some(q.price),
+            None => {
+                let mut prices = [None, None];
+                prices[q.side] = Some(q.price);
+                markets.push((q.market, q.line, prices));
+            }
+        }
+    }
+
+    let mut bets = Vec::new();
+    for (market, line, prices) in markets {
+        let model = model_probabilities(result, market, line)?;
+        let fair = match prices {
+            [Some(a), Some(b)] => Some(devig([a, b])),
+            _ => None,
+        };
+        for side in 0..2 {
+            let Some(price) = prices[side] else {
+                continue;
+            };
+            let pick = match (market, line) {
+                (Market::Moneyline, _) => [&game.away_name, &game.home_name][side].clone(),
+                (Market::RunLine, Some(h)) => {
+                    let handicap = if side == 1 { h } else { -h };
+                    format!(
+                        "{} {:+}",
+                        [&game.away_name, &game.home_name][side],
+                        handicap
+                    )
+                }
+                (_, line) => format!("{} {}", ["Over", "Under"][side], line.unwrap_or_default()),
+            };
+            bets.push(Bet {
+                market,
+                pick,
+                price,
+                model: model[side],
+                market_probability: fair.map_or(1.0 / price, |f| f[side]),
+                devigged: fair.is_some(),
+            });
+        }
+    }
+    Ok(bets)
+}
+
+fn print(game: &Game, bets: &[Bet], kelly_fraction: f64) {
+    println!();
+    for line in table(game, bets, kelly_fraction) {
+        println!("{}", line);
+    }
+}
+
+fn table(game: &Game, bets: &[Bet], kelly_fraction: f64) -> Vec<String> {
+    let mut lines = vec![
+        format!("{} @ {}", game.away_name, game.home_name),
+        format!(
+            "  {:<10}{:<28}{:>7}{:>7}{:>8}{:>8}{:>8}{:>8}{:>8}",
+            "Market", "Pick", "Price", "Fair", "Model", "Market", "Edge", "EV", "Kelly"
+        ),
+    ];
+    for bet in bets {
+        // One-sided quotes are marked within the Market column
+        let market = format!(
+            "{:>6.1}%{}",
+            100.0 * bet.market_probability,
+            if bet.devigged { " " } else { "*" }
+        );
+        lines.push(format!(
+            "  {:<10}{:<28}{:>7}{:>7}{:>7.1}%{:>8}{:>+7.1}%{:>+7.1}%{:>7.1}%",
+            bet.market.name(),
+            bet.pick,
+            american(1.0 / bet.price),
+            american(bet.model),
+            100.0 * bet.model,
+            market,
+            100.0 * bet.edge(),
+            100.0 * bet.expected_value(),
+            100.0 * kelly_fraction * bet.kelly()
+        ));
+    }
+    lines
+}
+
+/// Compare the predictions of the configured date with the quotes in a
+/// file
+pub fn odds(cfg: &Config, path: &Path, kelly_fraction: f64) -> Result<(), String> {
+    let quotes = read_quotes(path)?;
+    let date = cfg.naive_date()?;
+    let mut matched = vec![0; quotes.len()];
+    let mut one_sided = false;
+
+    let oracle = Oracle::with_config(cfg.clone());
+    for game in Slate::new(cfg)?.games(cfg)? {
+        let prediction = match oracle.predict(&game) {
+            Ok(prediction) => prediction,
+            Err(e) => {
+                eprintln!("[WARNING] {}: {}", game.game_id, e);
+                continue;
+            }
+        };
+        let (Some([away, home]), Some(result)) = (prediction.teams, prediction.result) else {
+            continue;
+        };
+        let game_quotes: Vec<&Quote> = quotes
+            .iter()
+            .enumerate()
+            .filter(|(_, q)| q.matches(&game, date, [&away.name, &home.name]))
+            .map(|(i, q)| {
+                matched[i] += 1;
+                q
+            })
+            .collect();
+        if game_quotes.is_empty() {
+            continue;
+        }
+        let bets = bets(&result, &game, &game_quotes)?;
+        one_sided |= bets.iter().any(|b| !b.devigged);
+        print(&game, &bets, kelly_fraction);
+    }
+
+    if one_sided {
+        println!();
+        println!("* Only one side quoted: the market probability includes the vig");
+    }
+    let for_date = |q: &&Quote| q.date.is_none_or(|d| d == date);
+    let unmatched = quotes
+        .iter()
+        .zip(&matched)
+        .filter(|(q, n)| **n == 0 && for_date(q))
+        .count();
+    if unmatched > 0 {
+        eprintln!(
+            "[WARNING] {} quotes match no game with lineups on {}",
+            unmatched, date
+        );
+    }
+    if matched.iter().any(|n| *n > 1) {
+        eprintln!("[WARNING] Some quotes match several games: give their game_id");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use crate::simba::Score;
+
+    fn quote(market: &str, side: &str, line: Option<&str>, price: &str) -> Quote {
+        let fields = [
+            ("away", "Blue Jays"),
+            ("home", "NYY"),
+            ("market", market),
+            ("side", side),
+            ("line", line.unwrap_or("")),
+            ("price", price),
+        ];
+        Quote::from_fields(|name| {
+            fields
+                .iter()
+                .find(|(k, v)| *k == name && !v.is_empty())
+                .map(|(_, v)| v.to_string())
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_parse_price() {
+        assert_eq!(parse_price("-150"), Ok(1.0 + 100.0 / 150.0));
+        assert_eq!(parse_price("+130"), Ok(2.3));
+        assert_eq!(parse_price("130"), Ok(2.3));
+        assert_eq!(parse_price("2.30"), Ok(2.3));
+        assert_eq!(parse_price("13/10"), Ok(2.3));
+        assert_eq!(parse_price("EVEN"), Ok(2.0));
+        assert!(parse_price("-50").is_err());
+        assert!(parse_price("0.9").is_err());
+        assert!(parse_price("1/0").is_err());
+        assert!(parse_price("ten").is_err());
+    }
+
+    #[test]
+    fn test_american_devig_kelly() {
+        assert_eq!(american(0.6), "-150");
+        assert_eq!(american(0.4), "+150");
+        assert_eq!(american(1.0), "-");
+        let fair = devig([parse_price("-110").unwrap(), parse_price("-110").unwrap()]);
+        assert!((fair[0] - 0.5).abs() < 1e-12);
+        let fair = devig([1.5, 2.6]);
+        assert!((fair[0] + fair[1] - 1.0).abs() < 1e-12);
+        assert!(fair[0] > fair[1]);
+        assert!((kelly(0.55, 2.0) - 0.1).abs() < 1e-12);
+        assert_eq!(kelly(0.45, 2.0), 0.0);
+    }
+
+    #[test]
+    fn test_read_quotes() {
+        let dir = std::env::temp_dir().join(format!("mlb-oracle-odds-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let csv = dir.join("odds.csv");
+        fs::write(
+            &csv,
+            "date,away,home,market,side,line,price\n\
+             2024-06-01,Blue Jays,Yankees,moneyline,away,,+120\n\
+             2024-06-01,Blue Jays,Yankees,run line,away,+1.5,-170\n\
+             2024-06-01,Blue Jays,Yankees,total,under,8.5,1.95\n",
+        )
+        .unwrap();
+        let quotes = read_quotes(&csv).unwrap();
+        assert_eq!(quotes.len(), 3);
+        assert_eq!(quotes[0].market, Market::Moneyline);
+        assert_eq!(quotes[1].line, Some(-1.5));
+        assert_eq!((quotes[2].side, quotes[2].line), (1, Some(8.5)));
+
+        let js = dir.join("odds.json");
+        fs::write(
+            &js,
+            r#"[{"away": "TOR", "home": "NYY", "market": "ml", "side": "home", "price": "4/6"}]"#,
+        )
+        .unwrap();
+        let quotes = read_quotes(&js).unwrap();
+        assert_eq!(quotes[0].side, 1);
+        assert!((quotes[0].price - (1.0 + 4.0 / 6.0)).abs() < 1e-12);
+
+        fs::write(&csv, "away,home,market,side,price\nA,B,total,over,1.9\n").unwrap();
+        assert!(read_quotes(&csv).unwrap_err().contains("line 2"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_matches() {
+        let game = fixtures::game("Toronto Blue Jays", "New York Yankees");
+        let date = NaiveDate::from_ymd_opt(2024, 6, 1).unwrap();
+        let mut q = quote("moneyline", "home", None, "-140");
+        assert!(q.matches(&game, date, ["TOR", "NYY"]));
+        q.date = NaiveDate::from_ymd_opt(2024, 6, 2);
+        assert!(!q.matches(&game, date, ["TOR", "NYY"]));
+        q.date = None;
+        q.home = "Mets".to_string();
+        assert!(!q.matches(&game, date, ["TOR", "NYY"]));
+        q.game_id = Some("745123".to_string());
+        assert!(q.matches(&game, date, ["TOR", "NYY"]));
+    }
+
+    #[test]
+    fn test_bets() {
+        let score = |away, home| Score { away, home };
+        let result = SimResult {
+            home_win_probability: Some(0.6),
+            expected_away_runs: 4.0,
+            expected_home_runs: 5.0,
+            iterations: 10,
+            seed: 0,
+            scores: vec![
+                (score(2, 5), 4),
+                (score(3, 4), 2),
+                (score(5, 3), 3),
+                (score(6, 4), 1),
+            ],
+        };
+        let game = fixtures::game("Toronto Blue Jays", "New York Yankees");
+        let quotes = [
+            quote("moneyline", "away", None, "+120"),
+            quote("moneyline", "home", None, "-140"),
+            quote("runline", "home", Some("-1.5"), "+150"),
+            quote("total", "over", Some("8"), "-110"),
+            quote("total", "under", Some("8"), "-110"),
+        ];
+        let bets = bets(&result, &game, &quotes.iter().collect::<Vec<_>>()).unwrap();
+        assert_eq!(bets.len(), 5);
+        assert_eq!(bets[1].pick, "New York Yankees");
+        assert!((bets[1].model - 0.6).abs() < 1e-12);
+        assert!((bets[0].market_probability + bets[1].market_probability - 1.0).abs() < 1e-12);
+
+        // Home wins by 2 or more in 4 of 10 games
+        assert_eq!(bets[2].pick, "New York Yankees -1.5");
+        assert!((bets[2].model - 0.4).abs() < 1e-12);
+        assert!(!bets[2].devigged);
+        assert!((bets[2].expected_value() - 0.0).abs() < 1e-12);
+
+        // The 3 games with 8 runs are pushes: over in 1 of the other 7
+        assert_eq!(bets[3].pick, "Over 8");
+        assert!((bets[3].model - 1.0 / 7.0).abs() < 1e-12);
+        assert_eq!(bets[3].kelly(), 0.0);
+        assert!((bets[4].edge() - (6.0 / 7.0 - 0.5)).abs() < 1e-12);
+        assert!(bets[4].kelly() > 0.0);
+
+        // The one-sided marker does not shift the columns after it
+        let lines = table(&game, &bets, 0.25);
+        assert!(lines[1..].iter().all(|l| l.chars().count() == lines[1].len()));
+        let edge = lines[1].find("Edge").unwrap();
+        assert_eq!(lines[4].chars().nth(edge - 5), Some('*'));
+        assert_eq!(lines[2].chars().nth(edge - 5), Some(' '));
+    }
+}
@@ -0,0 +1,406 @@
+use std::env;
+use std::ffi::OsString;
+use std::io::{self, IsTerminal, Write};
+
+use clap::ValueEnum;
+use serde_json as json;
+
+use crate::mlbstats::{Game, Team};
+use crate::simba::SimResult;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Colored probability bars, for humans
+    Text,
+    /// A single JSON array of predictions
+    Json,
+    /// One JSON object per line
+    Ndjson,
+    /// Comma-separated values with a header row
+    Csv,
+    /// A standalone web page, for `report`
+    Html,
+    /// Markdown, for `report`
+    #[value(name = "md")]
+    Markdown,
+}
+
+impl Format {
+    /// Whether this is a format of `report`, rather than of predictions
+    pub fn is_document(self) -> bool {
+        matches!(self, Format::Html | Format::Markdown)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+    /// Color when writing to a terminal, unless NO_COLOR is set
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Display {
+    /// Win probability bars only
+    Bar,
+    /// Bars with percentages, fair odds, expected runs and the total
+    Numbers,
+    /// Short bars, club nicknames and the total, within 80 columns
+    Compact,
+}
+
+/// Terminal colors, by their ANSI escape code
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Color {
+    Black = 30,
+    Red = 31,
+    Green = 32,
+    Yellow = 33,
+    Blue = 34,
+    Magenta = 35,
+    Cyan = 36,
+    White = 37,
+}
+
+/// Colors of the probability bars: a probability of at least
+/// `thresholds[i]` gets `colors[i]`, and anything lower the last color
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Colormap {
+    pub thresholds: [f64; 4],
+    pub colors: [Color; 5],
+}
+
+impl Colormap {
+    pub fn color(&self, p: f64) -> Color {
+        self.thresholds
+            .iter()
+            .position(|&t| p >= t)
+            .map_or(self.colors[4], |i| self.colors[i])
+    }
+
+    /// Thresholds must decrease
+    pub fn validate(&self) -> Result<(), String> {
+        if self.thresholds.windows(2).all(|w| w[0] >= w[1]) {
+            Ok(())
+        } else {
+            Err(format!("Colormap thresholds must decrease: {:?}", self.thresholds))
+        }
+    }
+}
+
+impl Default for Colormap {
+    fn default() -> Colormap {
+        Colormap {
+            thresholds: [0.75, 0.55, 0.45, 0.25],
+            colors: [Color::Cyan, Color::Green, Color::Yellow, Color::Red, Color::White],
+        }
+    }
+}
+
+/// How the text format may draw on stdout
+#[derive(Clone, Copy, Debug)]
+pub struct Style {
+    /// Emit color and bold escape codes
+    pub color: bool,
+    /// Rewrite lines in place to animate progress
+    pub interactive: bool,
+    pub display: Display,
+    pub colormap: Colormap,
+}
+
+impl Style {
+    pub fn detect(choice: ColorChoice, display: Display, colormap: Colormap) -> Style {
+        let interactive = io::stdout().is_terminal();
+        Style {
+            color: use_color(choice, interactive, env::var_os("NO_COLOR")),
+            interactive,
+            display,
+            colormap,
+        }
+    }
+}
+
+fn use_color(choice: ColorChoice, interactive: bool, no_color: Option<OsString>) -> bool {
+    match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        // See https://no-color.org: set and non-empty disables color
+        ColorChoice::Auto => interactive && no_color.is_none_or(|v| v.is_empty()),
+    }
+}
+
+/// Everything known about the prediction of one game
+#[derive(Debug, Default)]
+pub struct Record {
+    pub game_id: String,
+    pub status: String,
+    /// Time of first pitch in UTC, as RFC 3339
+    pub start_time: Option<String>,
+    pub away_name: String,
+    pub home_name: String,
+    pub away_team: Option<String>,
+    pub home_team: Option<String>,
+    pub away_starter: Option<String>,
+    pub home_starter: Option<String>,
+    pub away_lineup: Vec<String>,
+    pub home_lineup: Vec<String>,
+    /// How far the oracle got, e.g. "predicted" or "missing_lineups"
+    pub outcome: String,
+    pub home_win_probability: Option<f64>,
+    pub iterations: Option<usize>,
+    pub seed: Option<u64>,
+}
+
+const CSV_HEADER: [&str; 16] = [
+    "game_id",
+    "status",
+    "away_name",
+    "home_name",
+    "away_team",
+    "home_team",
+    "away_starter",
+    "home_starter",
+    "away_lineup",
+    "home_lineup",
+    "outcome",
+    "away_win_probability",
+    "home_win_probability",
+    "iterations",
+    "seed",
+    // Last, so that it does not move the columns that came before it
+    "start_time",
+];
+
+impl Record {
+    pub fn new(game: &Game) -> Record {
+        Record {
+            game_id: game.game_id.clone(),
+            status: game.status.clone(),
+            start_time: game
+                .start
+                .map(|t| t.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)),
+            away_name: game.away_name.clone(),
+            home_name: game.home_name.clone(),
+            ..Record::default()
+        }
+    }
+
+    /// Record the lineups and starters used for the prediction
+    pub fn teams(&mut self, away: &Team, home: &Team) {
+        self.away_team = Some(away.name.clone());
+        self.home_team = Some(home.name.clone());
+        self.away_starter = Some(away.starting_pitcher.name.clone());
+        self.home_starter = Some(home.starting_pitcher.name.clone());
+        self.away_lineup = away.batters.iter().map(|b| b.name.clone()).collect();
+        self.home_lineup = home.batters.iter().map(|b| b.name.clone()).collect();
+    }
+
+    /// Record the outcome of the simulations
+    pub fn simulated(&mut self, result: &SimResult) {
+        self.outcome = match result.home_win_probability {
+            Some(_) => "predicted".to_string(),
+            None => "no_prediction".to_string(),
+        };
+        self.home_win_probability = result.home_win_probability;
+        self.iterations = Some(result.iterations);
+        self.seed = Some(result.seed);
+    }
+
+    pub fn away_win_probability(&self) -> Option<f64> {
+        self.home_win_probability.map(|p| 1.0 - p)
+    }
+
+    pub fn to_json(&self) -> json::Value {
+        json::json!({
+            "game_id": self.game_id,
+            "status": self.status,
+            "start_time": self.start_time,
+            "away": {
+                "name": self.away_name,
+                "team": self.away_team,
+                "starter": self.away_starter,
+                "lineup": self.away_lineup,
+                "win_probability": self.away_win_probability(),
+            },
+            "home": {
+                "name": self.home_name,
+                "team": self.home_team,
+                "starter": self.home_starter,
+                "lineup": self.home_lineup,
+                "win_probability": self.home_win_probability,
+            },
+            "outcome": self.outcome,
+            "iterations": self.iterations,
+            "seed": self.seed,
+        })
+    }
+
+    pub fn to_csv(&self) -> String {
+        fn opt<T: ToString>(v: &Option<T>) -> String {
+            v.as_ref().map(|v| v.to_string()).unwrap_or_default()
+        }
+        [
+            self.game_id.clone(),
+            self.status.clone(),
+            self.away_name.clone(),
+            self.home_name.clone(),
+            opt(&self.away_team),
+            opt(&self.home_team),
+            opt(&self.away_starter),
+            opt(&self.home_starter),
+            self.away_lineup.join(";"),
+            self.home_lineup.join(";"),
+            self.outcome.clone(),
+            opt(&self.away_win_probability()),
+            opt(&self.home_win_probability),
+            opt(&self.iterations),
+            opt(&self.seed),
+            opt(&self.start_time),
+        ]
+        .iter()
+        .map(|f| csv_field(f))
+        .collect::<Vec<_>>()
+        .join(",")
+    }
+}
+
+pub(crate) fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Split a CSV line into fields, undoing `csv_field`
+pub(crate) fn csv_split(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+/// Writes records to stdout in the machine formats. The text format is
+/// drawn by `GameLine` instead, so records are dropped.
+pub struct Writer {
+    format: Format,
+    count: usize,
+}
+
+impl Writer {
+    pub fn new(format: Format) -> Writer {
+        Writer { format, count: 0 }
+    }
+
+    pub fn write(&mut self, record: &Record) {
+        let mut out = io::stdout().lock();
+        let _ = match self.format {
+            Format::Text | Format::Html | Format::Markdown => Ok(()),
+            Format::Json => {
+                let sep = if self.count == 0 { "[\n" } else { ",\n" };
+                write!(out, "{}{}", sep, record.to_json())
+            }
+            Format::Ndjson => writeln!(out, "{}", record.to_json()),
+            Format::Csv => {
+                if self.count == 0 {
+                    let _ = writeln!(out, "{}", CSV_HEADER.join(","));
+                }
+                writeln!(out, "{}", record.to_csv())
+            }
+        };
+        let _ = out.flush();
+        self.count += 1;
+    }
+
+    /// Close the output, e.g. the JSON array
+    pub fn finish(&mut self) {
+        match self.format {
+            Format::Json if self.count == 0 => println!("[]"),
+            Format::Json => println!("\n]"),
+            Format::Csv if self.count == 0 => println!("{}", CSV_HEADER.join(",")),
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record() -> Record {
+        Record {
+            game_id: "745123".to_string(),
+            status: "Scheduled".to_string(),
+            start_time: Some("2024-04-01T23:07:00Z".to_string()),
+            away_name: "Toronto Blue Jays".to_string(),
+            home_name: "New York Yankees".to_string(),
+            away_lineup: vec!["G. Springer".to_string(), "B. Bichette".to_string()],
+            outcome: "predicted".to_string(),
+            home_win_probability: Some(0.25),
+            iterations: Some(1000),
+            seed: Some(42),
+            ..Record::default()
+        }
+    }
+
+    #[test]
+    fn test_use_color() {
+        let set = Some(OsString::from("1"));
+        let empty = Some(OsString::new());
+        assert!(use_color(ColorChoice::Auto, true, None));
+        assert!(use_color(ColorChoice::Auto, true, empty));
+        assert!(!use_color(ColorChoice::Auto, true, set.clone()));
+        assert!(!use_color(ColorChoice::Auto, false, None));
+        assert!(use_color(ColorChoice::Always, false, set));
+        assert!(!use_color(ColorChoice::Never, true, None));
+    }
+
+    #[test]
+    fn test_csv_field() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn test_csv_split() {
+        for fields in [vec!["a", "b,c", "say \"hi\""], vec!["", ""]] {
+            let line = fields.iter().map(|f| csv_field(f)).collect::<Vec<_>>().join(",");
+            assert_eq!(csv_split(&line), fields);
+        }
+    }
+
+    #[test]
+    fn test_csv_row() {
+        let row = record().to_csv();
+        assert_eq!(row.split(',').count(), CSV_HEADER.len());
+        assert!(row.contains("G. Springer;B. Bichette"));
+        assert!(row.ends_with(",0.75,0.25,1000,42,2024-04-01T23:07:00Z"));
+        assert_eq!(CSV_HEADER.last(), Some(&"start_time"));
+        assert!(!row.contains('\x1B'));
+    }
+
+    #[test]
+    fn test_json() {
+        let val = record().to_json();
+        assert_eq!(val["away"]["win_probability"], json::json!(0.75));
+        assert_eq!(val["home"]["win_probability"], json::json!(0.25));
+        assert_eq!(val["home"]["starter"], json::Value::Null);
+        assert_eq!(val["seed"], json::json!(42));
+        assert_eq!(val["start_time"], json::json!("2024-04-01T23:07:00Z"));
+    }
+}
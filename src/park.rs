@@ -0,0 +1,197 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+use crate::output::csv_split;
+
+/// Park factors of the major league venues, as indices where 100 is neutral
+const BUNDLED: &str = include_str!("../data/park_factors.csv");
+
+/// Home-field adjustment used unless configured otherwise
+pub const DEFAULT_HOME_ADVANTAGE: f64 = 0.02;
+
+/// How much more (or less) often each outcome happens at a park than at an
+/// average one
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ParkFactors {
+    pub single: f64,
+    pub double: f64,
+    pub triple: f64,
+    pub homerun: f64,
+    pub walk: f64,
+    pub strikeout: f64,
+}
+
+impl ParkFactors {
+    pub fn neutral() -> ParkFactors {
+        ParkFactors {
+            single: 1.0,
+            double: 1.0,
+            triple: 1.0,
+            homerun: 1.0,
+            walk: 1.0,
+            strikeout: 1.0,
+        }
+    }
+}
+
+/// Park factors keyed by the venue ID of the game feed
+#[derive(Clone, Debug, Default)]
+pub struct ParkTable {
+    parks: HashMap<String, ParkFactors>,
+}
+
+impl ParkTable {
+    pub fn bundled() -> ParkTable {
+        ParkTable::parse(BUNDLED).expect("bundled park factors")
+    }
+
+    /// Parse a CSV table with the columns venue_id, single, double,
+    /// triple, homerun, walk and strikeout (any others are ignored). Lines
+    /// starting with '#' are comments.
+    pub fn parse(text: &str) -> Result<ParkTable, String> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .filter(|(_, l)| !l.trim_start().starts_with('#'));
+        let header = csv_split(lines.next().map(|(_, l)| l).unwrap_or(""));
+        let column = |name: &str| {
+            header
+                .iter()
+                .position(|h| h == name)
+                .ok_or(format!("no \"{}\" column", name))
+        };
+        let id = column("venue_id")?;
+        let cols = [
+            column("single")?,
+            column("double")?,
+            column("triple")?,
+            column("homerun")?,
+            column("walk")?,
+            column("strikeout")?,
+        ];
+
+        let mut parks = HashMap::new();
+        for (n, line) in lines.filter(|(_, l)| !l.trim().is_empty()) {
+            let row = csv_split(line);
+            let bad = || format!("line {}: bad row", n + 1);
+            let mut f = [0.0; 6];
+            for (v, col) in f.iter_mut().zip(cols) {
+                let index: f64 = row.get(col).and_then(|s| s.trim().parse().ok()).ok_or_else(bad)?;
+                if !index.is_finite() || index <= 0.0 {
+                    return Err(bad());
+                }
+                *v = index / 100.0;
+            }
+            let venue_id = row.get(id).ok_or_else(bad)?.trim().to_string();
+            parks.insert(
+                venue_id,
+                ParkFactors {
+                    single: f[0],
+                    double: f[1],
+                    triple: f[2],
+                    homerun: f[3],
+                    walk: f[4],
+                    strikeout: f[5],
+                },
+            );
+        }
+        Ok(ParkTable { parks })
+    }
+
+    /// The bundled table, with the venues of a user-supplied table replacing
+    /// or adding to it
+    pub fn load(path: &Path) -> Result<ParkTable, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let user = ParkTable::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut table = ParkTable::bundled();
+        table.parks.extend(user.parks);
+        Ok(table)
+    }
+
+    /// Every venue's factors, in venue order
+    pub fn sorted(&self) -> BTreeMap<&str, &ParkFactors> {
+        self.parks.iter().map(|(id, f)| (id.as_str(), f)).collect()
+    }
+
+    /// Factors of a venue, neutral if it is unknown
+    pub fn get(&self, venue_id: Option<&str>) -> ParkFactors {
+        venue_id
+            .and_then(|id| self.parks.get(id))
+            .copied()
+            .unwrap_or_else(ParkFactors::neutral)
+    }
+}
+
+/// Where a game is played
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Venue {
+    pub park: ParkFactors,
+    /// Relative boost to the home batters' walks and hits, and cut in their
+    /// strikeouts. Visiting batters get the opposite.
+    pub home_advantage: f64,
+}
+
+impl Venue {
+    /// Neutral park, no home-field advantage
+    pub fn neutral() -> Venue {
+        Venue {
+            park: ParkFactors::neutral(),
+            home_advantage: 0.0,
+        }
+    }
+
+    /// Multiplier of the batting team's walks and hits
+    pub fn edge(&self, batting_home: bool) -> f64 {
+        if batting_home {
+            1.0 + self.home_advantage
+        } else {
+            1.0 / (1.0 + self.home_advantage)
+        }
+    }
+}
+
+impl Default for Venue {
+    fn default() -> Venue {
+        Venue::neutral()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundled() {
+        let table = ParkTable::bundled();
+        let coors = table.get(Some("19"));
+        assert_eq!(coors.homerun, 1.12);
+        assert!(coors.strikeout < 1.0);
+        assert!(table.get(Some("2680")).homerun < 1.0);
+        assert_eq!(table.get(Some("0")), ParkFactors::neutral());
+        assert_eq!(table.get(None), ParkFactors::neutral());
+    }
+
+    #[test]
+    fn test_parse() {
+        let text = "venue,venue_id,single,double,triple,homerun,walk,strikeout\n\
+                    \"Park, The\",42,100,110,120,90,100,105\n";
+        let park = ParkTable::parse(text).unwrap().get(Some("42"));
+        assert_eq!(park.double, 1.1);
+        assert_eq!(park.homerun, 0.9);
+        assert!(ParkTable::parse("venue_id,single\n1,100\n").is_err());
+        assert!(ParkTable::parse(&text.replace("105", "x")).is_err());
+        let commented = format!("# Made up\n{}# Another venue\n", text);
+        assert_eq!(ParkTable::parse(&commented).unwrap().get(Some("42")), park);
+    }
+
+    #[test]
+    fn test_edge() {
+        let venue = Venue {
+            home_advantage: 0.05,
+            ..Venue::neutral()
+        };
+        assert_eq!(venue.edge(true), 1.05);
+        assert!((venue.edge(true) * venue.edge(false) - 1.0).abs() < 1e-12);
+    }
+}
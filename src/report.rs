@@ -0,0 +1,521 @@
+//! Standalone HTML and Markdown reports of a day's predictions, for people
+//! who do not run the oracle themselves
+
+use std::fs;
+use std::path::Path;
+
+use chrono::NaiveDate;
+use chrono_tz::Tz;
+
+use crate::{
+    api::Oracle,
+    config::Config,
+    filter::Slate,
+    history, lookup,
+    mlbstats::Game,
+    odds,
+    output::{Format, Record},
+    simba::SimResult,
+};
+
+/// Runs at and above which the charts lump scores together
+const MAX_RUNS: usize = 12;
+
+/// Bar colors of the away and home teams
+const COLORS: [&str; 2] = ["#4e79a7", "#e15759"];
+
+const STYLE: &str = "body{font-family:sans-serif;max-width:60em;margin:2em auto;color:#222}\
+table{border-collapse:collapse;margin:1em 0}\
+th,td{padding:.3em .6em;border-bottom:1px solid #ddd;text-align:left}\
+td.num{text-align:right}\
+section{margin-top:2.5em}";
+
+/// A game of the slate, simulated unless it could not be
+struct Entry {
+    game: Game,
+    record: Record,
+    result: Option<SimResult>,
+}
+
+impl Entry {
+    fn new(oracle: &Oracle, game: Game) -> Entry {
+        let mut record = Record::new(&game);
+        let mut result = None;
+        if game.status == "Postponed" {
+            record.outcome = "postponed".to_string();
+        } else {
+            match simulate(oracle, &game, &mut record) {
+                Ok(r) => result = r,
+                Err(e) => {
+                    eprintln!("[WARNING] {}: {}", game.game_id, e);
+                    record.outcome = "error".to_string();
+                }
+            }
+        }
+        Entry {
+            game,
+            record,
+            result,
+        }
+    }
+
+    /// Time, teams, win probabilities, fair odds, expected runs and total,
+    /// and status, unescaped
+    fn cells(&self, tz: Option<Tz>) -> Vec<String> {
+        let game = &self.game;
+        let mut status = game.status.clone();
+        if let (Some(a), Some(h)) = (game.away_score, game.home_score) {
+            status.push_str(&format!(" {}-{}", a, h));
+        }
+        if !matches!(self.record.outcome.as_str(), "predicted" | "postponed") {
+            status.push_str(&format!(", {}", self.record.outcome.replace('_', " ")));
+        }
+        let mut cells = vec![
+            lookup::start(game, tz),
+            game.away_name.clone(),
+            game.home_name.clone(),
+        ];
+        match (&self.result, self.record.home_win_probability) {
+            (Some(r), Some(hwp)) => {
+                cells.extend([
+                    format!("{:.1}%", 100.0 * (1.0 - hwp)),
+                    format!("{:.1}%", 100.0 * hwp),
+                ]);
+                // Once the game is over, there are no odds or runs left to
+                // expect
+                if game.is_final() {
+                    cells.extend(std::iter::repeat_n(String::new(), 3));
+                } else {
+                    cells.extend([
+                        format!("{} / {}", odds::american(1.0 - hwp), odds::american(hwp)),
+                        format!("{:.1}-{:.1}", r.expected_away_runs, r.expected_home_runs),
+                        format!("{:.1}", r.expected_away_runs + r.expected_home_runs),
+                    ]);
+                }
+            }
+            _ => cells.extend(std::iter::repeat_n(String::new(), 5)),
+        }
+        cells.push(status);
+        cells
+    }
+
+    /// Starters, then lineups, side by side
+    fn lineup_rows(&self) -> Vec<[String; 3]> {
+        let r = &self.record;
+        let mut rows = vec![[
+            "Starter".to_string(),
+            r.away_starter.clone().unwrap_or_default(),
+            r.home_starter.clone().unwrap_or_default(),
+        ]];
+        for slot in 0..r.away_lineup.len().max(r.home_lineup.len()) {
+            let name = |lineup: &[String]| lineup.get(slot).cloned().unwrap_or_default();
+            rows.push([
+                (slot + 1).to_string(),
+                name(&r.away_lineup),
+                name(&r.home_lineup),
+            ]);
+        }
+        rows
+    }
+
+    /// Subtitle of the game's section
+    fn summary(&self, tz: Option<Tz>) -> String {
+        let mut summary = format!(
+            "Game {}, first pitch {}",
+            self.game.game_id,
+            lookup::start(&self.game, tz)
+        );
+        if let Some(r) = self.result.as_ref().filter(|_| !self.game.is_final()) {
+            let total = r.expected_away_runs + r.expected_home_runs;
+            let line = total.floor() + 0.5;
+            let over = r.probability(|s| (s.away + s.home) as f64 > line);
+            summary.push_str(&format!(
+                ". Over {:.1} runs: {:.1}%, {} simulations",
+                line,
+                100.0 * over,
+                r.iterations
+            ));
+        }
+        summary
+    }
+}
+
+const HEADERS: [&str; 9] = [
+    "Time",
+    "Away",
+    "Home",
+    "Away win",
+    "Home win",
+    "Fair odds",
+    "Expected runs",
+    "Total",
+    "Status",
+];
+
+/// Simulate a game, or None if its lineups are missing
+fn simulate(oracle: &Oracle, game: &Game, record: &mut Record) -> Result<Option<SimResult>, String> {
+    let prediction = oracle.predict(game)?;
+    let (Some([away, home]), Some(result)) = (&prediction.teams, prediction.result) else {
+        record.outcome = "missing_lineups".to_string();
+        return Ok(None);
+    };
+    record.teams(away, home);
+    record.simulated(&result);
+    Ok(Some(result))
+}
+
+/// Share of the simulated games in which each team scored 0, 1, ... runs,
+/// the last count standing for MAX_RUNS or more
+fn run_distribution(result: &SimResult) -> [Vec<f64>; 2] {
+    let mut counts = [vec![0usize; MAX_RUNS + 1], vec![0usize; MAX_RUNS + 1]];
+    for (score, n) in &result.scores {
+        counts[0][(score.away as usize).min(MAX_RUNS)] += n;
+        counts[1][(score.home as usize).min(MAX_RUNS)] += n;
+    }
+    let total: usize = result.scores.iter().map(|(_, n)| n).sum();
+    counts.map(|c| c.iter().map(|&k| k as f64 / total.max(1) as f64).collect())
+}
+
+/// Bar chart of both teams' run distributions
+fn chart(names: [&str; 2], shares: &[Vec<f64>; 2]) -> String {
+    let (width, height) = (460.0, 180.0);
+    let (pad, top, bottom) = (10.0, 26.0, 20.0);
+    let plot = height - top - bottom;
+    let slot = (width - 2.0 * pad) / shares[0].len() as f64;
+    let highest = shares.iter().flatten().fold(0.01f64, |a, &b| a.max(b));
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" \
+         viewBox=\"0 0 {} {}\" font-family=\"sans-serif\" font-size=\"11\">",
+        width, height, width, height
+    );
+    svg.push_str(&format!(
+        "<title>Runs scored by {} and {}</title>",
+        escape(names[0]),
+        escape(names[1])
+    ));
+    for (idx, name) in names.iter().enumerate() {
+        let x = pad + 160.0 * idx as f64;
+        svg.push_str(&format!(
+            "<rect x=\"{:.1}\" y=\"6\" width=\"10\" height=\"10\" fill=\"{}\"/>\
+             <text x=\"{:.1}\" y=\"15\">{}</text>",
+            x,
+            COLORS[idx],
+            x + 14.0,
+            escape(name)
+        ));
+    }
+    for (runs, (away, home)) in shares[0].iter().zip(&shares[1]).enumerate() {
+        let x = pad + slot * runs as f64;
+        for (idx, share) in [away, home].into_iter().enumerate() {
+            let h = plot * share / highest;
+            svg.push_str(&format!(
+                "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"{}\">\
+                 <title>{}: {} runs, {:.1}%</title></rect>",
+                x + slot * (0.1 + 0.4 * idx as f64),
+                top + plot - h,
+                slot * 0.4,
+                h,
+                COLORS[idx],
+                escape(names[idx]),
+                runs,
+                100.0 * share
+            ));
+        }
+        let label = if runs == MAX_RUNS {
+            format!("{}+", runs)
+        } else {
+            runs.to_string()
+        };
+        svg.push_str(&format!(
+            "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{}</text>",
+            x + slot / 2.0,
+            height - 6.0,
+            label
+        ));
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+fn entry_chart(entry: &Entry) -> Option<String> {
+    let result = entry.result.as_ref()?;
+    let names = [
+        entry
+            .record
+            .away_team
+            .as_deref()
+            .unwrap_or(&entry.game.away_name),
+        entry
+            .record
+            .home_team
+            .as_deref()
+            .unwrap_or(&entry.game.home_name),
+    ];
+    Some(chart(names, &run_distribution(result)))
+}
+
+/// Escape text for HTML, and for SVG
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Escape text for a Markdown table cell
+fn cell(s: &str) -> String {
+    s.replace('|', "\\|")
+}
+
+fn title(date: NaiveDate) -> String {
+    format!("MLB predictions for {}", date.format("%A, %B %-d, %Y"))
+}
+
+fn byline(cfg: &Config) -> String {
+    format!(
+        "Generated {} by mlb-oracle {}, from {} simulations of each game.",
+        chrono::Utc::now().format("%F %H:%M UTC"),
+        history::model_version(cfg),
+        cfg.iterations
+    )
+}
+
+fn html(cfg: &Config, date: NaiveDate, entries: &[Entry]) -> String {
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>{}</title>\n<style>{}</style>\n</head>\n<body>\n<h1>{}</h1>\n<p>{}</p>\n",
+        title(date),
+        STYLE,
+        title(date),
+        escape(&byline(cfg))
+    );
+    out.push_str("<table>\n<tr>");
+    for header in HEADERS {
+        out.push_str(&format!("<th>{}</th>", header));
+    }
+    out.push_str("</tr>\n");
+    for entry in entries {
+        out.push_str("<tr>");
+        for (idx, text) in entry.cells(cfg.tz).iter().enumerate() {
+            // Probabilities, odds and runs
+            let class = if (3..8).contains(&idx) {
+                " class=\"num\""
+            } else {
+                ""
+            };
+            out.push_str(&format!("<td{}>{}</td>", class, escape(text)));
+        }
+        out.push_str("</tr>\n");
+    }
+    out.push_str("</table>\n");
+
+    for entry in entries.iter().filter(|e| e.record.away_team.is_some()) {
+        out.push_str(&format!(
+            "<section>\n<h2>{} @ {}</h2>\n<p>{}</p>\n<table>\n",
+            escape(&entry.game.away_name),
+            escape(&entry.game.home_name),
+            escape(&entry.summary(cfg.tz))
+        ));
+        let teams = [
+            entry.record.away_team.clone().unwrap_or_default(),
+            entry.record.home_team.clone().unwrap_or_default(),
+        ];
+        out.push_str(&format!(
+            "<tr><th></th><th>{}</th><th>{}</th></tr>\n",
+            escape(&teams[0]),
+            escape(&teams[1])
+        ));
+        for [label, away, home] in entry.lineup_rows() {
+            out.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                label,
+                escape(&away),
+                escape(&home)
+            ));
+        }
+        out.push_str("</table>\n");
+        if let Some(svg) = entry_chart(entry) {
+            out.push_str(&svg);
+            out.push('\n');
+        }
+        out.push_str("</section>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+fn markdown(cfg: &Config, date: NaiveDate, entries: &[Entry]) -> String {
+    let mut out = format!("# {}\n\n{}\n\n", title(date), byline(cfg));
+    out.push_str(&format!("| {} |\n", HEADERS.join(" | ")));
+    out.push_str(&format!("|{}\n", "---|".repeat(HEADERS.len())));
+    for entry in entries {
+        let cells: Vec<String> = entry.cells(cfg.tz).iter().map(|c| cell(c)).collect();
+        out.push_str(&format!("| {} |\n", cells.join(" | ")));
+    }
+
+    for entry in entries.iter().filter(|e| e.record.away_team.is_some()) {
+        out.push_str(&format!(
+            "\n## {} @ {}\n\n{}\n\n",
+            entry.game.away_name,
+            entry.game.home_name,
+            entry.summary(cfg.tz)
+        ));
+        out.push_str(&format!(
+            "| | {} | {} |\n|---|---|---|\n",
+            cell(entry.record.away_team.as_deref().unwrap_or_default()),
+            cell(entry.record.home_team.as_deref().unwrap_or_default())
+        ));
+        for [label, away, home] in entry.lineup_rows() {
+            out.push_str(&format!(
+                "| {} | {} | {} |\n",
+                label,
+                cell(&away),
+                cell(&home)
+            ));
+        }
+        // Inline HTML, which most Markdown renderers pass through
+        if let Some(svg) = entry_chart(entry) {
+            out.push_str(&format!("\n{}\n", svg));
+        }
+    }
+    out
+}
+
+pub fn report(cfg: &Config, output: Option<&Path>) -> Result<(), String> {
+    let date = cfg.naive_date()?;
+    let oracle = Oracle::with_config(cfg.clone());
+    let entries: Vec<Entry> = Slate::new(cfg)?
+        .games(cfg)?
+        .into_iter()
+        .map(|game| Entry::new(&oracle, game))
+        .collect();
+    let document = match cfg.format {
+        Format::Markdown => markdown(cfg, date, &entries),
+        _ => html(cfg, date, &entries),
+    };
+    match output {
+        Some(path) => fs::write(path, document).map_err(|e| format!("{}: {}", path.display(), e)),
+        None => {
+            print!("{}", document);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fixtures, simba::Score};
+
+    fn entry() -> Entry {
+        let game = Game {
+            start: "2024-07-01T23:05:00Z".parse().ok(),
+            ..fixtures::game("Chicago Cubs", "Texas Rangers")
+        };
+        let result = SimResult {
+            home_win_probability: Some(0.6),
+            expected_away_runs: 4.1,
+            expected_home_runs: 4.6,
+            iterations: 4,
+            seed: 1,
+            scores: vec![
+                (Score { away: 3, home: 5 }, 2),
+                (Score { away: 14, home: 2 }, 1),
+                (Score { away: 4, home: 5 }, 1),
+            ],
+        };
+        let mut record = Record::new(&game);
+        record.away_team = Some("CHC".to_string());
+        record.home_team = Some("TEX".to_string());
+        record.away_starter = Some("J. Steele".to_string());
+        record.home_starter = Some("N. Eovaldi".to_string());
+        record.away_lineup = vec!["I. Happ".to_string(), "S. Suzuki".to_string()];
+        record.home_lineup = vec!["M. Semien".to_string(), "C. Seager".to_string()];
+        record.simulated(&result);
+        Entry {
+            game,
+            record,
+            result: Some(result),
+        }
+    }
+
+    #[test]
+    fn report_cells() {
+        let tz = Some(chrono_tz::America::Chicago);
+        assert_eq!(
+            entry().cells(tz),
+            [
+                "18:05 CDT",
+                "Chicago Cubs",
+                "Texas Rangers",
+                "40.0%",
+                "60.0%",
+                "+150 / -150",
+                "4.1-4.6",
+                "8.7",
+                "Scheduled"
+            ]
+        );
+        let mut missing = entry();
+        missing.result = None;
+        missing.record.outcome = "missing_lineups".to_string();
+        let cells = missing.cells(tz);
+        assert_eq!(cells[3], "");
+        assert_eq!(cells[8], "Scheduled, missing lineups");
+        let mut over = entry();
+        over.game.status = "Final".to_string();
+        over.game.abstract_status = "Final".to_string();
+        let cells = over.cells(tz);
+        assert_eq!(cells[4], "60.0%");
+        assert_eq!(cells[5..8], ["", "", ""]);
+        assert!(!over.summary(tz).contains("Over"));
+        assert_eq!(entry().lineup_rows()[2], ["2", "S. Suzuki", "C. Seager"]);
+    }
+
+    #[test]
+    fn report_chart() {
+        let shares = run_distribution(entry().result.as_ref().unwrap());
+        assert_eq!(shares[0].len(), MAX_RUNS + 1);
+        assert_eq!(shares[0][3], 0.5);
+        assert_eq!(shares[0][MAX_RUNS], 0.25);
+        assert_eq!(shares[1][5], 0.75);
+        let svg = chart(["CHC", "A&M"], &shares);
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(svg.ends_with("</svg>"));
+        assert!(svg.contains("A&amp;M"));
+        assert!(svg.contains("<title>CHC: 3 runs, 50.0%</title>"));
+        assert!(svg.contains(">12+</text>"));
+        assert_eq!(svg.matches("<rect").count(), 2 + 2 * (MAX_RUNS + 1));
+        assert!(
+            !svg.contains('\n'),
+            "blank lines would end a Markdown HTML block"
+        );
+    }
+
+    #[test]
+    fn report_documents() {
+        let cfg = Config::default();
+        let date = NaiveDate::from_ymd_opt(2024, 7, 1).unwrap();
+        let entries = [entry()];
+
+        let page = html(&cfg, date, &entries);
+        assert!(page.starts_with("<!DOCTYPE html>"));
+        assert!(page.contains("<h1>MLB predictions for Monday, July 1, 2024</h1>"));
+        assert!(page.contains("<td class=\"num\">+150 / -150</td>"));
+        assert!(page.contains("<h2>Chicago Cubs @ Texas Rangers</h2>"));
+        assert!(page.contains("<tr><td>Starter</td><td>J. Steele</td><td>N. Eovaldi</td></tr>"));
+        assert!(page.contains("Over 8.5 runs: 50.0%, 4 simulations"));
+        assert_eq!(page.matches("<svg").count(), 1);
+
+        let md = markdown(&cfg, date, &entries);
+        assert!(md.starts_with("# MLB predictions for Monday, July 1, 2024\n"));
+        assert!(md.contains("\n| Time | Away | Home |"));
+        assert!(md.contains("|---|---|---|---|---|---|---|---|---|\n"));
+        assert!(md.contains("| 40.0% | 60.0% | +150 / -150 | 4.1-4.6 | 8.7 | Scheduled |\n"));
+        assert!(md.contains("\n## Chicago Cubs @ Texas Rangers\n"));
+        assert!(md.contains("| 1 | I. Happ | M. Semien |\n"));
+        assert!(md.contains("\n<svg "));
+        assert_eq!(cell("a|b"), "a\\|b");
+    }
+}
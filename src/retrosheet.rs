@@ -0,0 +1,859 @@
+// Parser for Retrosheet event files (.EVN/.EVA) and rosters (.ROS), see
+// https://www.retrosheet.org/eventfile.htm for the format.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{
+    mlbstats::{PitcherStats, Team},
+    simba::{GameState, Outcome, Play, Score},
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Player {
+    pub id: String,
+    pub last_name: String,
+    pub first_name: String,
+    pub bats: String,
+    pub throws: String,
+    pub team: String,
+    pub position: String,
+}
+
+/// A player entering the game, either in the starting lineup or as a sub
+#[derive(Clone, Debug, PartialEq)]
+pub struct Appearance {
+    pub player: String,
+    pub name: String,
+    /// 0 for the visiting team, 1 for the home team
+    pub team: i32,
+    /// 1-9, or 0 for a pitcher who does not bat
+    pub batting_order: i32,
+    pub position: i32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Base {
+    Batter,
+    First,
+    Second,
+    Third,
+    Home,
+}
+
+impl Base {
+    fn parse(c: char) -> Option<Base> {
+        match c {
+            'B' => Some(Base::Batter),
+            '1' => Some(Base::First),
+            '2' => Some(Base::Second),
+            '3' => Some(Base::Third),
+            'H' => Some(Base::Home),
+            _ => None,
+        }
+    }
+
+    /// Index in a `[bool; 3]` base state
+    fn index(self) -> Option<usize> {
+        match self {
+            Base::First => Some(0),
+            Base::Second => Some(1),
+            Base::Third => Some(2),
+            _ => None,
+        }
+    }
+
+    fn from_index(idx: usize) -> Base {
+        [Base::First, Base::Second, Base::Third][idx]
+    }
+
+    fn next(self) -> Base {
+        match self {
+            Base::Batter => Base::First,
+            Base::First => Base::Second,
+            Base::Second => Base::Third,
+            _ => Base::Home,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Advance {
+    pub from: Base,
+    pub to: Base,
+    /// Put out while advancing
+    pub out: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventKind {
+    Single,
+    Double,
+    Triple,
+    HomeRun,
+    Walk,
+    IntentionalWalk,
+    HitByPitch,
+    Interference,
+    Strikeout,
+    /// Ball in play fielded for an out, e.g. 63 or 8
+    Out,
+    FieldersChoice,
+    /// Batter reaches on an error
+    Error,
+    /// Stolen bases, caught stealing, wild pitches and other plays that
+    /// do not end the plate appearance
+    Baserunning,
+    NoPlay,
+}
+
+impl EventKind {
+    fn ends_plate_appearance(self) -> bool {
+        !matches!(self, EventKind::Baserunning | EventKind::NoPlay)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlayEvent {
+    pub inning: i32,
+    /// 0 for the visiting team, 1 for the home team
+    pub team: i32,
+    pub batter: String,
+    pub count: String,
+    pub pitches: String,
+    /// The event as written in the file, e.g. "S7/L.1-3;B-2"
+    pub event: String,
+    pub kind: EventKind,
+    pub modifiers: Vec<String>,
+    pub advances: Vec<Advance>,
+    /// Bases of runners put out in the basic play itself, e.g. (1) in 64(1)3
+    pub forced: Vec<Base>,
+    /// Bases of runners moved by baserunning events, e.g. SB2 or CS3(25)
+    pub steals: Vec<Advance>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    Play(PlayEvent),
+    Sub(Appearance),
+    Comment(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Game {
+    pub id: String,
+    pub info: Vec<(String, String)>,
+    pub starters: Vec<Appearance>,
+    pub events: Vec<Event>,
+}
+
+impl Game {
+    pub fn info(&self, key: &str) -> Option<&str> {
+        self.info
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn visteam(&self) -> &str {
+        self.info("visteam").unwrap_or("")
+    }
+
+    pub fn hometeam(&self) -> &str {
+        self.info("hometeam").unwrap_or("")
+    }
+
+    /// Starting batting order of a team, by batting order slot
+    pub fn lineup(&self, team: i32) -> Vec<&Appearance> {
+        let mut lineup: Vec<_> = self
+            .starters
+            .iter()
+            .filter(|a| a.team == team && a.batting_order > 0)
+            .collect();
+        lineup.sort_by_key(|a| a.batting_order);
+        lineup
+    }
+
+    pub fn plays(&self) -> impl Iterator<Item = &PlayEvent> {
+        self.events.iter().filter_map(|e| match e {
+            Event::Play(p) => Some(p),
+            _ => None,
+        })
+    }
+}
+
+/// Split a line on commas, honoring double-quoted fields
+fn fields(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    for c in line.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+fn parse_int(s: &str, line: usize) -> Result<i32, String> {
+    s.trim()
+        .parse()
+        .map_err(|_| format!("line {}: invalid number \"{}\"", line, s))
+}
+
+pub fn parse_roster(text: &str) -> Result<Vec<Player>, String> {
+    let mut players = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let f = fields(line);
+        if f.len() < 7 {
+            return Err(format!("line {}: expected 7 roster fields", n + 1));
+        }
+        players.push(Player {
+            id: f[0].clone(),
+            last_name: f[1].clone(),
+            first_name: f[2].clone(),
+            bats: f[3].clone(),
+            throws: f[4].clone(),
+            team: f[5].clone(),
+            position: f[6].clone(),
+        });
+    }
+    Ok(players)
+}
+
+fn parse_appearance(f: &[String], line: usize) -> Result<Appearance, String> {
+    if f.len() < 6 {
+        return Err(format!("line {}: expected 6 fields for {}", line, f[0]));
+    }
+    Ok(Appearance {
+        player: f[1].clone(),
+        name: f[2].clone(),
+        team: parse_int(&f[3], line)?,
+        batting_order: parse_int(&f[4], line)?,
+        position: parse_int(&f[5], line)?,
+    })
+}
+
+pub fn parse_events(text: &str) -> Result<Vec<Game>, String> {
+    let mut games: Vec<Game> = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let n = n + 1;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let f = fields(line);
+        if f[0] == "id" {
+            games.push(Game {
+                id: f.get(1).cloned().unwrap_or_default(),
+                info: Vec::new(),
+                starters: Vec::new(),
+                events: Vec::new(),
+            });
+            continue;
+        }
+        let Some(game) = games.last_mut() else {
+            return Err(format!("line {}: record before the first game id", n));
+        };
+        match f[0].as_str() {
+            "info" if f.len() >= 3 => game.info.push((f[1].clone(), f[2].clone())),
+            "start" => game.starters.push(parse_appearance(&f, n)?),
+            "sub" => game.events.push(Event::Sub(parse_appearance(&f, n)?)),
+            "com" => game
+                .events
+                .push(Event::Comment(f.get(1).cloned().unwrap_or_default())),
+            "play" => {
+                if f.len() < 7 {
+                    return Err(format!("line {}: expected 7 fields for play", n));
+                }
+                let play = parse_play(&f[6]).map_err(|e| format!("line {}: {}", n, e))?;
+                game.events.push(Event::Play(PlayEvent {
+                    inning: parse_int(&f[1], n)?,
+                    team: parse_int(&f[2], n)?,
+                    batter: f[3].clone(),
+                    count: f[4].clone(),
+                    pitches: f[5].clone(),
+                    ..play
+                }));
+            }
+            // version, data, badj, padj, ... carry nothing we replay
+            _ => (),
+        }
+    }
+    Ok(games)
+}
+
+pub fn read_events(path: &Path) -> Result<Vec<Game>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    parse_events(&text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+pub fn read_roster(path: &Path) -> Result<Vec<Player>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    parse_roster(&text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Whether parenthesized fielding credits such as (8E5) include an error,
+/// which makes an apparent out safe
+fn has_error(s: &str) -> bool {
+    s.contains('E')
+}
+
+/// Parse the event field of a play record. Only the event-derived fields
+/// of the returned play are filled.
+fn parse_play(event: &str) -> Result<PlayEvent, String> {
+    let (desc, advs) = match event.split_once('.') {
+        Some((d, a)) => (d, Some(a)),
+        None => (event, None),
+    };
+    let mut parts = desc.split('/');
+    let basic = parts.next().unwrap_or("");
+    let modifiers = parts.map(|m| m.to_string()).collect();
+
+    // Strikeouts and walks can be followed by a baserunning event: K+SB2
+    let (basic, extra) = match basic.split_once('+') {
+        Some((b, e)) => (b, Some(e)),
+        None => (basic, None),
+    };
+
+    let kind = classify(basic).ok_or(format!("unknown event \"{}\"", event))?;
+
+    let mut forced = Vec::new();
+    if kind == EventKind::Out || kind == EventKind::Strikeout {
+        // Runners put out in the basic play are named in parentheses
+        let mut rest = basic;
+        while let Some(open) = rest.find('(') {
+            let close = rest[open..].find(')').ok_or("unclosed parenthesis")? + open;
+            if let Some(base) = rest[open + 1..close].chars().next().and_then(Base::parse) {
+                forced.push(base);
+            }
+            rest = &rest[close + 1..];
+        }
+    }
+
+    let mut steals = Vec::new();
+    let running = if kind == EventKind::Baserunning {
+        Some(basic)
+    } else {
+        extra
+    };
+    if let Some(running) = running {
+        for ev in running.split(';') {
+            steals.extend(parse_baserunning(ev));
+        }
+    }
+
+    let mut advances = Vec::new();
+    if let Some(advs) = advs {
+        for adv in advs.split(';').filter(|a| !a.is_empty()) {
+            let mut chars = adv.chars();
+            let from = chars.next().and_then(Base::parse);
+            let sep = chars.next().filter(|c| matches!(c, '-' | 'X'));
+            let to = chars.next().and_then(Base::parse);
+            let (Some(from), Some(sep), Some(to)) = (from, sep, to) else {
+                return Err(format!("invalid advance \"{}\"", adv));
+            };
+            let out = sep == 'X' && !has_error(chars.as_str());
+            advances.push(Advance { from, to, out });
+        }
+    }
+
+    Ok(PlayEvent {
+        inning: 0,
+        team: 0,
+        batter: String::new(),
+        count: String::new(),
+        pitches: String::new(),
+        event: event.to_string(),
+        kind,
+        modifiers,
+        advances,
+        forced,
+        steals,
+    })
+}
+
+fn classify(basic: &str) -> Option<EventKind> {
+    let starts_digit = basic.starts_with(|c: char| c.is_ascii_digit());
+    let kind = if starts_digit {
+        EventKind::Out
+    } else if basic.starts_with("HP") {
+        EventKind::HitByPitch
+    } else if basic.starts_with('H') {
+        EventKind::HomeRun
+    } else if basic.starts_with("SB")
+        || basic.starts_with("CS")
+        || basic.starts_with("PO")
+        || basic.starts_with("WP")
+        || basic.starts_with("PB")
+        || basic.starts_with("BK")
+        || basic.starts_with("DI")
+        || basic.starts_with("OA")
+        || basic.starts_with("FLE")
+    {
+        EventKind::Baserunning
+    } else if basic.starts_with('S') {
+        EventKind::Single
+    } else if basic.starts_with('D') {
+        EventKind::Double
+    } else if basic.starts_with('T') {
+        EventKind::Triple
+    } else if basic.starts_with("IW") || basic == "I" {
+        EventKind::IntentionalWalk
+    } else if basic.starts_with('W') {
+        EventKind::Walk
+    } else if basic.starts_with('K') {
+        EventKind::Strikeout
+    } else if basic.starts_with("FC") {
+        EventKind::FieldersChoice
+    } else if basic.starts_with('E') {
+        EventKind::Error
+    } else if basic.starts_with('C') {
+        EventKind::Interference
+    } else if basic.starts_with("NP") {
+        EventKind::NoPlay
+    } else {
+        return None;
+    };
+    Some(kind)
+}
+
+/// Runner movements implied by SB2, CS3(25), PO1(13), POCS2(14) and the like
+fn parse_baserunning(ev: &str) -> Option<Advance> {
+    let credits = ev.find('(').map(|i| &ev[i..]).unwrap_or("");
+    let (base, out) = if let Some(b) = ev.strip_prefix("SB") {
+        (b, false)
+    } else if let Some(b) = ev.strip_prefix("POCS") {
+        (b, !has_error(credits))
+    } else if let Some(b) = ev.strip_prefix("CS") {
+        (b, !has_error(credits))
+    } else if let Some(b) = ev.strip_prefix("PO") {
+        // Picked off at the base the runner stands on
+        let from = b.chars().next().and_then(Base::parse)?;
+        return Some(Advance {
+            from,
+            to: from,
+            out: !has_error(credits),
+        });
+    } else {
+        return None;
+    };
+    let to = base.chars().next().and_then(Base::parse)?;
+    let from = match to {
+        Base::Second => Base::First,
+        Base::Third => Base::Second,
+        Base::Home => Base::Third,
+        _ => return None,
+    };
+    Some(Advance { from, to, out })
+}
+
+impl PlayEvent {
+    /// Where the batter ends up if the advances do not say otherwise.
+    /// None means that the batter is out.
+    fn batter_default(&self) -> Option<Base> {
+        match self.kind {
+            EventKind::Single => Some(Base::First),
+            EventKind::Double => Some(Base::Second),
+            EventKind::Triple => Some(Base::Third),
+            EventKind::HomeRun => Some(Base::Home),
+            EventKind::Walk
+            | EventKind::IntentionalWalk
+            | EventKind::HitByPitch
+            | EventKind::Interference
+            | EventKind::FieldersChoice
+            | EventKind::Error => Some(Base::First),
+            // 64(1) is a force out on which the batter reaches first
+            EventKind::Out => {
+                let basic = self.event.split(['/', '.']).next().unwrap_or("");
+                if basic.ends_with(')') && !self.forced.contains(&Base::Batter) {
+                    Some(Base::First)
+                } else {
+                    None
+                }
+            }
+            EventKind::Strikeout | EventKind::Baserunning | EventKind::NoPlay => None,
+        }
+    }
+
+    /// Apply the play to a base state, returning the outs and runs it
+    /// produced, as recorded in the file.
+    pub fn apply(&self, bases: &mut [bool; 3]) -> (i32, i32) {
+        let mut outs = 0;
+        let mut runs = 0;
+        let mut after = [false; 3];
+        let explicit = |base: Base| {
+            self.advances
+                .iter()
+                .chain(self.steals.iter())
+                .find(|a| a.from == base)
+        };
+
+        let land = |to: Base, out: bool, after: &mut [bool; 3], outs: &mut i32, runs: &mut i32| {
+            if out {
+                *outs += 1;
+            } else if to == Base::Home {
+                *runs += 1;
+            } else if let Some(idx) = to.index() {
+                after[idx] = true;
+            }
+        };
+
+        // Batter first, so that forced runners know where the batter went
+        let batter = match explicit(Base::Batter) {
+            Some(adv) => Some((adv.to, adv.out)),
+            None if self.kind.ends_plate_appearance() => match self.batter_default() {
+                Some(to) => Some((to, false)),
+                None => Some((Base::Batter, true)),
+            },
+            None => None,
+        };
+        let forcing = matches!(
+            self.kind,
+            EventKind::Walk
+                | EventKind::IntentionalWalk
+                | EventKind::HitByPitch
+                | EventKind::Interference
+        );
+
+        // Runners, lead runner first
+        let mut occupied_next = batter.is_some_and(|(to, out)| !out && to == Base::First);
+        for idx in 0..3 {
+            if !bases[idx] {
+                occupied_next = false;
+                continue;
+            }
+            let base = Base::from_index(idx);
+            if let Some(adv) = explicit(base) {
+                land(adv.to, adv.out, &mut after, &mut outs, &mut runs);
+                occupied_next = false;
+            } else if self.forced.contains(&base) {
+                outs += 1;
+                occupied_next = false;
+            } else if self.kind == EventKind::HomeRun {
+                land(Base::Home, false, &mut after, &mut outs, &mut runs);
+            } else if forcing && occupied_next {
+                // Forced along by the batter reaching first
+                land(base.next(), false, &mut after, &mut outs, &mut runs);
+            } else {
+                after[idx] = true;
+                occupied_next = false;
+            }
+        }
+        if let Some((to, out)) = batter {
+            if out && to == Base::Batter {
+                outs += 1;
+            } else {
+                land(to, out, &mut after, &mut outs, &mut runs);
+            }
+        }
+
+        *bases = after;
+        (outs, runs)
+    }
+
+    /// The simulator outcome closest to this play, if it ends the plate
+    /// appearance
+    pub fn outcome(&self) -> Option<Outcome> {
+        let fly = self
+            .modifiers
+            .iter()
+            .any(|m| m.starts_with('F') || m.starts_with('L') || m.starts_with('P') || m == "SF");
+        match self.kind {
+            EventKind::Single | EventKind::Error => Some(Outcome::Single),
+            EventKind::Double => Some(Outcome::Double),
+            EventKind::Triple => Some(Outcome::Triple),
+            EventKind::HomeRun => Some(Outcome::HomeRun),
+            EventKind::Walk
+            | EventKind::IntentionalWalk
+            | EventKind::HitByPitch
+            | EventKind::Interference => Some(Outcome::Walk),
+            EventKind::Strikeout => Some(Outcome::StrikeOut),
+            EventKind::Out if fly => Some(Outcome::FlyOut),
+            EventKind::Out | EventKind::FieldersChoice => Some(Outcome::TagOut),
+            EventKind::Baserunning | EventKind::NoPlay => None,
+        }
+    }
+}
+
+/// A play after which the replayed and recorded states disagree
+#[derive(Debug)]
+pub struct Discrepancy {
+    pub inning: i32,
+    pub team: i32,
+    pub event: String,
+    /// Outs and runs produced by the play
+    pub replayed: (i32, i32),
+    pub recorded: (i32, i32),
+}
+
+#[derive(Debug)]
+pub struct Replay {
+    pub game_id: String,
+    pub recorded: Score,
+    pub replayed: Score,
+    pub discrepancies: Vec<Discrepancy>,
+}
+
+/// Replay a game through `GameState::transition`. Before each play the
+/// simulator is synced to the recorded state, so that every discrepancy
+/// is attributed to the one play that caused it.
+pub fn replay(game: &Game) -> Replay {
+    // The transition function never looks at player stats
+    let team = |name: &str| Team {
+        name: name.to_string(),
+        starting_pitcher: PitcherStats {
+            id: String::new(),
+            name: String::new(),
+            hand: String::new(),
+            batters_faced: 0,
+            bases_on_balls: 0,
+            hits: 0,
+            doubles: 0,
+            triples: 0,
+            homeruns: 0,
+            strikeouts: 0,
+        },
+        batters: Vec::new(),
+    };
+    let (vis, home) = (team(game.visteam()), team(game.hometeam()));
+    let mut gs = GameState::new(&vis, &home);
+
+    let mut recorded = Score { away: 0, home: 0 };
+    let mut replayed = Score { away: 0, home: 0 };
+    let mut bases = [false; 3];
+    let mut outs = 0;
+    let mut half = (1, 0);
+    let mut discrepancies = Vec::new();
+
+    for play in game.plays() {
+        if (play.inning, play.team) != half {
+            half = (play.inning, play.team);
+            bases = [false; 3];
+            outs = 0;
+        }
+
+        gs.inning = play.inning;
+        gs.team_idx = play.team;
+        gs.outs = outs;
+        gs.bases = bases;
+        gs.score = recorded.clone();
+        gs.live = true;
+
+        let pre_outs = outs;
+        let (rec_outs, rec_runs) = play.apply(&mut bases);
+        outs += rec_outs;
+
+        let rep = match play.outcome() {
+            Some(outcome) => {
+                let runs = gs.transition(&Play {
+                    team: play.team,
+                    runs: 0,
+                    outcome,
+                });
+                // The simulator resets the outs when it moves to the next half
+                let rep_outs = if gs.team_idx != play.team {
+                    3 - pre_outs
+                } else {
+                    gs.outs - pre_outs
+                };
+                (rep_outs, runs)
+            }
+            None => (0, 0),
+        };
+
+        if play.team == 0 {
+            recorded.away += rec_runs;
+            replayed.away += rep.1;
+        } else {
+            recorded.home += rec_runs;
+            replayed.home += rep.1;
+        }
+
+        if rep != (rec_outs, rec_runs) {
+            discrepancies.push(Discrepancy {
+                inning: play.inning,
+                team: play.team,
+                event: play.event.clone(),
+                replayed: rep,
+                recorded: (rec_outs, rec_runs),
+            });
+        }
+    }
+
+    Replay {
+        game_id: game.id.clone(),
+        recorded,
+        replayed,
+        discrepancies,
+    }
+}
+
+impl Replay {
+    pub fn print(&self, game: &Game) {
+        println!(
+            "{} {} @ {}: recorded {}-{}, replayed {}-{}, {} discrepancies",
+            self.game_id,
+            game.visteam(),
+            game.hometeam(),
+            self.recorded.away,
+            self.recorded.home,
+            self.replayed.away,
+            self.replayed.home,
+            self.discrepancies.len()
+        );
+        for d in &self.discrepancies {
+            println!(
+                "  {} {:>2}  {:<24} replayed {} outs, {} runs; recorded {} outs, {} runs",
+                if d.team == 0 { "top" } else { "bot" },
+                d.inning,
+                d.event,
+                d.replayed.0,
+                d.replayed.1,
+                d.recorded.0,
+                d.recorded.1
+            );
+        }
+    }
+}
+
+/// Replay every game of the given event files and print the discrepancies
+pub fn report(paths: &[PathBuf]) -> Result<(), String> {
+    for path in paths {
+        for game in read_events(path)? {
+            replay(&game).print(&game);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EVENTS: &str = include_str!("../data/retrosheet/2023HOM.EVA");
+    const ROSTER: &str = include_str!("../data/retrosheet/HOM2023.ROS");
+
+    fn apply(event: &str, mut bases: [bool; 3]) -> ([bool; 3], (i32, i32)) {
+        let play = parse_play(event).unwrap();
+        let res = play.apply(&mut bases);
+        (bases, res)
+    }
+
+    #[test]
+    fn test_fields() {
+        assert_eq!(
+            fields(r#"start,goodn001,"Goodrum, Niko",0,1,4"#),
+            vec!["start", "goodn001", "Goodrum, Niko", "0", "1", "4"]
+        );
+    }
+
+    #[test]
+    fn test_parse_roster() {
+        let players = parse_roster(ROSTER).unwrap();
+        assert_eq!(players.len(), 11);
+        assert_eq!(players[0].id, "homb001");
+        assert_eq!(players[0].first_name, "Al");
+        assert_eq!(players[10].throws, "L");
+        assert!(parse_roster("foo,bar").is_err());
+    }
+
+    #[test]
+    fn test_parse_events() {
+        let games = parse_events(EVENTS).unwrap();
+        assert_eq!(games.len(), 1);
+        let game = &games[0];
+        assert_eq!(game.id, "HOM202304010");
+        assert_eq!(game.visteam(), "VIS");
+        assert_eq!(game.hometeam(), "HOM");
+        assert_eq!(game.info("date"), Some("2023/04/01"));
+        assert_eq!(game.lineup(0).len(), 9);
+        assert_eq!(game.lineup(1)[3].player, "homb004");
+        assert_eq!(game.plays().count(), 86);
+        assert!(game
+            .events
+            .iter()
+            .any(|e| matches!(e, Event::Sub(a) if a.player == "homrp01")));
+        assert!(parse_events("play,1,0,x,00,,S8").is_err());
+        assert!(parse_events("id,X\nplay,1,0,x,00,,ZZZ").is_err());
+    }
+
+    #[test]
+    fn test_classify() {
+        let kind = |e: &str| parse_play(e).unwrap().kind;
+        assert_eq!(kind("S8/L"), EventKind::Single);
+        assert_eq!(kind("DGR/9"), EventKind::Double);
+        assert_eq!(kind("DI.1-2"), EventKind::Baserunning);
+        assert_eq!(kind("H/9"), EventKind::HomeRun);
+        assert_eq!(kind("HP"), EventKind::HitByPitch);
+        assert_eq!(kind("W+WP.1-2"), EventKind::Walk);
+        assert_eq!(kind("IW"), EventKind::IntentionalWalk);
+        assert_eq!(kind("K23"), EventKind::Strikeout);
+        assert_eq!(kind("64(1)3/GDP"), EventKind::Out);
+        assert_eq!(kind("FC5/G.2X3(54)"), EventKind::FieldersChoice);
+        assert_eq!(kind("E6/G"), EventKind::Error);
+        assert_eq!(kind("C/E2"), EventKind::Interference);
+        assert_eq!(kind("CS2(24)"), EventKind::Baserunning);
+        assert_eq!(kind("NP"), EventKind::NoPlay);
+        assert!(parse_play("S8.1…2").is_err());
+        assert!(parse_play("S8.1/2").is_err());
+    }
+
+    #[test]
+    fn test_apply() {
+        let empty = [false; 3];
+        let loaded = [true; 3];
+        assert_eq!(apply("S8", empty), ([true, false, false], (0, 0)));
+        assert_eq!(apply("HR/7", loaded), (empty, (0, 4)));
+        assert_eq!(apply("W", loaded), (loaded, (0, 1)));
+        assert_eq!(apply("W", [false, true, false]), ([true, true, false], (0, 0)));
+        assert_eq!(apply("64(1)3/GDP", [true, false, false]), (empty, (2, 0)));
+        assert_eq!(apply("54(1)/FO", [true, false, false]), ([true, false, false], (1, 0)));
+        assert_eq!(apply("1(B)16(2)/LDP", [false, true, false]), (empty, (2, 0)));
+        assert_eq!(apply("9/SF.3-H", [false, false, true]), (empty, (1, 1)));
+        assert_eq!(apply("K+WP.B-1", empty), ([true, false, false], (0, 0)));
+        assert_eq!(apply("SB2;SB3", [true, true, false]), ([false, true, true], (0, 0)));
+        assert_eq!(apply("CS2(24)", [true, false, false]), (empty, (1, 0)));
+        assert_eq!(apply("CS2(2E4)", [true, false, false]), ([false, true, false], (0, 0)));
+        assert_eq!(apply("PO1(13)", [true, false, false]), (empty, (1, 0)));
+        assert_eq!(apply("D7.1X3(8E5)", [true, false, false]), ([false, true, true], (0, 0)));
+        assert_eq!(apply("FC6/G.3-H;1X2(64)", [true, false, true]), ([true, false, false], (1, 1)));
+    }
+
+    #[test]
+    fn test_replay() {
+        let games = parse_events(EVENTS).unwrap();
+        let replay = replay(&games[0]);
+        assert_eq!(replay.recorded, Score { away: 7, home: 8 });
+
+        // Plays the simulator cannot represent
+        let events: Vec<_> = replay.discrepancies.iter().map(|d| d.event.as_str()).collect();
+        assert_eq!(
+            events,
+            vec![
+                "64(1)3/GDP",
+                "9/SF.3-H",
+                "CS2(26)",
+                "PO1(13)",
+                "K+WP.B-1;1-2;3-H",
+                // Runners on second and third are not forced by a walk
+                "IW",
+                "64(1)3/GDP",
+                "FC6/G.3-H;1X2(64)",
+                "D7/L.2-H;1-H",
+                "S9/G.2-H",
+            ]
+        );
+        let sf = &replay.discrepancies[1];
+        assert_eq!((sf.inning, sf.team), (1, 1));
+        assert_eq!(sf.replayed, (1, 0));
+        assert_eq!(sf.recorded, (1, 1));
+    }
+}
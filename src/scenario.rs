@@ -0,0 +1,366 @@
+use clap::ValueEnum;
+
+use crate::{
+    config::Config,
+    explain::average_batter,
+    lineup::Side,
+    mlbstats::{self, BatterStats, PitcherStats, Team},
+    park::Venue,
+    simba::{GameState, SimbaConfig},
+};
+
+/// A change to the teams of a game before it is simulated
+#[derive(Clone, Debug, PartialEq)]
+pub enum Change {
+    /// Put a player in a lineup slot (1-9)
+    Swap {
+        side: Side,
+        slot: usize,
+        player_id: String,
+    },
+    /// Start another pitcher
+    Starter { side: Side, player_id: String },
+    /// Take a batter out of the lineup, for an average hitter of the game
+    Bench { player_id: String },
+}
+
+fn parse_side(s: &str) -> Result<Side, String> {
+    Side::from_str(s, true).map_err(|_| format!("Invalid team \"{}\": expected away or home", s))
+}
+
+fn parse_id(s: &str) -> Result<String, String> {
+    if s.is_empty() || !s.chars().all(|c| c.is_ascii_digit()) {
+        return Err(format!("Invalid player ID: \"{}\"", s));
+    }
+    Ok(s.to_string())
+}
+
+impl Change {
+    /// Parse `SIDE:SLOT=ID`
+    pub fn swap(s: &str) -> Result<Change, String> {
+        let err = || format!("Invalid swap \"{}\": expected e.g. home:3=660271", s);
+        let (target, id) = s.split_once('=').ok_or_else(err)?;
+        let (side, slot) = target.split_once(':').ok_or_else(err)?;
+        let slot: usize = slot.parse().map_err(|_| err())?;
+        if !(1..=9).contains(&slot) {
+            return Err(format!("Invalid lineup slot {}: expected 1 to 9", slot));
+        }
+        Ok(Change::Swap {
+            side: parse_side(side)?,
+            slot,
+            player_id: parse_id(id)?,
+        })
+    }
+
+    /// Parse `SIDE=ID`
+    pub fn starter(s: &str) -> Result<Change, String> {
+        let (side, id) = s
+            .split_once('=')
+            .ok_or(format!("Invalid starter \"{}\": expected e.g. away=543037", s))?;
+        Ok(Change::Starter {
+            side: parse_side(side)?,
+            player_id: parse_id(id)?,
+        })
+    }
+
+    /// Parse `ID`
+    pub fn bench(s: &str) -> Result<Change, String> {
+        Ok(Change::Bench {
+            player_id: parse_id(s)?,
+        })
+    }
+}
+
+/// A set of changes, applied in order
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Scenario {
+    pub changes: Vec<Change>,
+}
+
+/// Where to get the stats of the players brought in by a scenario
+pub trait Roster {
+    fn batter(&self, player_id: &str) -> Result<BatterStats, String>;
+    fn pitcher(&self, player_id: &str) -> Result<PitcherStats, String>;
+}
+
+/// Players are fetched from the stats API
+impl Roster for Config {
+    fn batter(&self, player_id: &str) -> Result<BatterStats, String> {
+        mlbstats::batter(self, player_id)
+    }
+
+    fn pitcher(&self, player_id: &str) -> Result<PitcherStats, String> {
+        mlbstats::pitcher(self, player_id)
+    }
+}
+
+impl Scenario {
+    /// Change the teams, and describe each change
+    pub fn apply(
+        &self,
+        roster: &impl Roster,
+        teams: &mut [Team; 2],
+    ) -> Result<Vec<String>, String> {
+        let mut log = Vec::new();
+        for change in &self.changes {
+            match change {
+                Change::Swap {
+                    side,
+                    slot,
+                    player_id,
+                } => {
+                    let team = &mut teams[side.idx()];
+                    let batter = roster.batter(player_id)?;
+                    let out = team.batters.get_mut(slot - 1).ok_or(format!(
+                        "{} has no lineup slot {}",
+                        team.name, slot
+                    ))?;
+                    log.push(format!("{} #{}: {} for {}", team.name, slot, batter.name, out.name));
+                    *out = batter;
+                }
+                Change::Starter { side, player_id } => {
+                    let team = &mut teams[side.idx()];
+                    let pitcher = roster.pitcher(player_id)?;
+                    log.push(format!(
+                        "{} starter: {} for {}",
+                        team.name, pitcher.name, team.starting_pitcher.name
+                    ));
+                    team.starting_pitcher = pitcher;
+                }
+                Change::Bench { player_id } => {
+                    let (idx, slot) = (0..2)
+                        .find_map(|idx| {
+                            teams[idx]
+                                .batters
+                                .iter()
+                                .position(|b| &b.id == player_id)
+                                .map(|slot| (idx, slot))
+                        })
+                        .ok_or(format!("Player {} is not in either lineup", player_id))?;
+                    let others = teams
+                        .iter()
+                        .flat_map(|t| t.batters.iter())
+                        .filter(|b| &b.id != player_id);
+                    let mut replacement = average_batter(others);
+                    replacement.name = "average hitter".to_string();
+                    let team = &mut teams[idx];
+                    log.push(format!(
+                        "{} #{}: {} benched",
+                        team.name,
+                        slot + 1,
+                        team.batters[slot].name
+                    ));
+                    team.batters[slot] = replacement;
+                }
+            }
+        }
+        Ok(log)
+    }
+}
+
+pub struct WhatIf {
+    pub teams: [String; 2],
+    pub changes: Vec<String>,
+    pub baseline_hwp: f64,
+    pub scenario_hwp: f64,
+    /// Indexed away first, then home
+    pub baseline_runs: [f64; 2],
+    pub scenario_runs: [f64; 2],
+}
+
+impl WhatIf {
+    /// Simulate the game as it is and with the scenario applied. Both use
+    /// the same seed, so that the delta is not swamped by sampling noise.
+    pub fn compute(
+        cfg: &Config,
+        roster: &impl Roster,
+        away: &Team,
+        home: &Team,
+        venue: &Venue,
+        scenario: &Scenario,
+    ) -> Result<WhatIf, String> {
+        let mut teams = [away.clone(), home.clone()];
+        let changes = scenario.apply(roster, &mut teams)?;
+
+        let sim = SimbaConfig {
+            n_iter: cfg.iterations,
+            seed: Some(cfg.seed.unwrap_or_else(rand::random)),
+        };
+        let run = |away: &Team, home: &Team| {
+            let mut gs = GameState::new(away, home);
+            gs.venue = *venue;
+            gs.fatigue = cfg.fatigue;
+            gs.matchup = cfg.matchup;
+            sim.run(&gs)
+        };
+        let base = run(away, home)?;
+        let changed = run(&teams[0], &teams[1])?;
+
+        Ok(WhatIf {
+            teams: [away.name.clone(), home.name.clone()],
+            changes,
+            baseline_hwp: base.home_win_probability.ok_or("No prediction")?,
+            scenario_hwp: changed.home_win_probability.ok_or("No prediction")?,
+            baseline_runs: [base.expected_away_runs, base.expected_home_runs],
+            scenario_runs: [changed.expected_away_runs, changed.expected_home_runs],
+        })
+    }
+
+    pub fn delta(&self) -> f64 {
+        self.scenario_hwp - self.baseline_hwp
+    }
+
+    pub fn print(&self) {
+        println!("Changes:");
+        for change in &self.changes {
+            println!("  {}", change);
+        }
+        println!();
+        println!("{:<16}{:>10}{:>10}{:>9}", "", "Baseline", "Scenario", "Delta");
+        let wp = [
+            (1.0 - self.baseline_hwp, 1.0 - self.scenario_hwp),
+            (self.baseline_hwp, self.scenario_hwp),
+        ];
+        for (idx, (before, after)) in wp.into_iter().enumerate() {
+            println!(
+                "{:<16}{:>9.1}%{:>9.1}%{:>+9.1}",
+                format!("{} win", self.teams[idx]),
+                100.0 * before,
+                100.0 * after,
+                100.0 * (after - before)
+            );
+        }
+        for idx in 0..2 {
+            let (before, after) = (self.baseline_runs[idx], self.scenario_runs[idx]);
+            println!(
+                "{:<16}{:>10.2}{:>10.2}{:>+9.2}",
+                format!("{} runs", self.teams[idx]),
+                before,
+                after,
+                after - before
+            );
+        }
+    }
+}
+
+pub fn what_if(cfg: &Config, game_id: &str, scenario: &Scenario) -> Result<(), String> {
+    let feed = mlbstats::feed(cfg, game_id)?;
+    match feed.teams(cfg)? {
+        (Some(away), Some(home)) => {
+            let venue = cfg.venue(feed.venue().as_deref());
+            WhatIf::compute(cfg, cfg, &away, &home, &venue, scenario)?.print();
+            Ok(())
+        }
+        _ => Err(format!("Lineups are not available for game {}", game_id)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, pitcher};
+
+    /// A batter named after its player id, so the change log reads "B900 for B202"
+    fn batter(id: &str) -> BatterStats {
+        BatterStats {
+            name: format!("B{}", id),
+            ..fixtures::batter(id)
+        }
+    }
+
+    fn team(name: &str, first_id: usize) -> Team {
+        Team {
+            starting_pitcher: pitcher(&format!("{}", first_id + 99)),
+            batters: (0..9).map(|i| batter(&format!("{}", first_id + i))).collect(),
+            ..fixtures::team(name)
+        }
+    }
+
+    /// Players brought in are sluggers and aces
+    struct Stars;
+
+    impl Roster for Stars {
+        fn batter(&self, player_id: &str) -> Result<BatterStats, String> {
+            Ok(BatterStats {
+                homeruns: 60,
+                ..batter(player_id)
+            })
+        }
+
+        fn pitcher(&self, player_id: &str) -> Result<PitcherStats, String> {
+            Ok(PitcherStats {
+                hits: 100,
+                ..pitcher(player_id)
+            })
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Change::swap("home:3=660271"),
+            Ok(Change::Swap {
+                side: Side::Home,
+                slot: 3,
+                player_id: "660271".to_string()
+            })
+        );
+        assert!(Change::swap("home:10=660271").is_err());
+        assert!(Change::swap("home=660271").is_err());
+        assert!(Change::swap("left:1=660271").is_err());
+        assert_eq!(
+            Change::starter("away=543037"),
+            Ok(Change::Starter {
+                side: Side::Away,
+                player_id: "543037".to_string()
+            })
+        );
+        assert!(Change::starter("away:543037").is_err());
+        assert!(Change::bench("Ohtani").is_err());
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut teams = [team("AWY", 100), team("HOM", 200)];
+        let scenario = Scenario {
+            changes: vec![
+                Change::swap("home:3=900").unwrap(),
+                Change::starter("away=901").unwrap(),
+                Change::bench("105").unwrap(),
+            ],
+        };
+        let log = scenario.apply(&Stars, &mut teams).unwrap();
+        assert_eq!(log[0], "HOM #3: B900 for B202");
+        assert_eq!(log[1], "AWY starter: P901 for P199");
+        assert_eq!(log[2], "AWY #6: B105 benched");
+        assert_eq!(teams[1].batters[2].id, "900");
+        assert_eq!(teams[0].starting_pitcher.id, "901");
+        assert_eq!(teams[0].batters[5].name, "average hitter");
+
+        let bench = Scenario {
+            changes: vec![Change::bench("999").unwrap()],
+        };
+        assert!(bench.apply(&Stars, &mut teams).is_err());
+    }
+
+    #[test]
+    fn test_what_if() {
+        let (away, home) = (team("AWY", 100), team("HOM", 200));
+        let cfg = Config {
+            iterations: 300,
+            seed: Some(5),
+            ..Config::default()
+        };
+        let scenario = Scenario {
+            changes: vec![Change::starter("home=900").unwrap()],
+        };
+        let venue = Venue::neutral();
+        let res = WhatIf::compute(&cfg, &Stars, &away, &home, &venue, &scenario).unwrap();
+        assert!(res.delta() > 0.0);
+        assert!(res.scenario_runs[0] < res.baseline_runs[0]);
+
+        let same = Scenario::default();
+        let none = WhatIf::compute(&cfg, &Stars, &away, &home, &venue, &same).unwrap();
+        assert_eq!(none.delta(), 0.0);
+    }
+}
@@ -0,0 +1,562 @@
+//! Season projections. Unlike the game predictions, these do not simulate
+//! lineups: a club's strength is its Pythagorean record from runs scored
+//! and allowed, regressed to .500 by `REGRESSION_GAMES`, and each remaining
+//! game is a log5 coin flip between the two strengths. The simulator only
+//! supplies the home-field edge, from a game between two average clubs.
+
+use std::collections::HashMap;
+
+use chrono::{Datelike, NaiveDate};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    config::Config,
+    mlbstats::{self, BatterStats, Club, Fixture, PitcherStats, Team},
+    simba::{GameState, SimbaConfig},
+};
+
+const PYTHAGOREAN_EXPONENT: f64 = 1.83;
+
+/// Games of .500 ball added to every club's record, which pulls early
+/// season strengths toward average
+const REGRESSION_GAMES: f64 = 60.0;
+
+/// Playoff clubs per league: three division winners, then three wild cards
+const N_DIVISION_WINNERS: usize = 3;
+const N_WILD_CARDS: usize = 3;
+const N_SEEDS: usize = N_DIVISION_WINNERS + N_WILD_CARDS;
+
+/// The home-field edge is simulated at least this many times
+const MIN_EDGE_ITERATIONS: usize = 10000;
+
+/// The state of a season on some date
+pub struct League {
+    pub clubs: Vec<Club>,
+    /// `wins[i][j]` is the number of games club `i` won against club `j`
+    pub wins: Vec<Vec<u32>>,
+    pub runs_scored: Vec<i32>,
+    pub runs_allowed: Vec<i32>,
+    /// Games still to play, as (away, home) club indices
+    pub remaining: Vec<(usize, usize)>,
+}
+
+impl League {
+    /// Games that are final on or before `date` count as played, and every
+    /// other game that was not called off as remaining
+    pub fn new(clubs: Vec<Club>, fixtures: &[Fixture], date: NaiveDate) -> League {
+        let n = clubs.len();
+        let index: HashMap<_, _> = clubs
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (c.id.clone(), i))
+            .collect();
+        let mut league = League {
+            clubs,
+            wins: vec![vec![0; n]; n],
+            runs_scored: vec![0; n],
+            runs_allowed: vec![0; n],
+            remaining: Vec::new(),
+        };
+
+        for f in fixtures {
+            let (Some(&away), Some(&home)) = (index.get(&f.away_id), index.get(&f.home_id)) else {
+                continue;
+            };
+            if f.status.starts_with("Postponed") || f.status.starts_with("Cancelled") {
+                continue;
+            }
+            let played = NaiveDate::parse_from_str(&f.date, "%F").is_ok_and(|d| d <= date)
+                && f.abstract_status == "Final";
+            match (played, f.away_score, f.home_score) {
+                (true, Some(a), Some(h)) if a != h => {
+                    if h > a {
+                        league.wins[home][away] += 1;
+                    } else {
+                        league.wins[away][home] += 1;
+                    }
+                    league.runs_scored[away] += a;
+                    league.runs_allowed[away] += h;
+                    league.runs_scored[home] += h;
+                    league.runs_allowed[home] += a;
+                }
+                (true, _, _) => (),
+                (false, _, _) => league.remaining.push((away, home)),
+            }
+        }
+        league
+    }
+
+    pub fn record(&self, i: usize) -> (u32, u32) {
+        record(&self.wins, i, |_| true)
+    }
+
+    /// Pythagorean winning percentage of each club, regressed to .500
+    pub fn strengths(&self) -> Vec<f64> {
+        (0..self.clubs.len())
+            .map(|i| {
+                let (w, l) = self.record(i);
+                let rs = (self.runs_scored[i] as f64).powf(PYTHAGOREAN_EXPONENT);
+                let ra = (self.runs_allowed[i] as f64).powf(PYTHAGOREAN_EXPONENT);
+                let pyth = if rs + ra > 0.0 { rs / (rs + ra) } else { 0.5 };
+                let games = (w + l) as f64;
+                (pyth * games + 0.5 * REGRESSION_GAMES) / (games + REGRESSION_GAMES)
+            })
+            .collect()
+    }
+
+    /// Playoff seed (0-5) of every club given the final records, or None
+    pub(crate) fn seeds(&self, wins: &[Vec<u32>], rng: &mut impl Rng) -> Vec<Option<usize>> {
+        let mut seeds = vec![None; self.clubs.len()];
+        for league in ["AL", "NL"] {
+            let members: Vec<usize> = (0..self.clubs.len())
+                .filter(|&i| self.clubs[i].league == league)
+                .collect();
+            let mut divisions: Vec<&str> = Vec::new();
+            for &i in &members {
+                if !divisions.contains(&self.clubs[i].division.as_str()) {
+                    divisions.push(&self.clubs[i].division);
+                }
+            }
+
+            let (mut winners, mut others) = (Vec::new(), Vec::new());
+            for division in divisions {
+                let mut clubs: Vec<usize> = members
+                    .iter()
+                    .copied()
+                    .filter(|&i| self.clubs[i].division == division)
+                    .collect();
+                self.rank(&mut clubs, wins, rng);
+                winners.push(clubs[0]);
+                others.extend_from_slice(&clubs[1..]);
+            }
+            self.rank(&mut winners, wins, rng);
+            self.rank(&mut others, wins, rng);
+
+            let winners = winners.into_iter().take(N_DIVISION_WINNERS);
+            let wild_cards = others.into_iter().take(N_WILD_CARDS);
+            for (seed, i) in winners.chain(wild_cards).enumerate() {
+                seeds[i] = Some(seed);
+            }
+        }
+        seeds
+    }
+
+    /// Sort clubs by winning percentage, best first, breaking ties with
+    /// MLB's tiebreakers: the record between the tied clubs, then the
+    /// record within the division, then within the league. What is still
+    /// tied after that is settled by a coin flip here.
+    fn rank(&self, clubs: &mut [usize], wins: &[Vec<u32>], rng: &mut impl Rng) {
+        let overall = |i: usize| pct(record(wins, i, |_| true));
+        clubs.sort_by(|&a, &b| overall(b).total_cmp(&overall(a)));
+
+        let mut start = 0;
+        while start < clubs.len() {
+            let end = (start..clubs.len())
+                .find(|&k| overall(clubs[k]) != overall(clubs[start]))
+                .unwrap_or(clubs.len());
+            if end - start > 1 {
+                let tied = clubs[start..end].to_vec();
+                let key = |i: usize| {
+                    [
+                        pct(record(wins, i, |j| tied.contains(&j))),
+                        pct(record(wins, i, |j| {
+                            self.clubs[j].division == self.clubs[i].division
+                        })),
+                        pct(record(wins, i, |j| {
+                            self.clubs[j].league == self.clubs[i].league
+                        })),
+                    ]
+                };
+                let mut keyed: Vec<_> = tied
+                    .iter()
+                    .map(|&i| (key(i), rng.gen::<u64>(), i))
+                    .collect();
+                keyed.sort_by(|a, b| {
+                    b.0.iter()
+                        .zip(&a.0)
+                        .map(|(x, y)| x.total_cmp(y))
+                        .find(|o| o.is_ne())
+                        .unwrap_or(b.1.cmp(&a.1))
+                });
+                for (slot, (_, _, i)) in clubs[start..end].iter_mut().zip(keyed) {
+                    *slot = i;
+                }
+            }
+            start = end;
+        }
+    }
+
+    /// Play out the remaining games `n` times
+    pub fn project(&self, home_edge: f64, n: usize, rng: &mut impl Rng) -> Vec<ClubOdds> {
+        let strengths = self.strengths();
+        let probs: Vec<f64> = self
+            .remaining
+            .iter()
+            .map(|&(away, home)| home_win_probability(strengths[home], strengths[away], home_edge))
+            .collect();
+
+        let mut odds: Vec<ClubOdds> = self
+            .clubs
+            .iter()
+            .enumerate()
+            .map(|(i, club)| {
+                let (wins, losses) = self.record(i);
+                ClubOdds {
+                    club: club.clone(),
+                    wins,
+                    losses,
+                    ..ClubOdds::default()
+                }
+            })
+            .collect();
+
+        for _ in 0..n {
+            let mut wins = self.wins.clone();
+            for (&(away, home), p) in self.remaining.iter().zip(&probs) {
+                if rng.gen::<f64>() < *p {
+                    wins[home][away] += 1;
+                } else {
+                    wins[away][home] += 1;
+                }
+            }
+            for (i, seed) in self.seeds(&wins, rng).into_iter().enumerate() {
+                let (w, l) = record(&wins, i, |_| true);
+                odds[i].projected_wins += w as f64;
+                odds[i].projected_losses += l as f64;
+                if let Some(seed) = seed {
+                    odds[i].seeds[seed] += 1.0;
+                }
+            }
+        }
+
+        for o in odds.iter_mut() {
+            o.projected_wins /= n as f64;
+            o.projected_losses /= n as f64;
+            o.seeds.iter_mut().for_each(|s| *s /= n as f64);
+        }
+        odds
+    }
+}
+
+/// Wins and losses of club `i` against the clubs matching `opponent`
+fn record(wins: &[Vec<u32>], i: usize, opponent: impl Fn(usize) -> bool) -> (u32, u32) {
+    (0..wins.len())
+        .filter(|&j| j != i && opponent(j))
+        .fold((0, 0), |(w, l), j| (w + wins[i][j], l + wins[j][i]))
+}
+
+pub(crate) fn pct((w, l): (u32, u32)) -> f64 {
+    if w + l == 0 {
+        0.5
+    } else {
+        w as f64 / (w + l) as f64
+    }
+}
+
+/// Log5 probability that the home club wins, given both clubs' strengths
+/// and the probability that the home club wins between equal clubs
+pub fn home_win_probability(home: f64, away: f64, home_edge: f64) -> f64 {
+    let odds = |p: f64| p / (1.0 - p);
+    let o = odds(home) / odds(away) * odds(home_edge);
+    o / (1.0 + o)
+}
+
+/// Probability that the home club wins between two average clubs, as
+/// simulated with the configured venue and home-field advantage
+pub fn home_edge(cfg: &Config) -> Result<f64, String> {
+    let batter = BatterStats {
+        id: String::new(),
+        name: "average".to_string(),
+        hand: String::new(),
+        plate_appearances: 600,
+        bases_on_balls: 50,
+        hits: 140,
+        doubles: 28,
+        triples: 3,
+        homeruns: 18,
+        strikeouts: 135,
+    };
+    let pitcher = PitcherStats {
+        id: String::new(),
+        name: "average".to_string(),
+        hand: String::new(),
+        batters_faced: 600,
+        bases_on_balls: 50,
+        hits: 140,
+        doubles: 28,
+        triples: 3,
+        homeruns: 18,
+        strikeouts: 135,
+    };
+    let team = Team {
+        name: "average".to_string(),
+        starting_pitcher: pitcher,
+        batters: vec![batter; 9],
+    };
+    let mut gs = GameState::new(&team, &team);
+    gs.venue = cfg.venue(None);
+    gs.fatigue = cfg.fatigue;
+    gs.matchup = cfg.matchup;
+    SimbaConfig {
+        n_iter: cfg.iterations.max(MIN_EDGE_ITERATIONS),
+        seed: cfg.seed,
+    }
+    .run(&gs)?
+    .home_win_probability
+    .ok_or("No prediction".to_string())
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ClubOdds {
+    pub club: Club,
+    pub wins: u32,
+    pub losses: u32,
+    pub projected_wins: f64,
+    pub projected_losses: f64,
+    /// Probability of each playoff seed, division winners first
+    pub seeds: [f64; N_SEEDS],
+}
+
+impl ClubOdds {
+    pub fn division(&self) -> f64 {
+        self.seeds[..N_DIVISION_WINNERS].iter().sum()
+    }
+
+    pub fn wild_card(&self) -> f64 {
+        self.seeds[N_DIVISION_WINNERS..].iter().sum()
+    }
+
+    pub fn playoffs(&self) -> f64 {
+        self.seeds.iter().sum()
+    }
+}
+
+fn print(odds: &[ClubOdds]) {
+    let pct = |p: f64| format!("{:>6.1}", 100.0 * p);
+    let mut divisions: Vec<(&str, &str)> = Vec::new();
+    for o in odds {
+        let key = (o.club.league.as_str(), o.club.division.as_str());
+        if !divisions.contains(&key) {
+            divisions.push(key);
+        }
+    }
+    divisions.sort();
+
+    for (league, division) in divisions {
+        let mut clubs: Vec<_> = odds
+            .iter()
+            .filter(|o| o.club.league == league && o.club.division == division)
+            .collect();
+        clubs.sort_by(|a, b| b.projected_wins.total_cmp(&a.projected_wins));
+
+        println!();
+        println!(
+            "{:<22}{:>4}{:>4}{:>7}{:>7}{:>6}{:>6}{:>6}  {}",
+            division,
+            "W",
+            "L",
+            "ProjW",
+            "ProjL",
+            "Div%",
+            "WC%",
+            "Post%",
+            (1..=N_SEEDS)
+                .map(|s| format!("{:>6}", format!("#{}", s)))
+                .collect::<String>()
+        );
+        for o in clubs {
+            println!(
+                "{:<22}{:>4}{:>4}{:>7.1}{:>7.1}{}{}{}  {}",
+                o.club.name,
+                o.wins,
+                o.losses,
+                o.projected_wins,
+                o.projected_losses,
+                pct(o.division()),
+                pct(o.wild_card()),
+                pct(o.playoffs()),
+                o.seeds.iter().map(|s| pct(*s)).collect::<String>()
+            );
+        }
+    }
+}
+
+/// Project the rest of the season from the standings on the configured date
+pub fn season(cfg: &Config, simulations: usize) -> Result<(), String> {
+    let date = cfg.naive_date()?;
+    let year = date.year().to_string();
+    let league = League::new(
+        mlbstats::clubs(cfg, &year)?,
+        &mlbstats::fixtures(cfg, &year)?,
+        date,
+    );
+    if league.clubs.is_empty() {
+        return Err(format!("No standings for {}", year));
+    }
+
+    let edge = home_edge(cfg)?;
+    let mut rng = StdRng::seed_from_u64(cfg.seed.unwrap_or_else(rand::random));
+    let odds = league.project(edge, simulations, &mut rng);
+
+    println!(
+        "{} season as of {}: {} games left, {} simulations (home edge {:.1}%)",
+        year,
+        date,
+        league.remaining.len(),
+        simulations,
+        100.0 * edge
+    );
+    print(&odds);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two leagues of three divisions with `per_division` clubs each
+    fn clubs(per_division: usize) -> Vec<Club> {
+        let mut clubs = Vec::new();
+        for league in ["AL", "NL"] {
+            for division in ["East", "Central", "West"] {
+                for k in 0..per_division {
+                    clubs.push(Club {
+                        id: format!("{}{}{}", league, division, k),
+                        name: format!("{}{}{}", league, &division[..1], k),
+                        league: league.to_string(),
+                        division: format!("{} {}", league, division),
+                        ..Club::default()
+                    });
+                }
+            }
+        }
+        clubs
+    }
+
+    fn fixture(away: &str, home: &str, date: &str, score: Option<(i32, i32)>) -> Fixture {
+        Fixture {
+            game_id: String::new(),
+            date: date.to_string(),
+            away_id: away.to_string(),
+            home_id: home.to_string(),
+            status: if score.is_some() {
+                "Final"
+            } else {
+                "Scheduled"
+            }
+            .to_string(),
+            abstract_status: if score.is_some() { "Final" } else { "Preview" }.to_string(),
+            away_score: score.map(|s| s.0),
+            home_score: score.map(|s| s.1),
+        }
+    }
+
+    #[test]
+    fn test_new() {
+        let date = NaiveDate::from_ymd_opt(2024, 6, 1).unwrap();
+        let mut postponed = fixture("ALEast0", "ALEast1", "2024-05-01", None);
+        postponed.status = "Postponed".to_string();
+        let fixtures = [
+            fixture("ALEast0", "ALEast1", "2024-05-01", Some((3, 5))),
+            fixture("ALEast1", "ALEast0", "2024-05-02", Some((4, 2))),
+            // Final, but after the date of the projection
+            fixture("ALEast1", "ALEast0", "2024-06-02", Some((1, 2))),
+            fixture("ALEast0", "NLWest1", "2024-06-03", None),
+            fixture("ALEast0", "nobody", "2024-06-04", None),
+            postponed,
+        ];
+        let league = League::new(clubs(2), &fixtures, date);
+        let (e0, e1) = (0, 1);
+        assert_eq!(league.record(e1), (2, 0));
+        assert_eq!(league.record(e0), (0, 2));
+        assert_eq!(league.runs_scored[e1], 9);
+        assert_eq!(league.runs_allowed[e1], 5);
+        assert_eq!(league.remaining.len(), 2);
+        assert_eq!(league.remaining[1], (e0, 11));
+
+        let s = league.strengths();
+        assert!(s[e1] > 0.5 && s[e0] < 0.5);
+        assert_eq!(s[2], 0.5);
+    }
+
+    #[test]
+    fn test_home_win_probability() {
+        assert!((home_win_probability(0.5, 0.5, 0.54) - 0.54).abs() < 1e-12);
+        assert!((home_win_probability(0.6, 0.4, 0.5) - 0.6923).abs() < 1e-4);
+        assert!(home_win_probability(0.4, 0.6, 0.54) < 0.5);
+    }
+
+    #[test]
+    fn test_rank_tiebreakers() {
+        let league = League::new(clubs(3), &[], NaiveDate::from_ymd_opt(2024, 6, 1).unwrap());
+        let mut rng = StdRng::seed_from_u64(1);
+        let overall = |wins: &[Vec<u32>], i| record(wins, i, |_| true);
+
+        // Clubs 0 and 1 are both 2-2, and 1 won the season series
+        let mut wins = vec![vec![0; 18]; 18];
+        wins[1][0] = 2;
+        wins[0][1] = 1;
+        wins[0][3] = 1;
+        wins[3][1] = 1;
+        assert_eq!(overall(&wins, 0), (2, 2));
+        assert_eq!(overall(&wins, 1), (2, 2));
+        let mut ranked = vec![0, 1];
+        league.rank(&mut ranked, &wins, &mut rng);
+        assert_eq!(ranked, vec![1, 0]);
+
+        // An even season series: 1 has the better record in the division
+        let mut wins = vec![vec![0; 18]; 18];
+        wins[0][1] = 1;
+        wins[1][0] = 1;
+        wins[1][2] = 1;
+        wins[3][1] = 1;
+        wins[0][3] = 1;
+        wins[2][0] = 1;
+        assert_eq!(overall(&wins, 0), (2, 2));
+        assert_eq!(overall(&wins, 1), (2, 2));
+        let mut ranked = vec![0, 1];
+        league.rank(&mut ranked, &wins, &mut rng);
+        assert_eq!(ranked, vec![1, 0]);
+
+        // No tie: the better record comes first
+        wins[4][0] = 1;
+        let mut ranked = vec![0, 1, 4];
+        league.rank(&mut ranked, &wins, &mut rng);
+        assert_eq!(ranked, vec![4, 1, 0]);
+    }
+
+    #[test]
+    fn test_project() {
+        // 5 clubs per division, so that seeding has wild cards to pick
+        let clubs = clubs(5);
+        let mut fixtures = Vec::new();
+        for (i, a) in clubs.iter().enumerate() {
+            for b in clubs.iter().skip(i + 1).filter(|b| b.league == a.league) {
+                fixtures.push(fixture(&a.id, &b.id, "2024-09-01", None));
+                fixtures.push(fixture(&b.id, &a.id, "2024-09-02", None));
+            }
+        }
+        let league = League::new(
+            clubs,
+            &fixtures,
+            NaiveDate::from_ymd_opt(2024, 6, 1).unwrap(),
+        );
+        let mut rng = StdRng::seed_from_u64(7);
+        let odds = league.project(0.54, 200, &mut rng);
+        assert_eq!(odds.len(), 30);
+        for league in ["AL", "NL"] {
+            let clubs: Vec<_> = odds.iter().filter(|o| o.club.league == league).collect();
+            let playoffs: f64 = clubs.iter().map(|o| o.playoffs()).sum();
+            let division: f64 = clubs.iter().map(|o| o.division()).sum();
+            assert!((playoffs - 6.0).abs() < 1e-9);
+            assert!((division - 3.0).abs() < 1e-9);
+            for seed in 0..N_SEEDS {
+                let total: f64 = clubs.iter().map(|o| o.seeds[seed]).sum();
+                assert!((total - 1.0).abs() < 1e-9);
+            }
+        }
+        // 28 games each, and every game has a winner
+        let wins: f64 = odds.iter().map(|o| o.projected_wins).sum();
+        assert!((wins - 30.0 * 14.0).abs() < 1e-9);
+    }
+}
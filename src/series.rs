@@ -0,0 +1,764 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{Datelike, NaiveDate};
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::{
+    config::Config,
+    mlbstats::{self, Club, Fixture, PitcherStats, Team},
+    park::Venue,
+    season::{self, League},
+    simba::{GameState, SimbaConfig},
+};
+
+/// Starters taken from a club's most recent games for its rotation
+const ROTATION_SIZE: usize = 4;
+
+/// Rounds of the postseason, and the number of games of each
+const ROUNDS: [(&str, usize); 4] = [
+    ("Wild Card", 3),
+    ("Division Series", 5),
+    ("LCS", 7),
+    ("World Series", 7),
+];
+
+/// Length of a series, and which club hosts each game
+#[derive(Clone, Debug, PartialEq)]
+pub struct Format {
+    pub best_of: usize,
+    pub pattern: String,
+    /// Whether each game is played at the club with home-field advantage
+    pub hosts: Vec<bool>,
+}
+
+impl Format {
+    /// `best_of` games played in alternating blocks such as "2-3-2", the
+    /// first block at the club with home-field advantage. The pattern
+    /// defaults to MLB's for 3, 5 and 7 games.
+    pub fn new(best_of: usize, pattern: Option<&str>) -> Result<Format, String> {
+        if best_of.is_multiple_of(2) {
+            return Err(format!(
+                "A series must be best-of an odd number of games, not {}",
+                best_of
+            ));
+        }
+        let pattern = match (pattern, best_of) {
+            (Some(p), _) => p,
+            (None, 3) => "3",
+            (None, 5) => "2-2-1",
+            (None, 7) => "2-3-2",
+            (None, _) => {
+                return Err(format!(
+                    "No default pattern for best-of-{}: use --pattern",
+                    best_of
+                ))
+            }
+        };
+        let err = || format!("Invalid pattern \"{}\": expected e.g. 2-3-2", pattern);
+        let mut hosts = Vec::new();
+        for (k, block) in pattern.split('-').enumerate() {
+            let n: usize = block.parse().map_err(|_| err())?;
+            if n == 0 {
+                return Err(err());
+            }
+            hosts.extend(std::iter::repeat_n(k % 2 == 0, n));
+        }
+        if hosts.len() != best_of {
+            return Err(format!(
+                "Pattern \"{}\" has {} games, not {}",
+                pattern,
+                hosts.len(),
+                best_of
+            ));
+        }
+        Ok(Format {
+            best_of,
+            pattern: pattern.to_string(),
+            hosts,
+        })
+    }
+
+    /// Wins needed to take the series
+    pub fn wins_needed(&self) -> usize {
+        self.best_of / 2 + 1
+    }
+}
+
+/// Parse `TEAM=ID,ID,...`
+pub fn parse_rotation(s: &str) -> Result<(String, Vec<String>), String> {
+    let err = || {
+        format!(
+            "Invalid rotation \"{}\": expected e.g. LAD=477132,605141",
+            s
+        )
+    };
+    let (team, ids) = s.split_once('=').ok_or_else(err)?;
+    let ids: Vec<String> = ids.split(',').map(|id| id.trim().to_string()).collect();
+    if team.is_empty()
+        || ids
+            .iter()
+            .any(|id| id.is_empty() || !id.chars().all(|c| c.is_ascii_digit()))
+    {
+        return Err(err());
+    }
+    Ok((team.to_uppercase(), ids))
+}
+
+/// A postseason club, with the players it is expected to use
+#[derive(Clone, Debug)]
+pub struct Contender {
+    pub club: Club,
+    /// Playoff seed within its league, 0 being the best
+    pub seed: usize,
+    /// Regular-season winning percentage
+    pub pct: f64,
+    pub lineup: Team,
+    /// Starters in the order they take the mound. Every series starts
+    /// again from the top of the rotation.
+    pub rotation: Vec<PitcherStats>,
+    /// The club's home park
+    pub venue: Venue,
+}
+
+impl Contender {
+    /// Whether the club has home-field advantage over `other`: the better
+    /// seed within a league, the better record in the World Series
+    fn hosts(&self, other: &Contender) -> bool {
+        if self.club.league == other.club.league || self.pct == other.pct {
+            self.seed < other.seed
+        } else {
+            self.pct > other.pct
+        }
+    }
+
+    fn starter(&self, game: usize) -> &PitcherStats {
+        &self.rotation[game % self.rotation.len()]
+    }
+}
+
+/// How the winner of a single game is predicted
+pub trait GameOdds {
+    /// Probability that `home` beats `away` at `venue`
+    fn home_win_probability(
+        &mut self,
+        away: &Team,
+        home: &Team,
+        venue: &Venue,
+    ) -> Result<f64, String>;
+}
+
+/// Games are simulated on common random numbers, each matchup of clubs
+/// and starters once
+pub struct Simulator<'a> {
+    cfg: &'a Config,
+    sim: SimbaConfig,
+    cache: HashMap<[String; 4], f64>,
+}
+
+impl<'a> Simulator<'a> {
+    pub fn new(cfg: &'a Config) -> Simulator<'a> {
+        Simulator {
+            cfg,
+            sim: SimbaConfig {
+                n_iter: cfg.iterations,
+                seed: Some(cfg.seed.unwrap_or_else(rand::random)),
+            },
+            cache: HashMap::new(),
+        }
+    }
+}
+
+impl GameOdds for Simulator<'_> {
+    fn home_win_probability(
+        &mut self,
+        away: &Team,
+        home: &Team,
+        venue: &Venue,
+    ) -> Result<f64, String> {
+        let key = [
+            away.name.clone(),
+            away.starting_pitcher.id.clone(),
+            home.name.clone(),
+            home.starting_pitcher.id.clone(),
+        ];
+        if let Some(p) = self.cache.get(&key) {
+            return Ok(*p);
+        }
+        let mut gs = GameState::new(away, home);
+        gs.venue = *venue;
+        gs.fatigue = self.cfg.fatigue;
+        gs.matchup = self.cfg.matchup;
+        let mut hwp = self
+            .sim
+            .run(&gs)?
+            .home_win_probability
+            .ok_or("No prediction")?;
+        if let Some(recalibration) = &self.cfg.recalibration {
+            hwp = recalibration.apply(hwp);
+        }
+        self.cache.insert(key, hwp);
+        Ok(hwp)
+    }
+}
+
+pub struct SeriesOdds {
+    /// The visiting club first, then the club with home-field advantage
+    pub clubs: [String; 2],
+    pub format: Format,
+    /// Starters of each game, visitors first
+    pub starters: Vec<[String; 2]>,
+    /// Probability that the club with home-field advantage wins each game
+    pub games: Vec<f64>,
+    /// Probability of each final tally of wins, visitors first
+    pub outcomes: Vec<([usize; 2], f64)>,
+}
+
+impl SeriesOdds {
+    pub fn compute(
+        odds: &mut impl GameOdds,
+        visitor: &Contender,
+        host: &Contender,
+        format: &Format,
+    ) -> Result<SeriesOdds, String> {
+        let (mut starters, mut games) = (Vec::new(), Vec::new());
+        for (game, &at_host) in format.hosts.iter().enumerate() {
+            let mut away = visitor.lineup.clone();
+            away.starting_pitcher = visitor.starter(game).clone();
+            let mut home = host.lineup.clone();
+            home.starting_pitcher = host.starter(game).clone();
+            let p = if at_host {
+                odds.home_win_probability(&away, &home, &host.venue)?
+            } else {
+                1.0 - odds.home_win_probability(&home, &away, &visitor.venue)?
+            };
+            starters.push([
+                away.starting_pitcher.name.clone(),
+                home.starting_pitcher.name.clone(),
+            ]);
+            games.push(p);
+        }
+        Ok(SeriesOdds {
+            clubs: [visitor.club.name.clone(), host.club.name.clone()],
+            format: format.clone(),
+            starters,
+            outcomes: outcomes(&games),
+            games,
+        })
+    }
+
+    /// Probability that club `idx` wins the series
+    pub fn advance(&self, idx: usize) -> f64 {
+        let need = self.format.wins_needed();
+        self.outcomes
+            .iter()
+            .filter(|(wins, _)| wins[idx] == need)
+            .map(|(_, p)| p)
+            .sum()
+    }
+
+    pub fn print(&self) {
+        println!(
+            "{} vs {}, best of {} ({})",
+            self.clubs[0], self.clubs[1], self.format.best_of, self.format.pattern
+        );
+        println!();
+        println!(
+            "{:>4}  {:<6}{:<36}{:>8}",
+            "Game",
+            "At",
+            "Starters",
+            format!("{} %", self.clubs[1])
+        );
+        for (game, p) in self.games.iter().enumerate() {
+            let at = &self.clubs[if self.format.hosts[game] { 1 } else { 0 }];
+            let starters = format!("{} vs {}", self.starters[game][0], self.starters[game][1]);
+            println!(
+                "{:>4}  {:<6}{:<36}{:>8.1}",
+                game + 1,
+                at,
+                starters,
+                100.0 * p
+            );
+        }
+        println!();
+        let need = self.format.wins_needed();
+        for (wins, p) in &self.outcomes {
+            let winner = if wins[1] == need { 1 } else { 0 };
+            println!(
+                "{:<6}{}-{}{:>8.1}%",
+                self.clubs[winner],
+                wins[winner],
+                wins[1 - winner],
+                100.0 * p
+            );
+        }
+        println!();
+        println!(
+            "Advance: {} {:.1}%, {} {:.1}%",
+            self.clubs[0],
+            100.0 * self.advance(0),
+            self.clubs[1],
+            100.0 * self.advance(1)
+        );
+    }
+}
+
+/// Probability of each final tally of a series, given the probability
+/// that the second club wins each game
+fn outcomes(games: &[f64]) -> Vec<([usize; 2], f64)> {
+    let need = games.len() / 2 + 1;
+    let mut states = BTreeMap::from([([0, 0], 1.0)]);
+    let mut done = BTreeMap::new();
+    for p in games {
+        let mut next = BTreeMap::new();
+        for (wins, q) in states {
+            for (idx, r) in [(0, 1.0 - p), (1, *p)] {
+                let mut wins: [usize; 2] = wins;
+                wins[idx] += 1;
+                let to = if wins[idx] == need {
+                    &mut done
+                } else {
+                    &mut next
+                };
+                *to.entry(wins).or_insert(0.0) += q * r;
+            }
+        }
+        states = next;
+    }
+    done.into_iter().collect()
+}
+
+/// A club, or the winner of a series between the winners of two brackets
+enum Node {
+    Club(usize),
+    Series(Box<Node>, Box<Node>, usize),
+}
+
+impl Node {
+    fn series(a: Node, b: Node, round: usize) -> Node {
+        Node::Series(Box::new(a), Box::new(b), round)
+    }
+
+    /// Probability that each contender wins this part of the bracket, and
+    /// the odds of winning each round added to `advance`
+    fn winners(
+        &self,
+        contenders: &[Contender],
+        odds: &mut impl GameOdds,
+        advance: &mut [[f64; 4]],
+    ) -> Result<Vec<(usize, f64)>, String> {
+        let (a, b, round) = match self {
+            Node::Club(i) => return Ok(vec![(*i, 1.0)]),
+            Node::Series(a, b, round) => (a, b, *round),
+        };
+        let a = a.winners(contenders, odds, advance)?;
+        let b = b.winners(contenders, odds, advance)?;
+        let format = Format::new(ROUNDS[round].1, None)?;
+
+        let mut winners: BTreeMap<usize, f64> = BTreeMap::new();
+        for &(i, p) in &a {
+            for &(j, q) in &b {
+                let (visitor, host) = if contenders[i].hosts(&contenders[j]) {
+                    (j, i)
+                } else {
+                    (i, j)
+                };
+                let series =
+                    SeriesOdds::compute(odds, &contenders[visitor], &contenders[host], &format)?;
+                *winners.entry(visitor).or_insert(0.0) += p * q * series.advance(0);
+                *winners.entry(host).or_insert(0.0) += p * q * series.advance(1);
+            }
+        }
+        for (&i, &p) in &winners {
+            advance[i][round] += p;
+        }
+        Ok(winners.into_iter().collect())
+    }
+}
+
+pub struct Bracket {
+    pub contenders: Vec<Contender>,
+    /// Probability that each contender wins each round, a bye counting as
+    /// a win of the wild card round
+    pub advance: Vec<[f64; 4]>,
+}
+
+impl Bracket {
+    /// The 12-club format: in each league, seeds 3 to 6 play the wild card
+    /// round, the top two seeds meet its winners in the division series,
+    /// and the league champions meet in the World Series.
+    pub fn compute(
+        odds: &mut impl GameOdds,
+        contenders: Vec<Contender>,
+    ) -> Result<Bracket, String> {
+        let find = |league: &str, seed: usize| {
+            contenders
+                .iter()
+                .position(|c| c.club.league == league && c.seed == seed)
+                .map(Node::Club)
+                .ok_or(format!("No {} seed {}", league, seed + 1))
+        };
+        let mut champions = Vec::new();
+        for league in ["AL", "NL"] {
+            let wc = [
+                Node::series(find(league, 2)?, find(league, 5)?, 0),
+                Node::series(find(league, 3)?, find(league, 4)?, 0),
+            ];
+            let [wc36, wc45] = wc;
+            let ds = [
+                Node::series(find(league, 0)?, wc45, 1),
+                Node::series(find(league, 1)?, wc36, 1),
+            ];
+            let [ds1, ds2] = ds;
+            champions.push(Node::series(ds1, ds2, 2));
+        }
+        let nl = champions.pop().unwrap();
+        let al = champions.pop().unwrap();
+        let final_ = Node::series(al, nl, 3);
+
+        let mut advance: Vec<[f64; 4]> = contenders
+            .iter()
+            .map(|c| [if c.seed < 2 { 1.0 } else { 0.0 }, 0.0, 0.0, 0.0])
+            .collect();
+        final_.winners(&contenders, odds, &mut advance)?;
+        Ok(Bracket {
+            contenders,
+            advance,
+        })
+    }
+
+    pub fn print(&self) {
+        for league in ["AL", "NL"] {
+            let mut clubs: Vec<usize> = (0..self.contenders.len())
+                .filter(|&i| self.contenders[i].club.league == league)
+                .collect();
+            clubs.sort_by_key(|&i| self.contenders[i].seed);
+            println!();
+            println!(
+                "{:<6}{:>5}{:>7}{}",
+                league,
+                "Seed",
+                "Pct",
+                ROUNDS
+                    .iter()
+                    .map(|(name, _)| format!("{:>17}", name))
+                    .collect::<String>()
+            );
+            for i in clubs {
+                let c = &self.contenders[i];
+                println!(
+                    "{:<6}{:>5}{:>7.3}{}",
+                    c.club.name,
+                    c.seed + 1,
+                    c.pct,
+                    self.advance[i]
+                        .iter()
+                        .map(|p| format!("{:>16.1}%", 100.0 * p))
+                        .collect::<String>()
+                );
+            }
+        }
+    }
+}
+
+/// The clubs of the season as of the configured date, and its games
+fn standings(cfg: &Config) -> Result<(League, Vec<Fixture>, NaiveDate), String> {
+    let date = cfg.naive_date()?;
+    let year = date.year().to_string();
+    let fixtures = mlbstats::fixtures(cfg, &year)?;
+    let league = League::new(mlbstats::clubs(cfg, &year)?, &fixtures, date);
+    if league.clubs.is_empty() {
+        return Err(format!("No standings for {}", year));
+    }
+    Ok((league, fixtures, date))
+}
+
+/// A club with the lineup of its last game, the starters of its last
+/// games as its rotation (unless given), and the venue of its last home
+/// game
+fn contender(
+    cfg: &Config,
+    league: &League,
+    i: usize,
+    seed: Option<usize>,
+    fixtures: &[Fixture],
+    date: NaiveDate,
+    rotation: Option<&Vec<String>>,
+) -> Result<Contender, String> {
+    let club = &league.clubs[i];
+    let mut recent: Vec<&Fixture> = fixtures
+        .iter()
+        .filter(|f| {
+            (f.away_id == club.id || f.home_id == club.id)
+                && f.abstract_status == "Final"
+                && f.away_score.is_some()
+                && NaiveDate::parse_from_str(&f.date, "%F").is_ok_and(|d| d <= date)
+        })
+        .collect();
+    recent.sort_by(|a, b| b.date.cmp(&a.date));
+    let side = |f: &Fixture| if f.home_id == club.id { 1 } else { 0 };
+
+    let last = recent
+        .first()
+        .ok_or(format!("{} has not played by {}", club.name, date))?;
+    let (away, home) = mlbstats::teams(cfg, &last.game_id)?;
+    let lineup = if side(last) == 1 { home } else { away }.ok_or(format!(
+        "No lineup for {} in game {}",
+        club.name, last.game_id
+    ))?;
+
+    let mut starters = Vec::new();
+    match rotation {
+        Some(ids) => {
+            for id in ids {
+                starters.push(mlbstats::pitcher(cfg, id)?);
+            }
+        }
+        None => {
+            for f in recent.iter().take(ROTATION_SIZE) {
+                let starter = mlbstats::feed(cfg, &f.game_id)?
+                    .starters(cfg)?
+                    .into_iter()
+                    .nth(side(f))
+                    .flatten();
+                if let Some(p) = starter {
+                    if !starters.iter().any(|s: &PitcherStats| s.id == p.id) {
+                        starters.push(p);
+                    }
+                }
+            }
+            // The starter of the oldest of these games is next in line
+            starters.reverse();
+        }
+    }
+    if starters.is_empty() {
+        starters.push(lineup.starting_pitcher.clone());
+    }
+
+    let venue_id = match recent.iter().find(|f| f.home_id == club.id) {
+        Some(f) => mlbstats::feed(cfg, &f.game_id)?.venue(),
+        None => None,
+    };
+
+    Ok(Contender {
+        club: club.clone(),
+        seed: seed.unwrap_or(usize::MAX),
+        pct: season::pct(league.record(i)),
+        lineup,
+        rotation: starters,
+        venue: cfg.venue(venue_id.as_deref()),
+    })
+}
+
+/// Simulate a series between two clubs, the one with home-field advantage
+/// last, or the whole bracket seeded from the standings if `teams` is None
+pub fn series(
+    cfg: &Config,
+    teams: Option<&[String; 2]>,
+    format: &Format,
+    rotations: &[(String, Vec<String>)],
+) -> Result<(), String> {
+    let (league, fixtures, date) = standings(cfg)?;
+    let mut rng = StdRng::seed_from_u64(cfg.seed.unwrap_or_else(rand::random));
+    let seeds = league.seeds(&league.wins, &mut rng);
+    let rotation = |club: &Club| {
+        rotations
+            .iter()
+            .find(|(team, _)| team.eq_ignore_ascii_case(&club.name))
+            .map(|(_, ids)| ids)
+    };
+    let contender = |i: usize| {
+        contender(
+            cfg,
+            &league,
+            i,
+            seeds[i],
+            &fixtures,
+            date,
+            rotation(&league.clubs[i]),
+        )
+    };
+    let mut odds = Simulator::new(cfg);
+
+    match teams {
+        Some(teams) => {
+            let mut clubs = Vec::new();
+            for team in teams {
+                let i = league
+                    .clubs
+                    .iter()
+                    .position(|c| c.name.eq_ignore_ascii_case(team))
+                    .ok_or(format!("Unknown team: {}", team))?;
+                clubs.push(contender(i)?);
+            }
+            SeriesOdds::compute(&mut odds, &clubs[0], &clubs[1], format)?.print();
+        }
+        None => {
+            let mut contenders = Vec::new();
+            for (i, seed) in seeds.iter().enumerate() {
+                if seed.is_some() {
+                    contenders.push(contender(i)?);
+                }
+            }
+            println!("Postseason as of {}, seeded from the standings", date);
+            Bracket::compute(&mut odds, contenders)?.print();
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{batter, pitcher};
+    use crate::mlbstats::BatterStats;
+
+    /// A club whose lineup gets `hits` per 600 plate appearances
+    fn contender(name: &str, league: &str, seed: usize, hits: i32) -> Contender {
+        Contender {
+            club: Club {
+                id: name.to_string(),
+                name: name.to_string(),
+                league: league.to_string(),
+                ..Club::default()
+            },
+            seed,
+            pct: 0.6 - 0.01 * seed as f64,
+            lineup: Team {
+                name: name.to_string(),
+                starting_pitcher: pitcher("0"),
+                batters: vec![
+                    BatterStats {
+                        hits,
+                        ..batter("B")
+                    };
+                    9
+                ],
+            },
+            rotation: vec![
+                pitcher(&format!("{}1", name)),
+                pitcher(&format!("{}2", name)),
+            ],
+            venue: Venue::neutral(),
+        }
+    }
+
+    /// Log5 of the lineups' hits, with a fixed home edge
+    struct Log5;
+
+    impl GameOdds for Log5 {
+        fn home_win_probability(
+            &mut self,
+            away: &Team,
+            home: &Team,
+            _venue: &Venue,
+        ) -> Result<f64, String> {
+            let strength = |t: &Team| t.batters[0].hits as f64 / 280.0;
+            Ok(season::home_win_probability(
+                strength(home),
+                strength(away),
+                0.54,
+            ))
+        }
+    }
+
+    #[test]
+    fn test_format() {
+        let f = Format::new(7, None).unwrap();
+        assert_eq!(f.hosts, [true, true, false, false, false, true, true]);
+        assert_eq!(f.wins_needed(), 4);
+        assert_eq!(Format::new(3, None).unwrap().hosts, [true; 3]);
+        assert_eq!(
+            Format::new(5, Some("2-3")).unwrap().hosts,
+            [true, true, false, false, false]
+        );
+        assert!(Format::new(4, None).is_err());
+        assert!(Format::new(9, None).is_err());
+        assert!(Format::new(7, Some("2-2-2")).is_err());
+        assert!(Format::new(5, Some("2-0-3")).is_err());
+    }
+
+    #[test]
+    fn test_parse_rotation() {
+        assert_eq!(
+            parse_rotation("lad=477132,605141"),
+            Ok((
+                "LAD".to_string(),
+                vec!["477132".to_string(), "605141".to_string()]
+            ))
+        );
+        assert!(parse_rotation("LAD").is_err());
+        assert!(parse_rotation("LAD=477132,").is_err());
+        assert!(parse_rotation("=477132").is_err());
+    }
+
+    #[test]
+    fn test_outcomes() {
+        let even = outcomes(&[0.5; 7]);
+        assert_eq!(even.len(), 8);
+        assert!((even.iter().map(|(_, p)| p).sum::<f64>() - 1.0).abs() < 1e-12);
+        let sweep = even.iter().find(|(w, _)| *w == [0, 4]).unwrap().1;
+        assert!((sweep - 1.0 / 16.0).abs() < 1e-12);
+        // 4-3 needs a 3-3 tie (20 of 64 orders) and a win
+        let seven = even.iter().find(|(w, _)| *w == [4, 3]).unwrap().1;
+        assert!((seven - 20.0 / 128.0).abs() < 1e-12);
+
+        let best_of_3 = outcomes(&[0.6; 3]);
+        let host = best_of_3
+            .iter()
+            .filter(|(w, _)| w[1] == 2)
+            .map(|(_, p)| p)
+            .sum::<f64>();
+        assert!((host - (0.36 + 2.0 * 0.6 * 0.4 * 0.6)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_series() {
+        let (low, high) = (
+            contender("LOW", "AL", 3, 130),
+            contender("HIGH", "AL", 0, 150),
+        );
+        let series =
+            SeriesOdds::compute(&mut Log5, &low, &high, &Format::new(7, None).unwrap()).unwrap();
+        assert_eq!(series.starters[0], ["PLOW1", "PHIGH1"]);
+        assert_eq!(series.starters[2], ["PLOW1", "PHIGH1"]);
+        // Stronger at home than away
+        assert!(series.games[0] > series.games[2]);
+        assert!(series.games[2] > 0.5);
+        assert!((series.advance(0) + series.advance(1) - 1.0).abs() < 1e-12);
+        assert!(series.advance(1) > series.games[2]);
+    }
+
+    #[test]
+    fn test_bracket() {
+        let mut contenders = Vec::new();
+        for league in ["AL", "NL"] {
+            for seed in 0..6 {
+                let name = format!("{}{}", league, seed + 1);
+                contenders.push(contender(&name, league, seed, 150 - 2 * seed as i32));
+            }
+        }
+        let bracket = Bracket::compute(&mut Log5, contenders).unwrap();
+        for round in 0..4 {
+            let total: f64 = bracket.advance.iter().map(|a| a[round]).sum();
+            let expected = [8.0, 4.0, 2.0, 1.0][round];
+            assert!(
+                (total - expected).abs() < 1e-9,
+                "round {}: {}",
+                round,
+                total
+            );
+        }
+        // The top seed has a bye, and the best odds
+        assert_eq!(bracket.advance[0][0], 1.0);
+        assert!((1..6).all(|i| bracket.advance[0][3] > bracket.advance[i][3]));
+        // A club only reaches a round by winning the one before
+        assert!(bracket
+            .advance
+            .iter()
+            .all(|a| a.windows(2).all(|w| w[0] >= w[1])));
+
+        let missing = vec![contender("AL1", "AL", 0, 150)];
+        assert!(Bracket::compute(&mut Log5, missing).is_err());
+    }
+}
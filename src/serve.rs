@@ -0,0 +1,469 @@
+//! A JSON API over HTTP, for dashboards and notebooks to query the oracle
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::NaiveDate;
+use log;
+use serde_json as json;
+
+use crate::{
+    api::Oracle,
+    config::Config,
+    dates,
+    explain::Explanation,
+    filter::Slate,
+    history,
+    mlbstats::{self, Game},
+    output::{Format, Record},
+};
+
+/// How long a request with `?wait` blocks for a result
+const WAIT_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// How long a client may take to send its request
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How many results are kept. Beyond that, the oldest finished one makes
+/// room for a new one.
+const MAX_ENTRIES: usize = 256;
+
+#[derive(Debug, PartialEq)]
+enum Route {
+    Health,
+    Schedule(NaiveDate),
+    Predict(NaiveDate),
+    Game(String),
+}
+
+impl Route {
+    /// The route of a path, e.g. /predict/2024-04-01 or /predict/today
+    fn parse(path: &str) -> Result<Route, (u16, String)> {
+        let parts: Vec<&str> = path.trim_matches('/').split('/').collect();
+        let date = |s: &str| dates::parse(s, dates::today()).map_err(|e| (400, e));
+        match parts.as_slice() {
+            ["health"] => Ok(Route::Health),
+            ["schedule"] => Ok(Route::Schedule(dates::today())),
+            ["schedule", d] => Ok(Route::Schedule(date(d)?)),
+            ["predict"] => Ok(Route::Predict(dates::today())),
+            ["predict", d] => Ok(Route::Predict(date(d)?)),
+            ["game", id] if !id.is_empty() && id.chars().all(|c| c.is_ascii_digit()) => {
+                Ok(Route::Game(id.to_string()))
+            }
+            ["game", id] => Err((400, format!("Invalid game ID: {}", id))),
+            _ => Err((404, format!("Not found: {}", path))),
+        }
+    }
+
+    /// The cache key of the route's result
+    fn key(&self) -> String {
+        match self {
+            Route::Health => "health".to_string(),
+            Route::Schedule(date) => format!("schedule/{}", date),
+            Route::Predict(date) => format!("predict/{}", date),
+            Route::Game(id) => format!("game/{}", id),
+        }
+    }
+}
+
+/// A result, and whether it can never change, e.g. because every game it
+/// covers is over
+type Outcome = Result<(json::Value, bool), String>;
+
+#[derive(Default)]
+struct Entry {
+    result: Option<Result<json::Value, String>>,
+    computed: Option<Instant>,
+    permanent: bool,
+    /// Whether a job is computing the result
+    running: bool,
+}
+
+#[derive(Debug, PartialEq)]
+enum Reply {
+    Ready(json::Value),
+    Failed(String),
+    Pending,
+    /// Every kept result is being computed, so there is no room for another
+    Busy,
+}
+
+/// Results shared between requests. Each is computed by a background job,
+/// and kept until it is older than `ttl`, but stale results are still
+/// served while they are refreshed.
+struct Cache {
+    entries: Mutex<HashMap<String, Entry>>,
+    done: Condvar,
+    ttl: Duration,
+}
+
+impl Cache {
+    fn new(ttl: Duration) -> Cache {
+        Cache {
+            entries: Mutex::new(HashMap::new()),
+            done: Condvar::new(),
+            ttl,
+        }
+    }
+
+    /// The result of `key`, starting `job` if it is missing or stale. With
+    /// `wait`, blocks until there is a result.
+    fn get<F>(self: &Arc<Self>, key: &str, wait: bool, job: F) -> Reply
+    where
+        F: FnOnce() -> Outcome + Send + 'static,
+    {
+        let mut entries = self.entries.lock().unwrap();
+        if !entries.contains_key(key) && entries.len() >= MAX_ENTRIES {
+            let oldest = entries
+                .iter()
+                .filter(|(_, e)| !e.running)
+                .min_by_key(|(_, e)| e.computed)
+                .map(|(k, _)| k.clone());
+            match oldest {
+                Some(k) => entries.remove(&k),
+                None => return Reply::Busy,
+            };
+        }
+        let entry = entries.entry(key.to_string()).or_default();
+        let stale = match entry.computed {
+            _ if entry.running => false,
+            Some(at) => !entry.permanent && at.elapsed() >= self.ttl,
+            None => true,
+        };
+        if stale {
+            entry.running = true;
+            let cache = Arc::clone(self);
+            let key = key.to_string();
+            thread::spawn(move || {
+                // A job that panics fails, rather than leaving its key
+                // pending for good
+                let outcome = panic::catch_unwind(AssertUnwindSafe(job))
+                    .unwrap_or_else(|_| Err("Internal error".to_string()));
+                let mut entries = cache.entries.lock().unwrap();
+                let entry = entries.entry(key).or_default();
+                match outcome {
+                    Ok((value, permanent)) => {
+                        entry.result = Some(Ok(value));
+                        entry.permanent = permanent;
+                    }
+                    Err(e) => entry.result = Some(Err(e)),
+                }
+                entry.computed = Some(Instant::now());
+                entry.running = false;
+                cache.done.notify_all();
+            });
+        }
+
+        let deadline = Instant::now() + WAIT_TIMEOUT;
+        loop {
+            let entry = entries.get(key);
+            match entry.and_then(|e| e.result.as_ref()) {
+                Some(Ok(value)) => return Reply::Ready(value.clone()),
+                Some(Err(e)) => return Reply::Failed(e.clone()),
+                None if !wait => return Reply::Pending,
+                None => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Reply::Pending;
+                    }
+                    entries = self.done.wait_timeout(entries, deadline - now).unwrap().0;
+                }
+            }
+        }
+    }
+}
+
+struct Server {
+    cfg: Arc<Config>,
+    cache: Arc<Cache>,
+}
+
+impl Server {
+    fn handle(&self, stream: TcpStream) {
+        let _ = stream.set_read_timeout(Some(READ_TIMEOUT));
+        let mut reader = BufReader::new(&stream);
+        let mut request = String::new();
+        if reader.read_line(&mut request).is_err() {
+            return;
+        }
+        // The headers are not used
+        let mut line = String::new();
+        while reader.read_line(&mut line).is_ok_and(|n| n > 0) && line.trim_end() != "" {
+            line.clear();
+        }
+        log::info!(target: "serve", "{}", request.trim_end());
+
+        let (status, body) = self.respond(&request);
+        let _ = (&stream).write_all(response(status, &body).as_bytes());
+    }
+
+    /// The status and body of the response to a request line
+    fn respond(&self, request: &str) -> (u16, json::Value) {
+        let mut words = request.split_whitespace();
+        let (Some(method), Some(target)) = (words.next(), words.next()) else {
+            return (400, error("Malformed request"));
+        };
+        if method != "GET" {
+            return (405, error(&format!("Method not allowed: {}", method)));
+        }
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let route = match Route::parse(path) {
+            Ok(route) => route,
+            Err((status, msg)) => return (status, error(&msg)),
+        };
+        if route == Route::Health {
+            return (
+                200,
+                json::json!({
+                    "status": "ok",
+                    "model_version": history::model_version(&self.cfg),
+                }),
+            );
+        }
+
+        let wait = query.split('&').any(|kv| match kv.split_once('=') {
+            Some(("wait", v)) => !matches!(v, "0" | "false"),
+            None => kv == "wait",
+            _ => false,
+        });
+        let cfg = Arc::clone(&self.cfg);
+        let key = route.key();
+        // Schedules are quick to fetch, so they are always waited for
+        let reply = match route {
+            Route::Schedule(date) => self.cache.get(&key, true, move || schedule(&cfg, date)),
+            Route::Predict(date) => self.cache.get(&key, wait, move || predict(&cfg, date)),
+            Route::Game(id) => self.cache.get(&key, wait, move || game(&cfg, &id)),
+            Route::Health => unreachable!(),
+        };
+        match reply {
+            Reply::Ready(value) => (200, value),
+            Reply::Failed(msg) => (502, error(&msg)),
+            Reply::Pending => (202, json::json!({"status": "pending"})),
+            Reply::Busy => (503, error("Too many requests in progress")),
+        }
+    }
+}
+
+fn error(msg: &str) -> json::Value {
+    json::json!({ "error": msg })
+}
+
+fn response(status: u16, body: &json::Value) -> String {
+    let reason = match status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        503 => "Service Unavailable",
+        _ => "Bad Gateway",
+    };
+    let body = body.to_string();
+    format!(
+        "HTTP/1.1 {} {}\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         Access-Control-Allow-Origin: *\r\n\
+         Connection: close\r\n\r\n{}",
+        status,
+        reason,
+        body.len(),
+        body
+    )
+}
+
+/// The configuration of the jobs for one date
+fn on_date(cfg: &Config, date: NaiveDate) -> Config {
+    Config {
+        date: date.format("%F").to_string(),
+        format: Format::Json,
+        // Refreshes would record the same predictions over and over
+        history: None,
+        ..cfg.clone()
+    }
+}
+
+/// Whether the results of a date's games can no longer change
+fn settled(games: &[Game], date: NaiveDate) -> bool {
+    date < dates::today() && games.iter().all(|g| g.is_final())
+}
+
+fn game_json(game: &Game) -> json::Value {
+    json::json!({
+        "game_id": game.game_id,
+        "status": game.status,
+        "start_time": Record::new(game).start_time,
+        "away": {"id": game.away_id, "name": game.away_name, "score": game.away_score},
+        "home": {"id": game.home_id, "name": game.home_name, "score": game.home_score},
+        "venue_id": game.venue_id,
+    })
+}
+
+fn schedule(cfg: &Config, date: NaiveDate) -> Outcome {
+    let cfg = on_date(cfg, date);
+    let games = Slate::new(&cfg)?.games(&cfg)?;
+    let value = games.iter().map(game_json).collect();
+    Ok((value, settled(&games, date)))
+}
+
+fn predict(cfg: &Config, date: NaiveDate) -> Outcome {
+    let oracle = Oracle::with_config(on_date(cfg, date));
+    let cfg = oracle.config();
+    let games = Slate::new(cfg)?.games(cfg)?;
+    let mut records = Vec::new();
+    for game in &games {
+        let mut record = Record::new(game);
+        if let Err(e) = crate::oracle(&oracle, game, &mut record) {
+            log::warn!(target: "serve", "{}: {}", game.game_id, e);
+        }
+        records.push(record.to_json());
+    }
+    Ok((json::Value::Array(records), settled(&games, date)))
+}
+
+/// The prediction of a game, and the explanation of its pregame one
+fn game(cfg: &Config, game_id: &str) -> Outcome {
+    let feed = mlbstats::feed(cfg, game_id)?;
+    let game = feed.game()?;
+    let mut record = Record::new(&game);
+    let prediction = Oracle::with_config(cfg.clone()).predict_from(&game, &feed)?;
+    let (Some([away, home]), Some(result)) = (&prediction.teams, prediction.result) else {
+        record.outcome = "missing_lineups".to_string();
+        return Ok((
+            json::json!({"game": game_json(&game), "prediction": record.to_json()}),
+            false,
+        ));
+    };
+    record.teams(away, home);
+    record.simulated(&result);
+
+    let venue = cfg.venue(game.venue_id.as_deref());
+    let explanation = Explanation::compute(cfg, away, home, &venue)?;
+    let value = json::json!({
+        "game": game_json(&game),
+        "prediction": record.to_json(),
+        "expected_runs": [result.expected_away_runs, result.expected_home_runs],
+        "explanation": explanation.to_json(),
+    });
+    Ok((value, game.is_final()))
+}
+
+pub fn serve(cfg: &Config, bind: &str, ttl: Duration) -> Result<(), String> {
+    let listener = TcpListener::bind(bind).map_err(|e| format!("Cannot bind {}: {}", bind, e))?;
+    eprintln!("[serve] Listening on http://{}", bind);
+    let server = Arc::new(Server {
+        cfg: Arc::new(cfg.clone()),
+        cache: Arc::new(Cache::new(ttl)),
+    });
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let server = Arc::clone(&server);
+                thread::spawn(move || server.handle(stream));
+            }
+            Err(e) => log::warn!(target: "serve", "{}", e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn serve_routes() {
+        let date = NaiveDate::from_ymd_opt(2024, 4, 1).unwrap();
+        assert_eq!(Route::parse("/health"), Ok(Route::Health));
+        assert_eq!(
+            Route::parse("/predict/2024-04-01/"),
+            Ok(Route::Predict(date))
+        );
+        assert_eq!(
+            Route::parse("/schedule/2024-04-01"),
+            Ok(Route::Schedule(date))
+        );
+        assert_eq!(
+            Route::parse("/predict/tomorrow"),
+            Ok(Route::Predict(dates::today() + chrono::Days::new(1)))
+        );
+        assert_eq!(
+            Route::parse("/game/745123"),
+            Ok(Route::Game("745123".to_string()))
+        );
+        assert_eq!(Route::parse("/game/x").unwrap_err().0, 400);
+        assert_eq!(Route::parse("/predict/someday").unwrap_err().0, 400);
+        assert_eq!(Route::parse("/teams").unwrap_err().0, 404);
+        assert_eq!(Route::Predict(date).key(), "predict/2024-04-01");
+    }
+
+    #[test]
+    fn serve_cache() {
+        let cache = Arc::new(Cache::new(Duration::from_secs(60)));
+        let reply = cache.get("a", true, || Ok((json::json!(1), false)));
+        assert_eq!(reply, Reply::Ready(json::json!(1)));
+        // Fresh results are reused
+        let reply = cache.get("a", true, || Ok((json::json!(2), false)));
+        assert_eq!(reply, Reply::Ready(json::json!(1)));
+
+        // Without waiting, a request gets an answer while the job runs
+        let (tx, rx) = mpsc::channel::<()>();
+        let reply = cache.get("b", false, move || {
+            let _ = rx.recv();
+            Err("unavailable".to_string())
+        });
+        assert_eq!(reply, Reply::Pending);
+        tx.send(()).unwrap();
+        let reply = cache.get("b", true, || Ok((json::json!(3), false)));
+        assert_eq!(reply, Reply::Failed("unavailable".to_string()));
+
+        // Stale results are served while they are refreshed
+        let cache = Arc::new(Cache::new(Duration::ZERO));
+        cache.get("c", true, || Ok((json::json!(4), false)));
+        let reply = cache.get("c", false, || Ok((json::json!(5), true)));
+        assert_eq!(reply, Reply::Ready(json::json!(4)));
+
+        // A job that panics fails, rather than staying pending
+        let reply = cache.get("d", true, || panic!("bug"));
+        assert_eq!(reply, Reply::Failed("Internal error".to_string()));
+        assert!(!cache.entries.lock().unwrap()["d"].running);
+    }
+
+    #[test]
+    fn serve_cache_bounded() {
+        let cache = Arc::new(Cache::new(Duration::from_secs(60)));
+        for n in 0..MAX_ENTRIES {
+            cache.get(&n.to_string(), true, move || Ok((json::json!(n), false)));
+        }
+        let reply = cache.get("new", true, || Ok((json::json!("new"), false)));
+        assert_eq!(reply, Reply::Ready(json::json!("new")));
+        let entries = cache.entries.lock().unwrap();
+        assert_eq!(entries.len(), MAX_ENTRIES);
+        assert!(!entries.contains_key("0"));
+    }
+
+    #[test]
+    fn serve_response() {
+        let server = Server {
+            cfg: Arc::new(Config::default()),
+            cache: Arc::new(Cache::new(Duration::from_secs(60))),
+        };
+        let (status, body) = server.respond("GET /health HTTP/1.1\r\n");
+        assert_eq!(status, 200);
+        assert_eq!(body["status"], "ok");
+        assert_eq!(server.respond("POST /health HTTP/1.1").0, 405);
+        assert_eq!(server.respond("GET /nowhere?wait HTTP/1.1").0, 404);
+        assert_eq!(server.respond("").0, 400);
+
+        let text = response(404, &error("Not found: /x"));
+        assert!(text.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert!(text.contains("Content-Length: 25\r\n"));
+        assert!(text.ends_with("\r\n\r\n{\"error\":\"Not found: /x\"}"));
+    }
+}
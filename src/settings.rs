@@ -0,0 +1,263 @@
+//! Settings read from a TOML file. Command-line flags take precedence over
+//! them, and they over the built-in defaults.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+
+use crate::config::{default_config_path, Config};
+use crate::mlbstats::StatSource;
+use crate::output::{Color, ColorChoice, Display, Format};
+use crate::simba::MatchupModel;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Settings {
+    pub iterations: Option<usize>,
+    pub seed: Option<u64>,
+    pub matchup: Option<MatchupModel>,
+    pub stats: Option<StatSource>,
+    pub batter_regression: Option<f64>,
+    pub pitcher_regression: Option<f64>,
+    pub home_advantage: Option<f64>,
+    pub tto_penalty: Option<f64>,
+    pub fatigue_threshold: Option<i32>,
+    pub fatigue_rate: Option<f64>,
+    pub pull_at: Option<i32>,
+    pub format: Option<Format>,
+    pub color: Option<ColorChoice>,
+    pub display: Option<Display>,
+    pub timezone: Option<String>,
+    pub thresholds: Option<[f64; 4]>,
+    pub colors: Option<[Color; 5]>,
+    pub api_url: Option<String>,
+    pub cache_dir: Option<PathBuf>,
+    pub offline: Option<bool>,
+}
+
+impl Settings {
+    /// Read the given file, or the default one if there is one
+    pub fn load(path: Option<&Path>) -> Result<Settings, String> {
+        match path {
+            Some(path) => Settings::read(path),
+            None => match default_config_path() {
+                Some(path) if path.is_file() => Settings::read(&path),
+                _ => Ok(Settings::default()),
+            },
+        }
+    }
+
+    pub fn read(path: &Path) -> Result<Settings, String> {
+        let data = fs::read_to_string(path)
+            .map_err(|err| format!("Cannot read {}: {}", path.display(), err))?;
+        Settings::parse(&data).map_err(|err| format!("Invalid config {}: {}", path.display(), err))
+    }
+
+    pub fn parse(data: &str) -> Result<Settings, String> {
+        let table: toml::Table = data.parse().map_err(|err: toml::de::Error| err.message().to_string())?;
+        let mut settings = Settings::default();
+        for (section, value) in &table {
+            let Some(fields) = value.as_table() else {
+                return Err(format!("Expected a table: {}", section));
+            };
+            for (key, value) in fields {
+                let name = format!("{}.{}", section, key);
+                settings.set(&name, value)?;
+            }
+        }
+        Ok(settings)
+    }
+
+    fn set(&mut self, name: &str, value: &toml::Value) -> Result<(), String> {
+        match name {
+            "model.iterations" => self.iterations = Some(integer(name, value)?),
+            "model.seed" => self.seed = Some(integer(name, value)?),
+            "model.matchup" => self.matchup = Some(choice(name, value)?),
+            "model.stats" => self.stats = Some(choice(name, value)?),
+            "model.batter_regression" => self.batter_regression = Some(float(name, value)?),
+            "model.pitcher_regression" => self.pitcher_regression = Some(float(name, value)?),
+            "model.home_advantage" => self.home_advantage = Some(float(name, value)?),
+            "model.tto_penalty" => self.tto_penalty = Some(float(name, value)?),
+            "model.fatigue_threshold" => self.fatigue_threshold = Some(integer(name, value)?),
+            "model.fatigue_rate" => self.fatigue_rate = Some(float(name, value)?),
+            "model.pull_at" => self.pull_at = Some(integer(name, value)?),
+            "output.format" => self.format = Some(choice(name, value)?),
+            "output.color" => self.color = Some(choice(name, value)?),
+            "output.display" => self.display = Some(choice(name, value)?),
+            "output.timezone" => self.timezone = Some(string(name, value)?.to_string()),
+            "output.colormap" => {
+                let Some(fields) = value.as_table() else {
+                    return Err(format!("Expected a table: {}", name));
+                };
+                for (key, value) in fields {
+                    let name = format!("{}.{}", name, key);
+                    match key.as_str() {
+                        "thresholds" => {
+                            let values = array(&name, value, float)?;
+                            self.thresholds = Some(sized(&name, values)?);
+                        }
+                        "colors" => {
+                            let values = array(&name, value, choice)?;
+                            self.colors = Some(sized(&name, values)?);
+                        }
+                        _ => return Err(format!("Unknown setting: {}", name)),
+                    }
+                }
+            }
+            "api.url" => self.api_url = Some(string(name, value)?.to_string()),
+            "cache.dir" => self.cache_dir = Some(PathBuf::from(string(name, value)?)),
+            "cache.offline" => {
+                let v = value.as_bool().ok_or(format!("Expected true or false: {}", name))?;
+                self.offline = Some(v);
+            }
+            _ => return Err(format!("Unknown setting: {}", name)),
+        }
+        Ok(())
+    }
+}
+
+fn string<'a>(name: &str, value: &'a toml::Value) -> Result<&'a str, String> {
+    value.as_str().ok_or(format!("Expected a string: {}", name))
+}
+
+fn integer<T: TryFrom<i64>>(name: &str, value: &toml::Value) -> Result<T, String> {
+    value
+        .as_integer()
+        .and_then(|v| T::try_from(v).ok())
+        .ok_or(format!("Expected a non-negative integer: {}", name))
+}
+
+/// Integers are accepted too, e.g. `home_advantage = 0`
+fn float(name: &str, value: &toml::Value) -> Result<f64, String> {
+    value
+        .as_float()
+        .or(value.as_integer().map(|v| v as f64))
+        .ok_or(format!("Expected a number: {}", name))
+}
+
+fn choice<T: ValueEnum>(name: &str, value: &toml::Value) -> Result<T, String> {
+    T::from_str(string(name, value)?, true).map_err(|_| {
+        let choices: Vec<_> = T::value_variants().iter().map(self::name).collect();
+        format!("Expected one of {}: {}", choices.join(", "), name)
+    })
+}
+
+fn array<T>(
+    name: &str,
+    value: &toml::Value,
+    item: fn(&str, &toml::Value) -> Result<T, String>,
+) -> Result<Vec<T>, String> {
+    let values = value.as_array().ok_or(format!("Expected an array: {}", name))?;
+    values.iter().map(|v| item(name, v)).collect()
+}
+
+fn sized<T, const N: usize>(name: &str, values: Vec<T>) -> Result<[T; N], String> {
+    values
+        .try_into()
+        .map_err(|_| format!("Expected {} values: {}", N, name))
+}
+
+/// The name of a value on the command line and in the config file
+fn name<T: ValueEnum>(value: &T) -> String {
+    value
+        .to_possible_value()
+        .map_or(String::new(), |v| v.get_name().to_string())
+}
+
+/// The configuration in effect, in the format of the config file
+pub fn show(cfg: &Config) -> String {
+    let mut model = toml::Table::new();
+    model.insert("iterations".into(), (cfg.iterations as i64).into());
+    if let Some(seed) = cfg.seed {
+        model.insert("seed".into(), (seed as i64).into());
+    }
+    model.insert("matchup".into(), name(&cfg.matchup.model).into());
+    model.insert("stats".into(), name(&cfg.stats).into());
+    model.insert("batter_regression".into(), cfg.matchup.batter_regression.into());
+    model.insert("pitcher_regression".into(), cfg.matchup.pitcher_regression.into());
+    model.insert("home_advantage".into(), cfg.home_advantage.into());
+    model.insert("tto_penalty".into(), cfg.fatigue.times_through_order.into());
+    model.insert("fatigue_threshold".into(), (cfg.fatigue.threshold as i64).into());
+    model.insert("fatigue_rate".into(), cfg.fatigue.per_pitch.into());
+    model.insert("pull_at".into(), (cfg.fatigue.pull_at as i64).into());
+
+    let mut colormap = toml::Table::new();
+    colormap.insert("thresholds".into(), cfg.colormap.thresholds.to_vec().into());
+    let colors: Vec<_> = cfg.colormap.colors.iter().map(name).collect();
+    colormap.insert("colors".into(), colors.into());
+    let mut output = toml::Table::new();
+    output.insert("format".into(), name(&cfg.format).into());
+    output.insert("color".into(), name(&cfg.color).into());
+    output.insert("display".into(), name(&cfg.display).into());
+    let timezone = cfg.tz.map_or("local".to_string(), |tz| tz.name().to_string());
+    output.insert("timezone".into(), timezone.into());
+    output.insert("colormap".into(), colormap.into());
+
+    let mut api = toml::Table::new();
+    api.insert("url".into(), cfg.api_url.clone().into());
+
+    let mut cache = toml::Table::new();
+    if let Some(dir) = &cfg.cache_dir {
+        cache.insert("dir".into(), dir.display().to_string().into());
+    }
+    cache.insert("offline".into(), cfg.offline.into());
+
+    let mut table = toml::Table::new();
+    table.insert("model".into(), model.into());
+    table.insert("output".into(), output.into());
+    table.insert("api".into(), api.into());
+    table.insert("cache".into(), cache.into());
+    table.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_parse() {
+        let settings = Settings::parse(
+            r#"
+            [model]
+            iterations = 5000
+            matchup = "log5"
+            home_advantage = 0
+
+            [output]
+            display = "compact"
+            colormap = { thresholds = [0.8, 0.6, 0.4, 0.2], colors = ["blue", "green", "white", "red", "magenta"] }
+
+            [api]
+            url = "http://localhost:8080/api"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(settings.iterations, Some(5000));
+        assert_eq!(settings.matchup, Some(MatchupModel::Log5));
+        assert_eq!(settings.home_advantage, Some(0.0));
+        assert_eq!(settings.display, Some(Display::Compact));
+        assert_eq!(settings.colors.unwrap()[4], Color::Magenta);
+        assert_eq!(settings.api_url.as_deref(), Some("http://localhost:8080/api"));
+        assert_eq!(settings.seed, None);
+    }
+
+    #[test]
+    fn settings_invalid() {
+        assert!(Settings::parse("[model]\niteration = 10").is_err());
+        assert!(Settings::parse("[model]\niterations = -1").is_err());
+        assert!(Settings::parse("[model]\nmatchup = \"average\"").is_err());
+        assert!(Settings::parse("[output.colormap]\nthresholds = [0.5]").is_err());
+        assert!(Settings::parse("iterations = 10").is_err());
+        assert!(Settings::parse("[model").is_err());
+    }
+
+    #[test]
+    fn settings_show() {
+        let cfg = Config::default();
+        let shown = Settings::parse(&show(&cfg)).unwrap();
+        assert_eq!(shown.iterations, Some(cfg.iterations));
+        assert_eq!(shown.matchup, Some(cfg.matchup.model));
+        assert_eq!(shown.colors, Some(cfg.colormap.colors));
+        assert_eq!(shown.api_url, Some(cfg.api_url));
+    }
+}
@@ -1,26 +1,32 @@
 // TODO:
 // - Split off the GameState from the SimState such that it can
 //   be used by analyzers as well
-// - Using this, start validating against retrosheet data
 // - Properly design the Play type
 // - Organize this file
 
 use std::iter;
+use std::sync::LazyLock;
 
 use itertools::Itertools;
+use rand::{rngs::StdRng, Rng, SeedableRng};
 
-use crate::mlbstats::{BatterStats, PitcherStats, Team};
+use crate::mlbstats::{BatterStats, PitcherStats, Situation, Team};
+use crate::park::{ParkFactors, Venue};
 
 pub struct SimbaConfig {
     pub n_iter: usize,
+    /// Seed for the random number generator, drawn at random if None
+    pub seed: Option<u64>,
 }
 
 impl SimbaConfig {
-    pub fn run<'a>(&self, gamestate: &GameState) -> Result<SimResult, String> {
+    pub fn run(&self, gamestate: &GameState) -> Result<SimResult, String> {
+        let seed = self.seed.unwrap_or_else(rand::random);
+        let mut rng = StdRng::seed_from_u64(seed);
         let scores = iter::repeat_with(|| {
-            SimbaState::new(gamestate.clone())
+            SimbaState::new(gamestate.clone(), StdRng::seed_from_u64(rng.gen()))
                 .into_iter()
-                .fold_ok(Score::default(), |s, p| s.add(p.team, p.runs))
+                .fold_ok(gamestate.score.clone(), |s, p| s.add(p.team, p.runs))
         })
         .take(self.n_iter)
         .collect::<Result<Vec<_>, _>>()?;
@@ -33,17 +39,30 @@ impl SimbaConfig {
             .iter()
             .fold(0, |acc, (s, n)| acc + if s.home > s.away { *n } else { 0 });
 
-        let hwp = wins as f64 / norm as f64;
+        // Without simulated games there is nothing to predict
+        let hwp = (norm > 0).then(|| wins as f64 / norm as f64);
+
+        let (away_runs, home_runs) = scores.iter().fold((0, 0), |(a, h), (s, n)| {
+            (a + s.away as usize * n, h + s.home as usize * n)
+        });
 
         Ok(SimResult {
-            home_win_probability: Some(hwp),
+            home_win_probability: hwp,
+            expected_away_runs: away_runs as f64 / norm.max(1) as f64,
+            expected_home_runs: home_runs as f64 / norm.max(1) as f64,
+            iterations: self.n_iter,
+            seed,
+            scores: scores.into_iter().map(|(s, n)| (s.clone(), n)).collect(),
         })
     }
 }
 
 impl Default for SimbaConfig {
     fn default() -> SimbaConfig {
-        SimbaConfig { n_iter: 1000 }
+        SimbaConfig {
+            n_iter: 1000,
+            seed: None,
+        }
     }
 }
 
@@ -56,6 +75,9 @@ pub struct GameState<'a> {
     pub inning: i32,
     pub outs: i32,
     pub live: bool,
+    pub venue: Venue,
+    pub fatigue: Fatigue,
+    pub matchup: Matchup,
 }
 
 impl<'a> GameState<'a> {
@@ -68,10 +90,80 @@ impl<'a> GameState<'a> {
             inning: 1,
             outs: 0,
             live: true,
+            venue: Venue::neutral(),
+            fatigue: Fatigue::none(),
+            matchup: Matchup::default(),
+        }
+    }
+
+    /// Pick up a game in progress from the situation reported by the live feed
+    pub fn from_situation(
+        visteam: &'a Team,
+        hometeam: &'a Team,
+        situation: &'a Situation,
+    ) -> GameState<'a> {
+        let mut teams = [LiveTeam::from(visteam), LiveTeam::from(hometeam)];
+        for (idx, team) in teams.iter_mut().enumerate() {
+            team.current_batter = situation.batting_order[idx] % team.team.batters.len().max(1);
+            team.current_pitcher = situation.pitchers[idx].as_ref();
+            team.pitcher_usage = PitcherUsage {
+                batters_faced: situation.batters_faced[idx],
+                pitch_count: situation.pitch_counts[idx],
+            };
+        }
+
+        let mut gs = GameState {
+            bases: situation.bases,
+            score: Score {
+                away: situation.away_score,
+                home: situation.home_score,
+            },
+            teams,
+            team_idx: if situation.top { 0 } else { 1 },
+            inning: situation.inning,
+            outs: situation.outs,
+            live: true,
+            venue: Venue::neutral(),
+            fatigue: Fatigue::none(),
+            matchup: Matchup::default(),
+        };
+        gs.settle();
+        gs
+    }
+
+    /// End the game or the half-inning if the current state calls for it
+    fn settle(&mut self) {
+        let vis_ab = self.team_idx == 0;
+        let vis_losing = self.score.home > self.score.away;
+
+	// Game is over if either:
+        // A. inning >= 9, vis at bat,  vis losing, 3 outs
+        // C. inning >= 9, home at bat, vis winning, 3 outs
+        // B. inning >= 9, home at bat, vis losing
+
+        if self.inning >= 9 {
+            let tied = self.score.home == self.score.away;
+            if self.outs == 3 && vis_ab == vis_losing && !tied {
+                self.live = false;
+            } else if !vis_ab && vis_losing {
+                self.live = false
+            }
+        }
+
+        // Else, Inning is over if 3 outs
+        if self.live && self.outs == 3 {
+            self.bases[0] = false;
+            self.bases[1] = false;
+            self.bases[2] = false;
+            if self.team_idx == 1 {
+                self.inning += 1;
+            }
+            self.team_idx = 1 - self.team_idx;
+            self.outs = 0;
         }
     }
 
-    fn transition(&mut self, play: &Play) -> i32 {
+    pub fn transition(&mut self, play: &Play) -> i32 {
 	let (advs, outs) = match play.outcome {
             Outcome::Walk => (1, 0),
             Outcome::Single => (1, 0),
@@ -85,6 +177,9 @@ impl<'a> GameState<'a> {
 
 	// Step in the batting order
         self.teams[self.team_idx as usize].advance();
+        self.teams[1 - self.team_idx as usize]
+            .pitcher_usage
+            .record(play.outcome);
 
         // Count runs and advance field state
         let mut runs = 0;
@@ -107,70 +202,57 @@ impl<'a> GameState<'a> {
         // Charge outs to offense
         self.outs += outs;
 
-        let vis_ab = self.team_idx == 0;
-        let vis_losing = self.score.home > self.score.away;
-
-	// Game is over if either:
-        // A. inning >= 9, vis at bat,  vis losing, 3 outs
-        // C. inning >= 9, home at bat, vis winning, 3 outs
-        // B. inning >= 9, home at bat, vis losing
-
-        if self.inning >= 9 {
-            if vis_ab && vis_losing && self.outs == 3 {
-                self.live = false;
-            } else if !vis_ab && !vis_losing && self.outs == 3 {
-                self.live = false;
-            } else if !vis_ab && vis_losing {
-                self.live = false
-            }
-        }
-
-        // Else, Inning is over if 3 outs
-        if self.live && self.outs == 3 {
-            self.bases[0] = false;
-            self.bases[1] = false;
-            self.bases[2] = false;
-            if self.team_idx == 1 {
-                self.inning += 1;
-            }
-            self.team_idx = 1 - self.team_idx;
-            self.outs = 0;
-        }
+        self.settle();
 
 	runs
     }
 }
 
 
-struct Play {
-    team: i32,
-    runs: i32,
-    outcome: Outcome,
+pub struct Play {
+    pub team: i32,
+    pub runs: i32,
+    pub outcome: Outcome,
 }
 
 struct SimbaState<'a> {
-    gamestate: GameState<'a>
+    gamestate: GameState<'a>,
+    rng: StdRng,
 }
 
 impl<'a> SimbaState<'a> {
-    pub fn new(gamestate: GameState<'a>) -> SimbaState<'a> {
-	SimbaState { gamestate }
+    pub fn new(gamestate: GameState<'a>, rng: StdRng) -> SimbaState<'a> {
+	SimbaState { gamestate, rng }
     }    
 
-    fn eval(&self) -> Option<Play> {
+    fn eval(&mut self) -> Option<Play> {
         if !self.gamestate.live {
             return None;
         }
 
         let off_idx = self.gamestate.team_idx as usize;
         let def_idx = 1 - self.gamestate.team_idx as usize;
+        let defense = &mut self.gamestate.teams[def_idx];
+        if self.gamestate.fatigue.spent(&defense.pitcher_usage) {
+            defense.relieve();
+        }
         let batter = self.gamestate.teams[off_idx].batter();
         let pitcher = self.gamestate.teams[def_idx].pitcher();
+        let edge = self.gamestate.venue.edge(off_idx == 1)
+            * self
+                .gamestate
+                .fatigue
+                .penalty(&self.gamestate.teams[def_idx].pitcher_usage);
+        let park = &self.gamestate.venue.park;
 
         Some(Play {
             team: self.gamestate.team_idx,
             runs: 0, // Filled later
-            outcome: OutcomeProbs::compute(pitcher, batter).sample(),
+            outcome: self
+                .gamestate
+                .matchup
+                .probs(pitcher, batter, park, edge)
+                .sample(&mut self.rng),
         })
     }
 
@@ -210,11 +292,28 @@ impl<'a> IntoIterator for SimbaState<'a> {
     }
 }
 
+#[derive(Clone, Debug, PartialEq)]
 pub struct SimResult {
     pub home_win_probability: Option<f64>,
+    pub expected_away_runs: f64,
+    pub expected_home_runs: f64,
+    pub iterations: usize,
+    pub seed: u64,
+    /// Final scores, and how many simulated games ended with each
+    pub scores: Vec<(Score, usize)>,
+}
+
+impl SimResult {
+    /// Share of the simulated games whose final score matches `event`
+    pub fn probability(&self, event: impl Fn(&Score) -> bool) -> f64 {
+        let (hits, total) = self.scores.iter().fold((0, 0), |(h, t), (s, n)| {
+            (h + if event(s) { *n } else { 0 }, t + n)
+        });
+        hits as f64 / total.max(1) as f64
+    }
 }
 
-#[derive(Clone, PartialEq, Eq, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Score {
     pub away: i32,
     pub home: i32,
@@ -238,6 +337,9 @@ impl Score {
 pub struct LiveTeam<'a> {
     pub team: &'a Team,
     pub current_batter: usize,
+    pub current_pitcher: Option<&'a PitcherStats>,
+    /// Workload of the pitcher currently in the game
+    pub pitcher_usage: PitcherUsage,
 }
 
 impl<'a> LiveTeam<'a> {
@@ -245,6 +347,8 @@ impl<'a> LiveTeam<'a> {
         LiveTeam {
             team,
             current_batter: 0,
+            current_pitcher: None,
+            pitcher_usage: PitcherUsage::default(),
         }
     }
 
@@ -257,7 +361,13 @@ impl<'a> LiveTeam<'a> {
     }
 
     pub fn pitcher(&self) -> &PitcherStats {
-        &self.team.starting_pitcher
+        self.current_pitcher.unwrap_or(&self.team.starting_pitcher)
+    }
+
+    /// Bring in a fresh reliever
+    fn relieve(&mut self) {
+        self.current_pitcher = Some(&RELIEVER);
+        self.pitcher_usage = PitcherUsage::default();
     }
 
     pub fn batter(&self) -> &BatterStats {
@@ -265,7 +375,153 @@ impl<'a> LiveTeam<'a> {
     }
 }
 
-enum Outcome {
+/// The bullpen, as a pitcher with league-average rates
+static RELIEVER: LazyLock<PitcherStats> = LazyLock::new(|| PitcherStats {
+    id: String::new(),
+    name: "Reliever".to_string(),
+    hand: "RHP".to_string(),
+    batters_faced: 1000,
+    bases_on_balls: 85,
+    hits: 215,
+    doubles: 43,
+    triples: 4,
+    homeruns: 30,
+    strikeouts: 225,
+});
+
+/// How much a pitcher has worked in the current game
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PitcherUsage {
+    pub batters_faced: i32,
+    /// Estimated from the outcomes of the plate appearances
+    pub pitch_count: i32,
+}
+
+impl PitcherUsage {
+    fn record(&mut self, outcome: Outcome) {
+        self.batters_faced += 1;
+        self.pitch_count += match outcome {
+            Outcome::Walk => 6,
+            Outcome::StrikeOut => 5,
+            _ => 3,
+        };
+    }
+
+    /// Completed trips through the batting order
+    pub fn times_through(&self) -> i32 {
+        self.batters_faced / 9
+    }
+}
+
+/// How pitchers get worse as they work longer
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fatigue {
+    /// Boost to batters for each completed trip through the order
+    pub times_through_order: f64,
+    /// Pitch count from which the pitcher tires
+    pub threshold: i32,
+    /// Boost to batters for each pitch past the threshold
+    pub per_pitch: f64,
+    /// Pitch count at which the pitcher makes way for a reliever
+    pub pull_at: i32,
+    /// Times through the order after which the pitcher makes way for a
+    /// reliever
+    pub max_times_through: i32,
+}
+
+impl Fatigue {
+    pub fn none() -> Fatigue {
+        Fatigue {
+            times_through_order: 0.0,
+            threshold: 0,
+            per_pitch: 0.0,
+            pull_at: i32::MAX,
+            max_times_through: i32::MAX,
+        }
+    }
+
+    /// Whether a pitcher with this usage is taken out of the game
+    pub fn spent(&self, usage: &PitcherUsage) -> bool {
+        usage.pitch_count >= self.pull_at || usage.times_through() >= self.max_times_through
+    }
+
+    /// Multiplier of the batter's walks and hits against a pitcher with
+    /// this usage
+    pub fn penalty(&self, usage: &PitcherUsage) -> f64 {
+        let tired = (usage.pitch_count - self.threshold).max(0);
+        1.0 + self.times_through_order * usage.times_through() as f64
+            + self.per_pitch * tired as f64
+    }
+}
+
+impl Default for Fatigue {
+    fn default() -> Fatigue {
+        Fatigue {
+            times_through_order: 0.03,
+            threshold: 90,
+            per_pitch: 0.002,
+            pull_at: 100,
+            max_times_through: 3,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum MatchupModel {
+    /// Geometric mean of the batter's and the pitcher's rates
+    Geometric,
+    /// Odds ratio of both rates to the league average
+    Log5,
+}
+
+/// How batter and pitcher stats combine into plate appearance outcomes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Matchup {
+    pub model: MatchupModel,
+    /// League-average plate appearances added to every batter's stats,
+    /// which pulls small samples toward the average
+    pub batter_regression: f64,
+    /// League-average batters faced added to every pitcher's stats
+    pub pitcher_regression: f64,
+}
+
+impl Matchup {
+    pub fn probs(
+        &self,
+        pitcher: &PitcherStats,
+        batter: &BatterStats,
+        park: &ParkFactors,
+        edge: f64,
+    ) -> OutcomeProbs {
+        let p = Rates::pitcher(pitcher).regress(
+            pitcher.batters_faced,
+            pitcher.hits,
+            self.pitcher_regression,
+        );
+        let b = Rates::batter(batter).regress(
+            batter.plate_appearances,
+            batter.hits,
+            self.batter_regression,
+        );
+        match self.model {
+            MatchupModel::Geometric => OutcomeProbs::combine(&p, &b, park, edge, |x, y, _| average(x, y)),
+            MatchupModel::Log5 => OutcomeProbs::combine(&p, &b, park, edge, log5),
+        }
+    }
+}
+
+impl Default for Matchup {
+    fn default() -> Matchup {
+        Matchup {
+            model: MatchupModel::Geometric,
+            batter_regression: 0.0,
+            pitcher_regression: 0.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
     Walk,
     Single,
     Double,
@@ -276,15 +532,92 @@ enum Outcome {
     FlyOut,
 }
 
-struct OutcomeProbs {
-    prob_walk: f64,
-    prob_single: f64,
-    prob_double: f64,
-    prob_triple: f64,
-    prob_homerun: f64,
-    prob_strikeout: f64,
-    prob_tagout: f64,
-    prob_flyout: f64,
+pub struct OutcomeProbs {
+    pub prob_walk: f64,
+    pub prob_single: f64,
+    pub prob_double: f64,
+    pub prob_triple: f64,
+    pub prob_homerun: f64,
+    pub prob_strikeout: f64,
+    pub prob_tagout: f64,
+    pub prob_flyout: f64,
+}
+
+/// Raw rates of a batter or pitcher: walks, strikeouts and hits per plate
+/// appearance, and extra-base hits per hit
+pub struct Rates {
+    pub walk: f64,
+    pub strikeout: f64,
+    pub hit: f64,
+    pub double: f64,
+    pub triple: f64,
+    pub homerun: f64,
+}
+
+impl Rates {
+    pub fn batter(batter: &BatterStats) -> Rates {
+        Rates {
+            walk: div(batter.bases_on_balls, batter.plate_appearances),
+            strikeout: div(batter.strikeouts, batter.plate_appearances),
+            hit: div(batter.hits, batter.plate_appearances),
+            double: div(batter.doubles, batter.hits),
+            triple: div(batter.triples, batter.hits),
+            homerun: div(batter.homeruns, batter.hits),
+        }
+    }
+
+    /// Rates of a typical MLB plate appearance
+    pub fn league() -> Rates {
+        Rates {
+            walk: 0.085,
+            strikeout: 0.225,
+            hit: 0.215,
+            double: 0.2,
+            triple: 0.017,
+            homerun: 0.14,
+        }
+    }
+
+    /// Mix in `k` league-average plate appearances with the `n` ones,
+    /// with `hits` hits, that these rates come from
+    fn regress(self, n: i32, hits: i32, k: f64) -> Rates {
+        if k == 0.0 {
+            return self;
+        }
+        let league = Rates::league();
+        let mix = |rate: f64, n: i32, average: f64, k: f64| {
+            let observed = if n > 0 { rate * n as f64 } else { 0.0 };
+            (observed + average * k) / (n.max(0) as f64 + k)
+        };
+        let k_hits = k * league.hit;
+        Rates {
+            walk: mix(self.walk, n, league.walk, k),
+            strikeout: mix(self.strikeout, n, league.strikeout, k),
+            hit: mix(self.hit, n, league.hit, k),
+            double: mix(self.double, hits, league.double, k_hits),
+            triple: mix(self.triple, hits, league.triple, k_hits),
+            homerun: mix(self.homerun, hits, league.homerun, k_hits),
+        }
+    }
+
+    pub fn pitcher(pitcher: &PitcherStats) -> Rates {
+        Rates {
+            walk: div(pitcher.bases_on_balls, pitcher.batters_faced),
+            strikeout: div(pitcher.strikeouts, pitcher.batters_faced),
+            hit: div(pitcher.hits, pitcher.batters_faced),
+            double: div(pitcher.doubles, pitcher.hits),
+            triple: div(pitcher.triples, pitcher.hits),
+            homerun: div(pitcher.homeruns, pitcher.hits),
+        }
+    }
+}
+
+/// Rate of a batter with rate `x` against a pitcher with rate `y`, in a
+/// league where the rate is `league`
+fn log5(x: f64, y: f64, league: f64) -> f64 {
+    let odds = |p: f64| p.min(0.999) / (1.0 - p.min(0.999));
+    let o = odds(x) * odds(y) / odds(league);
+    o / (1.0 + o)
 }
 
 fn average(x: f64, y: f64) -> f64 {
@@ -300,78 +633,332 @@ fn div(x: i32, y: i32) -> f64 {
 }
 
 impl OutcomeProbs {
-    fn compute(pitcher: &PitcherStats, batter: &BatterStats) -> OutcomeProbs {
-        let prob_walk_p = div(pitcher.bases_on_balls, pitcher.batters_faced);
-        let prob_walk_b = div(batter.bases_on_balls, batter.plate_appearances);
-        let prob_walk = average(prob_walk_p, prob_walk_b);
-
-        let prob_strikeout_p = div(pitcher.strikeouts, pitcher.batters_faced);
-        let prob_strikeout_b = div(batter.strikeouts, batter.plate_appearances);
-        let prob_strikeout = average(prob_strikeout_p, prob_strikeout_b);
-
-        let prob_hit_p = div(pitcher.hits, pitcher.batters_faced);
-        let prob_hit_b = div(batter.hits, batter.plate_appearances);
-        let prob_hit = average(prob_hit_p, prob_hit_b);
-
-        let prob_bip_out = 1.0 - prob_hit - prob_walk - prob_strikeout;
-
-        let prob_2b_p = div(pitcher.doubles, pitcher.hits);
-        let prob_2b_b = div(batter.doubles, batter.hits);
-        let prob_2b = average(prob_2b_p, prob_2b_b);
-        let prob_3b_p = div(pitcher.triples, pitcher.hits);
-        let prob_3b_b = div(batter.triples, batter.hits);
-        let prob_3b = average(prob_3b_p, prob_3b_b);
-        let prob_hr_p = div(pitcher.homeruns, pitcher.hits);
-        let prob_hr_b = div(batter.homeruns, batter.hits);
-        let prob_hr = average(prob_hr_p, prob_hr_b);
+    /// Outcome probabilities of a plate appearance in `park`. The batter's
+    /// walks and hits are multiplied by `edge` and strikeouts divided by it,
+    /// e.g. for home-field advantage or a tired pitcher.
+    pub fn compute(
+        pitcher: &PitcherStats,
+        batter: &BatterStats,
+        park: &ParkFactors,
+        edge: f64,
+    ) -> OutcomeProbs {
+        Matchup::default().probs(pitcher, batter, park, edge)
+    }
+
+    /// Outcome probabilities from the rates of both players, combined
+    /// rate by rate by `average` along with the league rate
+    fn combine(
+        p: &Rates,
+        b: &Rates,
+        park: &ParkFactors,
+        edge: f64,
+        average: impl Fn(f64, f64, f64) -> f64,
+    ) -> OutcomeProbs {
+        let l = Rates::league();
+        let prob_walk = average(p.walk, b.walk, l.walk) * park.walk * edge;
+        let prob_strikeout = average(p.strikeout, b.strikeout, l.strikeout) * park.strikeout / edge;
+        let prob_hit = average(p.hit, b.hit, l.hit) * edge;
+
+        let prob_2b = average(p.double, b.double, l.double);
+        let prob_3b = average(p.triple, b.triple, l.triple);
+        let prob_hr = average(p.homerun, b.homerun, l.homerun);
         let prob_1b = 1.0 - prob_2b - prob_3b - prob_hr;
 
+        let prob_single = prob_hit * prob_1b * park.single;
+        let prob_double = prob_hit * prob_2b * park.double;
+        let prob_triple = prob_hit * prob_3b * park.triple;
+        let prob_homerun = prob_hit * prob_hr * park.homerun;
+
+        // Extreme parks could push the other outcomes past certainty
+        let not_bip = prob_walk
+            + prob_strikeout
+            + prob_single
+            + prob_double
+            + prob_triple
+            + prob_homerun;
+        let scale = if not_bip > 1.0 { 1.0 / not_bip } else { 1.0 };
+        let prob_bip_out = 1.0 - not_bip * scale;
+
         let prob_flyout = 0.5 * prob_bip_out;
         let prob_tagout = 0.5 * prob_bip_out;
 
-        let prob_single = prob_hit * prob_1b;
-        let prob_double = prob_hit * prob_2b;
-        let prob_triple = prob_hit * prob_3b;
-        let prob_homerun = prob_hit * prob_hr;
-
         OutcomeProbs {
-            prob_walk,
-            prob_single,
-            prob_double,
-            prob_triple,
-            prob_homerun,
-            prob_strikeout,
+            prob_walk: prob_walk * scale,
+            prob_single: prob_single * scale,
+            prob_double: prob_double * scale,
+            prob_triple: prob_triple * scale,
+            prob_homerun: prob_homerun * scale,
+            prob_strikeout: prob_strikeout * scale,
             prob_tagout,
             prob_flyout,
         }
     }
 
-    fn sample(&self) -> Outcome {
+    fn sample<R: Rng>(&self, rng: &mut R) -> Outcome {
         loop {
-            if rand::random::<f64>() < self.prob_walk {
+            if rng.gen::<f64>() < self.prob_walk {
                 return Outcome::Walk;
             }
-            if rand::random::<f64>() < self.prob_single {
+            if rng.gen::<f64>() < self.prob_single {
                 return Outcome::Single;
             }
-            if rand::random::<f64>() < self.prob_double {
+            if rng.gen::<f64>() < self.prob_double {
                 return Outcome::Double;
             }
-            if rand::random::<f64>() < self.prob_triple {
+            if rng.gen::<f64>() < self.prob_triple {
                 return Outcome::Triple;
             }
-            if rand::random::<f64>() < self.prob_homerun {
+            if rng.gen::<f64>() < self.prob_homerun {
                 return Outcome::HomeRun;
             }
-            if rand::random::<f64>() < self.prob_strikeout {
+            if rng.gen::<f64>() < self.prob_strikeout {
                 return Outcome::StrikeOut;
             }
-            if rand::random::<f64>() < self.prob_tagout {
+            if rng.gen::<f64>() < self.prob_tagout {
                 return Outcome::TagOut;
             }
-            if rand::random::<f64>() < self.prob_flyout {
+            if rng.gen::<f64>() < self.prob_flyout {
                 return Outcome::FlyOut;
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{batter, pitcher, team};
+
+    fn situation(inning: i32, top: bool, outs: i32, away: i32, home: i32) -> Situation {
+        Situation {
+            inning,
+            top,
+            outs,
+            bases: [false, true, false],
+            away_score: away,
+            home_score: home,
+            batting_order: [4, 7],
+            pitchers: [None, Some(pitcher("2"))],
+            batters_faced: [22, 6],
+            pitch_counts: [88, 21],
+        }
+    }
+
+    #[test]
+    fn from_situation_resumes() {
+        let (away, home) = (team("AWY"), team("HOM"));
+        let sit = situation(6, false, 1, 3, 2);
+        let gs = GameState::from_situation(&away, &home, &sit);
+        assert!(gs.live);
+        assert_eq!(gs.inning, 6);
+        assert_eq!(gs.team_idx, 1);
+        assert_eq!(gs.outs, 1);
+        assert_eq!(gs.bases, [false, true, false]);
+        assert_eq!((gs.score.away, gs.score.home), (3, 2));
+        assert_eq!(gs.teams[0].current_batter, 4);
+        assert_eq!(gs.teams[1].current_batter, 7);
+        assert!(gs.teams[0].current_pitcher.is_none());
+        assert!(gs.teams[1].current_pitcher.is_some());
+    }
+
+    #[test]
+    fn from_situation_settles() {
+        let (away, home) = (team("AWY"), team("HOM"));
+
+        // Three outs in the top of the 4th: bottom half is up
+        let sit = situation(4, true, 3, 0, 0);
+        let gs = GameState::from_situation(&away, &home, &sit);
+        assert!(gs.live);
+        assert_eq!((gs.inning, gs.team_idx, gs.outs), (4, 1, 0));
+        assert_eq!(gs.bases, [false; 3]);
+
+        // Home leads after the top of the 9th: game over
+        let sit = situation(9, true, 3, 1, 2);
+        assert!(!GameState::from_situation(&away, &home, &sit).live);
+
+        // Tied after the bottom of the 9th: extra innings
+        let sit = situation(9, false, 3, 2, 2);
+        let gs = GameState::from_situation(&away, &home, &sit);
+        assert!(gs.live);
+        assert_eq!((gs.inning, gs.team_idx), (10, 0));
+    }
+
+    #[test]
+    fn simulate_seeded() {
+        let (away, home) = (team("AWY"), team("HOM"));
+        let gs = GameState::new(&away, &home);
+        let cfg = SimbaConfig {
+            n_iter: 200,
+            seed: Some(42),
+        };
+        let a = cfg.run(&gs).unwrap();
+        let b = cfg.run(&gs).unwrap();
+        assert_eq!(a.seed, 42);
+        assert_eq!(a.iterations, 200);
+        assert_eq!(a.home_win_probability, b.home_win_probability);
+
+        let none = SimbaConfig { n_iter: 0, ..cfg }.run(&gs).unwrap();
+        assert_eq!(none.home_win_probability, None);
+        assert_eq!(none.expected_home_runs, 0.0);
+    }
+
+    #[test]
+    fn simulate_from_situation() {
+        let (away, home) = (team("AWY"), team("HOM"));
+        let sit = situation(9, true, 2, 0, 15);
+        let gs = GameState::from_situation(&away, &home, &sit);
+        let hwp = SimbaConfig::default()
+            .run(&gs)
+            .unwrap()
+            .home_win_probability
+            .unwrap();
+        assert!(hwp > 0.99);
+    }
+
+    #[test]
+    fn outcome_probs_venue() {
+        let total = |p: &OutcomeProbs| {
+            p.prob_walk
+                + p.prob_single
+                + p.prob_double
+                + p.prob_triple
+                + p.prob_homerun
+                + p.prob_strikeout
+                + p.prob_tagout
+                + p.prob_flyout
+        };
+        let neutral_park = ParkFactors::neutral();
+        let neutral = OutcomeProbs::compute(&pitcher("2"), &batter("1"), &neutral_park, 1.0);
+
+        let coors = crate::park::ParkTable::bundled().get(Some("19"));
+        let probs = OutcomeProbs::compute(&pitcher("2"), &batter("1"), &coors, 1.0);
+        assert!(probs.prob_homerun > neutral.prob_homerun);
+        assert!(probs.prob_strikeout < neutral.prob_strikeout);
+        assert!((total(&probs) - 1.0).abs() < 1e-12);
+
+        let venue = Venue {
+            home_advantage: 0.05,
+            ..Venue::neutral()
+        };
+        let home = OutcomeProbs::compute(&pitcher("2"), &batter("1"), &neutral_park, venue.edge(true));
+        let away = OutcomeProbs::compute(&pitcher("2"), &batter("1"), &neutral_park, venue.edge(false));
+        assert!(home.prob_single > neutral.prob_single);
+        assert!(away.prob_single < neutral.prob_single);
+        assert!(home.prob_strikeout < away.prob_strikeout);
+
+        // Outcomes are rescaled rather than exceeding certainty
+        let mut park = ParkFactors::neutral();
+        park.walk = 5.0;
+        park.single = 5.0;
+        let probs = OutcomeProbs::compute(&pitcher("2"), &batter("1"), &park, 1.0);
+        assert!(probs.prob_flyout.abs() < 1e-12);
+        assert!((total(&probs) - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn matchup_models() {
+        let park = ParkFactors::neutral();
+        // Against a league-average pitcher, log5 keeps the batter's rate
+        assert!((log5(0.3, 0.215, 0.215) - 0.3).abs() < 1e-12);
+        assert!(log5(0.3, 0.25, 0.215) > 0.3);
+
+        let geometric = Matchup::default().probs(&pitcher("2"), &batter("1"), &park, 1.0);
+        let log5 = Matchup {
+            model: MatchupModel::Log5,
+            ..Matchup::default()
+        }
+        .probs(&pitcher("2"), &batter("1"), &park, 1.0);
+        // Both strike out less than the league, which log5 compounds
+        assert!(log5.prob_strikeout < geometric.prob_strikeout);
+
+        // A tiny sample is pulled most of the way to the league average
+        let rookie = BatterStats {
+            plate_appearances: 10,
+            bases_on_balls: 1,
+            hits: 4,
+            doubles: 0,
+            triples: 0,
+            homeruns: 2,
+            strikeouts: 2,
+            ..batter("1")
+        };
+        let raw = Matchup::default().probs(&pitcher("2"), &rookie, &park, 1.0);
+        let regressed = Matchup {
+            batter_regression: 200.0,
+            ..Matchup::default()
+        }
+        .probs(&pitcher("2"), &rookie, &park, 1.0);
+        assert!(regressed.prob_homerun < raw.prob_homerun / 2.0);
+    }
+
+    #[test]
+    fn pitcher_fatigue() {
+        let (away, home) = (team("AWY"), team("HOM"));
+        let mut gs = GameState::new(&away, &home);
+        for outcome in [Outcome::Walk, Outcome::StrikeOut, Outcome::FlyOut] {
+            gs.transition(&Play {
+                team: 0,
+                runs: 0,
+                outcome,
+            });
+        }
+        // The home pitcher faced the visitors
+        let usage = gs.teams[1].pitcher_usage;
+        assert_eq!(usage.batters_faced, 3);
+        assert_eq!(usage.pitch_count, 14);
+        assert_eq!(gs.teams[0].pitcher_usage, PitcherUsage::default());
+
+        let fatigue = Fatigue::default();
+        assert_eq!(fatigue.penalty(&usage), 1.0);
+        let third_time = PitcherUsage {
+            batters_faced: 18,
+            pitch_count: 80,
+        };
+        assert!((fatigue.penalty(&third_time) - 1.06).abs() < 1e-12);
+        let tired = PitcherUsage {
+            batters_faced: 27,
+            pitch_count: 110,
+        };
+        assert!((fatigue.penalty(&tired) - (1.0 + 0.09 + 0.04)).abs() < 1e-12);
+        assert_eq!(Fatigue::none().penalty(&tired), 1.0);
+
+        let sit = situation(6, true, 0, 2, 1);
+        let gs = GameState::from_situation(&away, &home, &sit);
+        assert_eq!(gs.teams[1].pitcher_usage.pitch_count, 21);
+    }
+
+    #[test]
+    fn pitcher_relieved() {
+        let (away, home) = (team("AWY"), team("HOM"));
+        let fatigue = Fatigue::default();
+        assert!(!fatigue.spent(&PitcherUsage { batters_faced: 26, pitch_count: 99 }));
+        assert!(fatigue.spent(&PitcherUsage { batters_faced: 20, pitch_count: 100 }));
+        assert!(fatigue.spent(&PitcherUsage { batters_faced: 27, pitch_count: 80 }));
+        assert!(!Fatigue::none().spent(&PitcherUsage { batters_faced: 40, pitch_count: 150 }));
+
+        // A starter at 140 pitches does not take the mound for the 9th
+        let mut sit = situation(9, true, 0, 2, 1);
+        sit.pitchers = [None, None];
+        sit.pitch_counts = [60, 140];
+        let mut gs = GameState::from_situation(&away, &home, &sit);
+        gs.fatigue = fatigue;
+        let mut state = SimbaState::new(gs, StdRng::seed_from_u64(1));
+        state.transition().unwrap();
+        assert_eq!(state.gamestate.teams[1].pitcher().name, "Reliever");
+
+        // Nor does any starter over a whole game
+        for seed in 0..20 {
+            let mut gs = GameState::new(&away, &home);
+            gs.fatigue = fatigue;
+            let mut state = SimbaState::new(gs, StdRng::seed_from_u64(seed));
+            while state.transition().unwrap().is_some() {
+                for team in &state.gamestate.teams {
+                    if team.current_pitcher.is_none() {
+                        assert!(team.pitcher_usage.pitch_count < fatigue.pull_at + 6);
+                        assert!(state.gamestate.inning < 9 || team.pitcher_usage.pitch_count < 140);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,279 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::thread;
+use std::time::Duration;
+
+use chrono_tz::Tz;
+use log;
+
+use crate::{
+    config::Config,
+    filter::Slate,
+    mlbstats::{self, Game, Situation, Team},
+    output::Style,
+    simulate, GameLine,
+};
+
+/// What a row of the watch display currently shows
+enum RowState {
+    Fetching,
+    Postponed,
+    MissingLineups,
+    FrontendError,
+    BackendError,
+    /// Home win probability, and the expected final runs of each team
+    Prediction(f64, [f64; 2]),
+}
+
+struct Row {
+    game: Game,
+    teams: Option<(Team, Team)>,
+    state: RowState,
+    detail: Option<String>,
+    /// Home win probability at the previous refresh
+    hwp: Option<f64>,
+    /// Set once the game is over, after which it is no longer polled
+    locked: bool,
+}
+
+impl Row {
+    fn new(game: Game) -> Row {
+        Row {
+            game,
+            teams: None,
+            state: RowState::Fetching,
+            detail: None,
+            hwp: None,
+            locked: false,
+        }
+    }
+
+    fn refresh(&mut self, cfg: &Config, swing: f64) {
+        if self.game.status == "Postponed" {
+            self.state = RowState::Postponed;
+            self.locked = true;
+            return;
+        }
+
+        // Final games need no simulation, only the final score
+        if self.game.is_final() {
+            match mlbstats::feed(cfg, &self.game.game_id).map(|feed| feed.score()) {
+                Ok(Some((score, inning))) => {
+                    // A game called while tied, e.g. to be resumed later,
+                    // has no winner yet
+                    let hwp = match score[1].cmp(&score[0]) {
+                        Ordering::Greater => 1.0,
+                        Ordering::Less => 0.0,
+                        Ordering::Equal => 0.5,
+                    };
+                    let runs = [score[0] as f64, score[1] as f64];
+                    self.state = RowState::Prediction(hwp, runs);
+                    self.detail = Some(describe_final(score, inning));
+                    self.locked = true;
+                }
+                Ok(None) => self.state = RowState::FrontendError,
+                Err(e) => {
+                    log::error!(target: "watch", "{}: {}", self.game.game_id, e);
+                    self.state = RowState::FrontendError;
+                }
+            }
+            return;
+        }
+
+        // Lineups are fetched once, and retried until they are posted
+        if self.teams.is_none() {
+            match mlbstats::teams(cfg, &self.game.game_id) {
+                Ok((Some(away), Some(home))) => self.teams = Some((away, home)),
+                Ok(_) => {
+                    self.state = RowState::MissingLineups;
+                    return;
+                }
+                Err(e) => {
+                    log::error!(target: "watch", "{}: {}", self.game.game_id, e);
+                    self.state = RowState::FrontendError;
+                    return;
+                }
+            }
+        }
+        let (away, home) = self.teams.as_ref().unwrap();
+
+        let situation = if self.game.is_live() {
+            match mlbstats::feed(cfg, &self.game.game_id).and_then(|feed| feed.situation(cfg)) {
+                Ok(s) => s,
+                Err(e) => {
+                    log::error!(target: "watch", "{}: {}", self.game.game_id, e);
+                    self.state = RowState::FrontendError;
+                    return;
+                }
+            }
+        } else {
+            None
+        };
+
+        let (hwp, runs) = match simulate(cfg, &self.game, away, home, situation.as_ref()) {
+            Ok(res) => (
+                res.home_win_probability,
+                [res.expected_away_runs, res.expected_home_runs],
+            ),
+            Err(e) => {
+                log::error!(target: "watch", "{}: {}", self.game.game_id, e);
+                (None, [0.0; 2])
+            }
+        };
+        let Some(hwp) = hwp else {
+            self.state = RowState::BackendError;
+            return;
+        };
+
+        let mut detail = situation.map(|s| describe(&s)).unwrap_or_default();
+        if let Some(prev) = self.hwp {
+            let delta = hwp - prev;
+            if delta.abs() >= swing {
+                // Point towards the team that gained, away on the left
+                let arrow = if delta > 0.0 { '▶' } else { '◀' };
+                detail.push_str(&format!(" {} {:.0}%", arrow, 100.0 * delta.abs()));
+            }
+        }
+        self.state = RowState::Prediction(hwp, runs);
+        self.detail = Some(detail);
+        self.hwp = Some(hwp);
+    }
+
+    fn line(&self, style: Style, tz: Option<Tz>) -> GameLine<'_> {
+        let mut gline = GameLine::new(&self.game, style);
+        gline.first_pitch(tz);
+        match self.state {
+            RowState::Fetching => gline.fetching(),
+            RowState::Postponed => gline.postponed(),
+            RowState::MissingLineups => gline.missing_lineups(),
+            RowState::FrontendError => gline.frontend_error(),
+            RowState::BackendError => gline.backend_error(),
+            RowState::Prediction(hwp, runs) => {
+                gline.expected_runs(runs);
+                gline.prediction(Some(hwp));
+                if self.locked {
+                    gline.settled();
+                }
+            }
+        }
+        // Padded so that a shorter detail overwrites a longer one
+        gline.detail(format!("{:<28}", self.detail.clone().unwrap_or_default()));
+        gline
+    }
+}
+
+/// Score and inning of a game in progress, e.g. "3-2 Top 7, 1 out"
+pub(crate) fn describe(situation: &Situation) -> String {
+    format!(
+        "{}-{} {} {}, {} out",
+        situation.away_score,
+        situation.home_score,
+        if situation.top { "Top" } else { "Bot" },
+        situation.inning,
+        situation.outs
+    )
+}
+
+/// Final score, away first, e.g. "4-3 Final", "4-3 F/10" or "2-2 Tied/7"
+fn describe_final(score: [i32; 2], inning: i32) -> String {
+    let runs = format!("{}-{}", score[0], score[1]);
+    if score[0] == score[1] {
+        format!("{} Tied/{}", runs, inning)
+    } else if inning != 9 {
+        // Extra innings, or a game called early
+        format!("{} F/{}", runs, inning)
+    } else {
+        format!("{} Final", runs)
+    }
+}
+
+/// Rewrite the rows in place, moving back up over the ones drawn last time
+fn redraw(rows: &[Row], drawn: usize, style: Style, tz: Option<Tz>) -> usize {
+    if drawn > 0 {
+        print!("\x1B[{}F", drawn);
+    }
+    for row in rows {
+        row.line(style, tz).finalize();
+    }
+    rows.len()
+}
+
+pub fn watch(cfg: &Config, interval: Duration, swing: f64) -> Result<(), String> {
+    let style = Style::detect(cfg.color, cfg.display, cfg.colormap);
+    let slate = Slate::new(cfg)?;
+    let mut rows: Vec<Row> = slate.games(cfg)?.into_iter().map(Row::new).collect();
+    if rows.is_empty() {
+        crate::no_games();
+    }
+    let mut drawn = 0;
+    if style.interactive {
+        drawn = redraw(&rows, drawn, style, cfg.tz);
+    }
+
+    loop {
+        for idx in 0..rows.len() {
+            if rows[idx].locked {
+                continue;
+            }
+            rows[idx].refresh(cfg, swing);
+            if style.interactive {
+                drawn = redraw(&rows, drawn, style, cfg.tz);
+            }
+        }
+
+        // Without a terminal to redraw on, print each refresh as a block
+        if !style.interactive {
+            redraw(&rows, 0, style, cfg.tz);
+            println!();
+        }
+
+        if rows.iter().all(|r| r.locked) {
+            return Ok(());
+        }
+        thread::sleep(interval);
+
+        // Pick up status changes, e.g. games starting or ending
+        match slate.games(cfg) {
+            Ok(games) => {
+                let mut games: HashMap<String, Game> =
+                    games.into_iter().map(|g| (g.game_id.clone(), g)).collect();
+                for row in rows.iter_mut() {
+                    if let Some(game) = games.remove(&row.game.game_id) {
+                        row.game = game;
+                    }
+                }
+            }
+            Err(e) => log::error!(target: "watch", "{}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn situation(inning: i32, top: bool) -> Situation {
+        Situation {
+            inning,
+            top,
+            outs: 2,
+            bases: [false; 3],
+            away_score: 4,
+            home_score: 3,
+            batting_order: [0, 0],
+            pitchers: [None, None],
+            batters_faced: [0, 0],
+            pitch_counts: [0, 0],
+        }
+    }
+
+    #[test]
+    fn test_describe() {
+        assert_eq!(describe(&situation(7, true)), "4-3 Top 7, 2 out");
+        assert_eq!(describe(&situation(8, false)), "4-3 Bot 8, 2 out");
+        assert_eq!(describe_final([4, 3], 9), "4-3 Final");
+        assert_eq!(describe_final([4, 3], 11), "4-3 F/11");
+        assert_eq!(describe_final([1, 0], 7), "1-0 F/7");
+        assert_eq!(describe_final([2, 2], 7), "2-2 Tied/7");
+    }
+}